@@ -0,0 +1,3 @@
+DROP TABLE long_term_memories;
+DROP TABLE posts;
+DROP TABLE users;
@@ -0,0 +1,33 @@
+CREATE TABLE users (
+    id SERIAL PRIMARY KEY,
+    username VARCHAR NOT NULL UNIQUE,
+    email VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('users');
+
+CREATE TABLE posts (
+    id SERIAL PRIMARY KEY,
+    content TEXT NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    username VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    post_type VARCHAR NOT NULL,
+    comment_count INTEGER NOT NULL DEFAULT 0,
+    image_path VARCHAR,
+    tweet_id VARCHAR
+);
+
+CREATE INDEX posts_created_at_idx ON posts (created_at DESC);
+SELECT diesel_manage_updated_at('posts');
+
+CREATE TABLE long_term_memories (
+    id SERIAL PRIMARY KEY,
+    content TEXT NOT NULL,
+    embedding REAL[] NOT NULL,
+    significance_score REAL NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
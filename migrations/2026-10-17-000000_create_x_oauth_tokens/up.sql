@@ -0,0 +1,14 @@
+-- OAuth 2.0 tokens for the agent's X account. Access and refresh tokens are
+-- stored encrypted (ChaCha20-Poly1305) in `ciphertext`.
+CREATE TABLE x_oauth_tokens (
+    id SERIAL PRIMARY KEY,
+    username VARCHAR NOT NULL UNIQUE,
+    nonce BYTEA NOT NULL,
+    ciphertext BYTEA NOT NULL,
+    scope TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('x_oauth_tokens');
@@ -0,0 +1,2 @@
+DROP TABLE handled_tweets;
+DROP TABLE x_checkpoints;
@@ -0,0 +1,15 @@
+-- Newest tweet id seen per polled stream, e.g. `mentions:<user id>`
+CREATE TABLE x_checkpoints (
+    name VARCHAR PRIMARY KEY,
+    since_id VARCHAR NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+SELECT diesel_manage_updated_at('x_checkpoints');
+
+-- Tweets the agent has taken on, so each is answered at most once
+CREATE TABLE handled_tweets (
+    tweet_id VARCHAR PRIMARY KEY,
+    kind VARCHAR NOT NULL,
+    handled_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,5 @@
+DROP INDEX posts_thread_id_idx;
+
+ALTER TABLE posts
+    DROP COLUMN thread_position,
+    DROP COLUMN thread_id;
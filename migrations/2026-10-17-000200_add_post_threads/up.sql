@@ -0,0 +1,7 @@
+-- Parts of a multi-tweet thread share the first tweet's id as thread_id and
+-- are numbered from 1 by thread_position
+ALTER TABLE posts
+    ADD COLUMN thread_id VARCHAR,
+    ADD COLUMN thread_position INTEGER;
+
+CREATE INDEX posts_thread_id_idx ON posts (thread_id, thread_position);
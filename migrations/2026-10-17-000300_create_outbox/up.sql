@@ -0,0 +1,31 @@
+-- Posts are written here before they are sent. Each row is one tweet; the
+-- later parts of a thread point at the part they reply to via parent_id.
+-- status moves pending -> sending -> sent, or to dead once X rejects the
+-- post or it runs out of attempts.
+CREATE TABLE outbox (
+    id SERIAL PRIMARY KEY,
+    idempotency_key VARCHAR NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    username VARCHAR NOT NULL,
+    content TEXT NOT NULL,
+    post_type VARCHAR NOT NULL,
+    reply_to_id VARCHAR,
+    parent_id INTEGER REFERENCES outbox(id),
+    thread_position INTEGER,
+    image_path VARCHAR,
+    alt_text TEXT,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    claimed_at TIMESTAMPTZ,
+    last_error TEXT,
+    tweet_id VARCHAR,
+    thread_id VARCHAR,
+    post_id INTEGER REFERENCES posts(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX outbox_due_idx ON outbox (next_attempt_at) WHERE status IN ('pending', 'sending');
+
+SELECT diesel_manage_updated_at('outbox');
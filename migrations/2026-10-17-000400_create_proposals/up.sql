@@ -0,0 +1,25 @@
+-- Generated posts, replies and transfers held for review in approval mode.
+-- status moves pending -> approved (by a reviewer or an auto-approve rule) or
+-- rejected; the agent then claims approved rows as executing and records
+-- executed or failed with the outcome in result.
+CREATE TABLE proposals (
+    id SERIAL PRIMARY KEY,
+    category VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'pending',
+    content TEXT NOT NULL,
+    -- What the agent generated, kept when a reviewer edits content
+    original_content TEXT,
+    reply_to_id VARCHAR,
+    to_address VARCHAR,
+    amount_eth DOUBLE PRECISION,
+    decided_by VARCHAR,
+    decided_at TIMESTAMPTZ,
+    reason TEXT,
+    result TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX proposals_status_idx ON proposals (status, id);
+
+SELECT diesel_manage_updated_at('proposals');
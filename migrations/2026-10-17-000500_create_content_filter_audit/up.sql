@@ -0,0 +1,16 @@
+-- Every content filter decision on a generated post or reply, including
+-- the ones that let it through unchanged
+CREATE TABLE content_filter_audit (
+    id SERIAL PRIMARY KEY,
+    proposal_id INTEGER REFERENCES proposals(id),
+    category VARCHAR NOT NULL,
+    decision VARCHAR NOT NULL,
+    content TEXT NOT NULL,
+    -- Set when the decision was to rewrite
+    rewritten TEXT,
+    -- [{filter, reason, action, spans}] for each match
+    findings JSONB NOT NULL DEFAULT '[]',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX content_filter_audit_decision_idx ON content_filter_audit (decision, created_at);
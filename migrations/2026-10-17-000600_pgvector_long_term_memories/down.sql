@@ -0,0 +1,6 @@
+DROP INDEX long_term_memories_embedding_idx;
+
+ALTER TABLE long_term_memories
+    ALTER COLUMN embedding TYPE REAL[] USING embedding::real[];
+
+DROP EXTENSION IF EXISTS vector;
@@ -0,0 +1,8 @@
+CREATE EXTENSION IF NOT EXISTS vector;
+
+-- Must match embeddings.dimensions
+ALTER TABLE long_term_memories
+    ALTER COLUMN embedding TYPE vector(1536) USING embedding::vector(1536);
+
+CREATE INDEX long_term_memories_embedding_idx
+    ON long_term_memories USING hnsw (embedding vector_cosine_ops);
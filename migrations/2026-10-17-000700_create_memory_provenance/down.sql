@@ -0,0 +1 @@
+DROP TABLE memory_provenance;
@@ -0,0 +1,13 @@
+-- The memories a consolidated memory was merged from. Sources are deleted by
+-- the merge, so their content is kept here; when a consolidated memory is
+-- merged again its rows move to the new memory.
+CREATE TABLE memory_provenance (
+    id SERIAL PRIMARY KEY,
+    memory_id INTEGER NOT NULL REFERENCES long_term_memories(id) ON DELETE CASCADE,
+    source_id INTEGER NOT NULL,
+    source_content TEXT NOT NULL,
+    source_created_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX memory_provenance_memory_id_idx ON memory_provenance (memory_id);
@@ -0,0 +1,6 @@
+DROP INDEX long_term_memories_active_idx;
+
+ALTER TABLE long_term_memories
+    DROP COLUMN archived_at,
+    DROP COLUMN last_accessed_at,
+    DROP COLUMN access_count;
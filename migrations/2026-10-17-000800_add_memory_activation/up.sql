@@ -0,0 +1,8 @@
+-- Recall history for decay and reinforcement. Forgotten memories are
+-- archived rather than deleted, so their provenance survives.
+ALTER TABLE long_term_memories
+    ADD COLUMN access_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN last_accessed_at TIMESTAMPTZ,
+    ADD COLUMN archived_at TIMESTAMPTZ;
+
+CREATE INDEX long_term_memories_active_idx ON long_term_memories (id) WHERE archived_at IS NULL;
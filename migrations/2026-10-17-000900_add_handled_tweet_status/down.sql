@@ -0,0 +1,7 @@
+DROP INDEX handled_tweets_pending_idx;
+
+ALTER TABLE handled_tweets
+    DROP COLUMN tweet,
+    DROP COLUMN claimed_at,
+    DROP COLUMN attempts,
+    DROP COLUMN status;
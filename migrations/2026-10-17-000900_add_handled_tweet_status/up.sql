@@ -0,0 +1,10 @@
+-- Mentions are recorded as pending when polled and only marked answered once
+-- a reply is submitted, so one whose answer failed or was cut short by
+-- shutdown is handed out again. Rows from before this were already answered.
+ALTER TABLE handled_tweets
+    ADD COLUMN status VARCHAR NOT NULL DEFAULT 'answered',
+    ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN claimed_at TIMESTAMPTZ,
+    ADD COLUMN tweet JSONB;
+
+CREATE INDEX handled_tweets_pending_idx ON handled_tweets (kind, tweet_id) WHERE status = 'pending';
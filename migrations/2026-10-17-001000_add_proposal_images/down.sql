@@ -0,0 +1,3 @@
+ALTER TABLE proposals
+    DROP COLUMN alt_text,
+    DROP COLUMN image_path;
@@ -0,0 +1,5 @@
+-- Posts with media go through review like any other, so the image to attach
+-- travels with the proposal.
+ALTER TABLE proposals
+    ADD COLUMN image_path VARCHAR,
+    ADD COLUMN alt_text TEXT;
@@ -0,0 +1,3 @@
+ALTER TABLE x_checkpoints
+    DROP COLUMN newest_id,
+    DROP COLUMN until_id;
@@ -0,0 +1,6 @@
+-- A poll that hits its page limit leaves since_id where it was and records how
+-- far back it got, so the next poll fetches the older tweets below until_id
+-- before moving since_id up to the newest tweet seen.
+ALTER TABLE x_checkpoints
+    ADD COLUMN until_id VARCHAR,
+    ADD COLUMN newest_id VARCHAR;
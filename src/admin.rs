@@ -0,0 +1,352 @@
+//! Local admin API and CLI for reviewing proposals held in approval mode.
+//!
+//! Routes, all JSON:
+//! - `GET /proposals?status=pending` lists proposals (`status=all` for every one)
+//! - `GET /proposals/:id`
+//! - `POST /proposals/:id/approve`
+//! - `POST /proposals/:id/edit` with `{"content": "..."}` approves the new text
+//! - `POST /proposals/:id/reject`, optionally with `{"reason": "..."}`
+
+use std::collections::HashMap;
+
+use reqwest::Url;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{
+    cli::ProposalAction,
+    db::{models::Proposal, Pool},
+    engines::approval::{self, Decision, ReviewError},
+};
+
+/// Largest request body the API reads
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Most proposals one list returns
+const LIST_LIMIT: i64 = 100;
+
+/// Serves the admin API until shutdown. Requests must carry
+/// `Authorization: Bearer <token>` unless `token` is empty.
+pub async fn serve(listener: TcpListener, pool: Pool, token: String, shutdown: CancellationToken) {
+    loop {
+        let stream = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Admin API accept failed: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        tokio::spawn(handle_connection(stream, pool.clone(), token.clone()));
+    }
+
+    info!("Admin API stopped");
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+async fn handle_connection(stream: TcpStream, pool: Pool, token: String) {
+    let mut reader = BufReader::new(stream);
+
+    let (status, body) = match read_request(&mut reader).await {
+        Some(request) if !authorized(&request, &token) => (401, json!({"error": "unauthorized"})),
+        Some(request) => {
+            debug!("Admin API {} {}", request.method, request.path);
+            route(&pool, &request).await
+        }
+        None => (400, json!({"error": "malformed request"})),
+    };
+
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        reason_phrase(status),
+        body.len(),
+        body
+    );
+    let _ = reader.get_mut().write_all(response.as_bytes()).await;
+}
+
+async fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.ok()?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.ok()? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .map_or(Some(0), |len| len.parse().ok())?;
+    if length > MAX_BODY_BYTES {
+        return None;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await.ok()?;
+
+    Some(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body,
+    })
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    if token.is_empty() {
+        return true;
+    }
+
+    let expected = format!("Bearer {}", token);
+    let given = request.headers.get("authorization").map(String::as_str).unwrap_or_default();
+
+    // Compare every byte so timing doesn't reveal how much matched
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn route(pool: &Pool, request: &Request) -> (u16, Value) {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["proposals"]) => {
+            let status = query_param(&request.query, "status").unwrap_or_else(|| Proposal::PENDING.to_string());
+            let filter = (status != "all").then_some(status.as_str());
+            match Proposal::list(pool, filter, LIST_LIMIT).await {
+                Ok(proposals) => (200, json!(proposals)),
+                Err(e) => (500, json!({"error": e.to_string()})),
+            }
+        }
+        ("GET", ["proposals", id]) => {
+            let Ok(id) = id.parse() else {
+                return (404, json!({"error": "not found"}));
+            };
+            match Proposal::find(pool, id).await {
+                Ok(Some(proposal)) => (200, json!(proposal)),
+                Ok(None) => (404, json!({"error": ReviewError::NotFound(id).to_string()})),
+                Err(e) => (500, json!({"error": e.to_string()})),
+            }
+        }
+        ("POST", ["proposals", id, action]) => {
+            let Ok(id) = id.parse() else {
+                return (404, json!({"error": "not found"}));
+            };
+            let body: Value = if request.body.is_empty() {
+                json!({})
+            } else {
+                match serde_json::from_slice(&request.body) {
+                    Ok(body) => body,
+                    Err(e) => return (400, json!({"error": format!("invalid JSON: {}", e)})),
+                }
+            };
+
+            let decision = match *action {
+                "approve" => Decision::Approve,
+                "edit" => match body["content"].as_str() {
+                    Some(content) => Decision::Edit(content.to_string()),
+                    None => return (400, json!({"error": "edit needs a \"content\" string"})),
+                },
+                "reject" => Decision::Reject(body["reason"].as_str().map(str::to_string)),
+                _ => return (404, json!({"error": "not found"})),
+            };
+
+            match approval::review(pool, id, decision, "admin-api").await {
+                Ok(proposal) => {
+                    info!("Reviewed via admin API: {}", approval::summary(&proposal));
+                    (200, json!(proposal))
+                }
+                Err(e) => (review_status(&e), json!({"error": e.to_string()})),
+            }
+        }
+        _ => (404, json!({"error": "not found"})),
+    }
+}
+
+fn review_status(error: &ReviewError) -> u16 {
+    match error {
+        ReviewError::NotFound(_) => 404,
+        ReviewError::NotPending { .. } => 409,
+        ReviewError::Invalid(_) => 400,
+        ReviewError::Database(_) => 500,
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    Url::parse(&format!("http://localhost/?{}", query))
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Runs a `tomflash proposals` subcommand, printing the result
+pub async fn run_command(pool: &Pool, action: ProposalAction) -> anyhow::Result<()> {
+    let (id, decision) = match action {
+        ProposalAction::List { status } => {
+            let filter = (status != "all").then_some(status.as_str());
+            let proposals = Proposal::list(pool, filter, LIST_LIMIT).await?;
+            if proposals.is_empty() {
+                println!("No {} proposals", status);
+            }
+            for proposal in proposals {
+                println!("{}", approval::summary(&proposal));
+            }
+            return Ok(());
+        }
+        ProposalAction::Approve { id } => (id, Decision::Approve),
+        ProposalAction::Edit { id, content } => (id, Decision::Edit(content)),
+        ProposalAction::Reject { id, reason } => (id, Decision::Reject(reason)),
+    };
+
+    let proposal = approval::review(pool, id, decision, "cli").await?;
+    println!("{}", approval::summary(&proposal));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::NewProposal;
+    use crate::testing;
+
+    async fn start(pool: Pool, token: &str) -> (String, CancellationToken) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let shutdown = CancellationToken::new();
+        tokio::spawn(serve(listener, pool, token.to_string(), shutdown.clone()));
+        (base, shutdown)
+    }
+
+    fn pending(category: &str, content: &str) -> NewProposal {
+        NewProposal {
+            category: category.to_string(),
+            status: Proposal::PENDING.to_string(),
+            content: content.to_string(),
+            original_content: None,
+            reply_to_id: None,
+            to_address: None,
+            amount_eth: None,
+            decided_by: None,
+            reason: None,
+            image_path: None,
+            alt_text: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_review_over_http() {
+        let server = testing::FakeServer::start().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&pool).await;
+        let (base, shutdown) = start(pool.clone(), "").await;
+        let http = reqwest::Client::new();
+
+        let post = Proposal::create(&pool, pending("post", "first draft")).await.unwrap();
+        let transfer = Proposal::create(&pool, pending("transfer", "Send 0.01 ETH to 0xabc")).await.unwrap();
+
+        let listed: Value = http.get(format!("{}/proposals", base)).send().await.unwrap().json().await.unwrap();
+        let ids: Vec<i64> = listed.as_array().unwrap().iter().map(|p| p["id"].as_i64().unwrap()).collect();
+        assert_eq!(ids, vec![post.id as i64, transfer.id as i64]);
+
+        let edited = http
+            .post(format!("{}/proposals/{}/edit", base, post.id))
+            .json(&json!({"content": "second draft"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(edited.status(), 200);
+        let edited: Value = edited.json().await.unwrap();
+        assert_eq!(edited["status"], "approved");
+        assert_eq!(edited["content"], "second draft");
+        assert_eq!(edited["original_content"], "first draft");
+        assert_eq!(edited["decided_by"], "admin-api");
+
+        let again = http.post(format!("{}/proposals/{}/reject", base, post.id)).send().await.unwrap();
+        assert_eq!(again.status(), 409);
+
+        let edit_transfer = http
+            .post(format!("{}/proposals/{}/edit", base, transfer.id))
+            .json(&json!({"content": "Send 10 ETH instead"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(edit_transfer.status(), 400);
+
+        let rejected: Value = http
+            .post(format!("{}/proposals/{}/reject", base, transfer.id))
+            .json(&json!({"reason": "not today"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(rejected["status"], "rejected");
+        assert_eq!(rejected["reason"], "not today");
+
+        let missing = http.get(format!("{}/proposals/999999", base)).send().await.unwrap();
+        assert_eq!(missing.status(), 404);
+
+        shutdown.cancel();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_requires_token() {
+        let server = testing::FakeServer::start().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let (base, shutdown) = start(pool, "s3cret").await;
+        let http = reqwest::Client::new();
+
+        let anonymous = http.get(format!("{}/proposals", base)).send().await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+
+        let wrong = http.get(format!("{}/proposals", base)).bearer_auth("guess").send().await.unwrap();
+        assert_eq!(wrong.status(), 401);
+
+        let authorized = http.get(format!("{}/proposals", base)).bearer_auth("s3cret").send().await.unwrap();
+        assert_eq!(authorized.status(), 200);
+
+        shutdown.cancel();
+    }
+}
@@ -0,0 +1,125 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Command line arguments; config overrides here take precedence over env vars
+#[derive(Debug, Parser)]
+#[command(name = "tomflash", version, about = "Autonomous social agent")]
+pub struct Cli {
+    /// Path to the TOML config file (defaults to ./tomflash.toml if present)
+    #[arg(short, long, env = "TOMFLASH_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Override a config key, e.g. --set llm.model=my-model
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub overrides: Vec<(String, String)>,
+
+    /// Runs the agent when omitted
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Authorize the X account with OAuth 2.0 (PKCE) and store its tokens
+    XLogin,
+    /// Review posts, replies and transfers held in approval mode
+    Proposals {
+        #[command(subcommand)]
+        action: ProposalAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProposalAction {
+    /// List proposals, oldest first
+    List {
+        /// Only proposals in this status; "all" lists everything
+        #[arg(long, default_value = "pending")]
+        status: String,
+    },
+    /// Approve a pending proposal as it is
+    Approve { id: i32 },
+    /// Approve a pending post or reply with new content
+    Edit { id: i32, content: String },
+    /// Reject a pending proposal
+    Reject {
+        id: i32,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+fn parse_override(raw: &str) -> Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", raw))?;
+
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(format!("missing key in {:?}", raw));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_overrides() {
+        let cli = Cli::try_parse_from([
+            "tomflash",
+            "--config",
+            "agent.toml",
+            "--set",
+            "llm.model=foo=bar",
+            "--set",
+            "memory.significance_threshold=0.8",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.config, Some(PathBuf::from("agent.toml")));
+        assert_eq!(
+            cli.overrides,
+            vec![
+                ("llm.model".to_string(), "foo=bar".to_string()),
+                ("memory.significance_threshold".to_string(), "0.8".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_x_login_subcommand() {
+        let cli = Cli::try_parse_from(["tomflash", "--set", "twitter.auth=oauth2", "x-login"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::XLogin)));
+
+        let cli = Cli::try_parse_from(["tomflash"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_proposals_subcommand() {
+        let cli = Cli::try_parse_from(["tomflash", "proposals", "edit", "7", "better wording"]).unwrap();
+        match cli.command {
+            Some(Command::Proposals { action: ProposalAction::Edit { id, content } }) => {
+                assert_eq!(id, 7);
+                assert_eq!(content, "better wording");
+            }
+            other => panic!("expected proposals edit, got {:?}", other),
+        }
+
+        let cli = Cli::try_parse_from(["tomflash", "proposals", "list"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Proposals { action: ProposalAction::List { status } }) if status == "pending"
+        ));
+        assert!(Cli::try_parse_from(["tomflash", "proposals", "approve", "seven"]).is_err());
+    }
+
+    #[test]
+    fn test_reject_malformed_override() {
+        assert!(Cli::try_parse_from(["tomflash", "--set", "llm.model"]).is_err());
+        assert!(Cli::try_parse_from(["tomflash", "--set", "=value"]).is_err());
+    }
+}
@@ -1,75 +1,1277 @@
-use serde::Deserialize;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::db::vector::EMBEDDING_DIMENSIONS;
+
+/// Config file read when no explicit path is given, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "tomflash.toml";
+
+/// Prefix for generic env overrides, e.g. `TOMFLASH__LLM__MODEL`
+const ENV_PREFIX: &str = "TOMFLASH__";
+
+/// Well-known env vars and the config keys they override
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("DATABASE_URL", "database_url"),
+    ("TWITTER_API_KEY", "twitter.api_key"),
+    ("TWITTER_API_SECRET", "twitter.api_secret"),
+    ("TWITTER_ACCESS_TOKEN", "twitter.access_token"),
+    ("TWITTER_ACCESS_SECRET", "twitter.access_secret"),
+    ("TWITTER_BEARER_TOKEN", "twitter.bearer_token"),
+    ("TWITTER_USERNAME", "twitter.username"),
+    ("TWITTER_AUTH", "twitter.auth"),
+    ("TWITTER_RATE_LIMIT_POLICY", "twitter.rate_limit_policy"),
+    ("TWITTER_CLIENT_ID", "twitter.oauth2.client_id"),
+    ("TWITTER_CLIENT_SECRET", "twitter.oauth2.client_secret"),
+    ("TWITTER_REDIRECT_URI", "twitter.oauth2.redirect_uri"),
+    ("TWITTER_TOKEN_ENCRYPTION_KEY", "twitter.oauth2.token_encryption_key"),
+    ("LLM_PROVIDER", "llm.provider"),
+    ("LLM_BASE_URL", "llm.base_url"),
+    ("LLM_MODEL", "llm.model"),
+    ("HYPERBOLIC_API_KEY", "llm.api_key"),
+    ("EMBEDDINGS_PROVIDER", "embeddings.provider"),
+    ("EMBEDDINGS_BASE_URL", "embeddings.base_url"),
+    ("EMBEDDINGS_MODEL", "embeddings.model"),
+    ("OPENAI_API_KEY", "embeddings.api_key"),
+    ("ETH_MAINNET_RPC", "wallet.rpc_url"),
+    ("ETH_PRIVATE_KEY", "wallet.private_key"),
+    ("ETH_CHAIN_ID", "wallet.chain_id"),
+    ("MIN_TRANSACTION_BALANCE", "wallet.min_transaction_balance"),
+    ("ADMIN_TOKEN", "approval.admin_token"),
+    ("PERSONA_FILE", "persona.file"),
+    ("MEMORY_SIGNIFICANCE_THRESHOLD", "memory.significance_threshold"),
+    ("VECTOR_INDEX", "memory.vector_index"),
+    ("VECTOR_INDEX_SNAPSHOT_PATH", "memory.index_snapshot_path"),
+    ("MEMORY_HALF_LIFE_DAYS", "memory.recency_half_life_days"),
+    ("MEMORY_ACTIVATION_FLOOR", "memory.activation_floor"),
+    ("SHORT_TERM_CAPACITY", "memory.short_term_capacity"),
+    ("SHORT_TERM_TTL_SECS", "memory.short_term_ttl_secs"),
+    ("COGNITIVE_CYCLE_INTERVAL_SECS", "scheduling.cognitive_cycle_interval_secs"),
+    ("INTERACTION_INTERVAL_SECS", "scheduling.interaction_interval_secs"),
+    ("CONSOLIDATION_INTERVAL_SECS", "scheduling.consolidation_interval_secs"),
+    ("FORGETTING_INTERVAL_SECS", "scheduling.forgetting_interval_secs"),
+    ("WALLET_CHECK_INTERVAL_SECS", "scheduling.wallet_check_interval_secs"),
+    ("SHUTDOWN_DRAIN_SECS", "shutdown.drain_deadline_secs"),
+    ("SHORT_TERM_SNAPSHOT_PATH", "shutdown.snapshot_path"),
+];
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
-    #[error("Missing environment variable: {0}")]
-    MissingEnv(String),
-    
-    #[error("Invalid environment variable: {0}")]
-    InvalidEnv(String),
+    #[error("Failed to read config file {path}: {source}")]
+    ReadFile {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to parse config: {0}")]
+    Parse(String),
+
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub database_url: String,
-    pub openai_api_key: String,
-    pub eth_rpc_url: String,
-    pub twitter_config: TwitterConfig,
+    pub twitter: TwitterConfig,
+    pub llm: LlmConfig,
+    pub embeddings: EmbeddingsConfig,
+    pub wallet: WalletConfig,
+    pub memory: MemoryConfig,
+    pub scheduling: SchedulingConfig,
+    pub shutdown: ShutdownConfig,
+    pub outbox: OutboxConfig,
+    pub approval: ApprovalConfig,
+    pub content_filter: ContentFilterConfig,
+    pub novelty: NoveltyConfig,
+    pub persona: PersonaConfig,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct TwitterConfig {
+    pub api_base: String,
+    /// v1.1 base for chunked media uploads
+    pub upload_base: String,
     pub api_key: String,
     pub api_secret: String,
     pub access_token: String,
     pub access_secret: String,
     pub bearer_token: String,
+    pub username: String,
+    /// How user-context requests are authorized
+    pub auth: XAuthMethod,
+    pub oauth2: OAuth2Config,
+    /// What to do when an endpoint's rate limit is used up
+    pub rate_limit_policy: RateLimitPolicy,
+    /// Longest reset the `wait` policy sleeps for before failing instead
+    #[serde(rename = "max_rate_limit_wait_secs", with = "duration_secs")]
+    pub max_rate_limit_wait: Duration,
+}
+
+impl Default for TwitterConfig {
+    fn default() -> Self {
+        Self {
+            api_base: "https://api.twitter.com/2".to_string(),
+            upload_base: "https://upload.twitter.com/1.1".to_string(),
+            api_key: String::new(),
+            api_secret: String::new(),
+            access_token: String::new(),
+            access_secret: String::new(),
+            bearer_token: String::new(),
+            username: String::new(),
+            auth: XAuthMethod::OAuth1,
+            oauth2: OAuth2Config::default(),
+            rate_limit_policy: RateLimitPolicy::Wait,
+            max_rate_limit_wait: Duration::from_secs(900),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XAuthMethod {
+    /// Consumer key plus access token, signed per request
+    OAuth1,
+    /// Authorization Code with PKCE; tokens are obtained with `tomflash x-login`
+    OAuth2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitPolicy {
+    /// Sleep until the window resets, up to `max_rate_limit_wait_secs`
+    Wait,
+    /// Return `XError::RateLimit` immediately
+    FailFast,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OAuth2Config {
+    pub client_id: String,
+    /// Only set for confidential clients
+    pub client_secret: String,
+    /// Must match the callback registered for the app; the login command
+    /// listens on its host and port
+    pub redirect_uri: String,
+    pub authorize_url: String,
+    pub scopes: Vec<String>,
+    /// Base64-encoded 32-byte key used to encrypt stored tokens
+    pub token_encryption_key: String,
+}
+
+impl Default for OAuth2Config {
+    fn default() -> Self {
+        Self {
+            client_id: String::new(),
+            client_secret: String::new(),
+            redirect_uri: "http://127.0.0.1:8976/callback".to_string(),
+            authorize_url: "https://twitter.com/i/oauth2/authorize".to_string(),
+            scopes: ["tweet.read", "tweet.write", "users.read", "follows.write", "offline.access"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            token_encryption_key: String::new(),
+        }
+    }
+}
+
+/// Backends that speak the OpenAI-compatible chat and embeddings API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    Hyperbolic,
+    OpenAi,
+    Vllm,
+    LlamaCpp,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn default_base_url(&self) -> &'static str {
+        match self {
+            ProviderKind::Hyperbolic => "https://api.hyperbolic.xyz/v1",
+            ProviderKind::OpenAi => "https://api.openai.com/v1",
+            ProviderKind::Vllm => "http://localhost:8000/v1",
+            ProviderKind::LlamaCpp => "http://localhost:8080/v1",
+            ProviderKind::Ollama => "http://localhost:11434/v1",
+        }
+    }
+
+    /// Hosted providers reject unauthenticated requests; local servers usually don't
+    pub fn requires_api_key(&self) -> bool {
+        matches!(self, ProviderKind::Hyperbolic | ProviderKind::OpenAi)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    pub provider: ProviderKind,
+    /// Overrides the provider's default base URL, e.g. a staging server
+    pub base_url: Option<String>,
+    pub api_key: String,
+    pub model: String,
+    pub timeout_secs: u64,
+}
+
+impl LlmConfig {
+    pub fn endpoint(&self) -> &str {
+        self.base_url.as_deref().unwrap_or(self.provider.default_base_url())
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::Hyperbolic,
+            base_url: None,
+            api_key: String::new(),
+            model: "meta-llama/Meta-Llama-3.1-70B-Instruct".to_string(),
+            timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingsConfig {
+    pub provider: ProviderKind,
+    pub base_url: Option<String>,
+    pub api_key: String,
+    pub model: String,
+    pub dimensions: usize,
+    pub timeout_secs: u64,
+}
+
+impl EmbeddingsConfig {
+    pub fn endpoint(&self) -> &str {
+        self.base_url.as_deref().unwrap_or(self.provider.default_base_url())
+    }
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::OpenAi,
+            base_url: None,
+            api_key: String::new(),
+            model: "text-embedding-3-small".to_string(),
+            dimensions: EMBEDDING_DIMENSIONS,
+            timeout_secs: 30,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalletConfig {
+    pub rpc_url: String,
+    pub private_key: String,
+    pub chain_id: u64,
+    /// Minimum balance in ETH before the agent considers sending anything
+    pub min_transaction_balance: f64,
+    /// ETH sent to an address found in an interaction; 0 disables transfers
+    pub transfer_amount: f64,
+}
+
+impl Default for WalletConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: String::new(),
+            private_key: String::new(),
+            chain_id: 1,
+            min_transaction_balance: 0.1,
+            transfer_amount: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    pub significance_threshold: f32,
+    pub vector_index: VectorIndexKind,
+    /// Where the in-process index is saved on shutdown and loaded from on start
+    pub index_snapshot_path: PathBuf,
+    /// Memories are merged when every pair in a group is at least this similar
+    pub consolidation_threshold: f32,
+    pub max_cluster_size: usize,
+    /// A memory's activation halves over this many days without a recall
+    pub recency_half_life_days: f32,
+    /// How much each recall raises activation, on a log scale
+    pub reinforcement_weight: f32,
+    /// Memories whose activation falls below this are archived
+    pub activation_floor: f32,
+    /// Recent thoughts kept as working context, oldest dropped first
+    pub short_term_capacity: usize,
+    /// Recent thoughts older than this are dropped, including on restore
+    #[serde(rename = "short_term_ttl_secs", with = "duration_secs")]
+    pub short_term_ttl: Duration,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            significance_threshold: 0.5,
+            vector_index: VectorIndexKind::Auto,
+            index_snapshot_path: PathBuf::from("data/vector_index.json"),
+            consolidation_threshold: 0.85,
+            max_cluster_size: 8,
+            recency_half_life_days: 30.0,
+            reinforcement_weight: 0.5,
+            activation_floor: 0.05,
+            short_term_capacity: 100,
+            short_term_ttl: Duration::from_secs(24 * 3600),
+        }
+    }
+}
+
+/// Backend for similarity search over long-term memories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorIndexKind {
+    /// pgvector when `long_term_memories.embedding` is a `vector` column,
+    /// otherwise the in-process index
+    Auto,
+    Pgvector,
+    /// In-process HNSW graph, for Postgres without the pgvector extension
+    Hnsw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulingConfig {
+    #[serde(rename = "cognitive_cycle_interval_secs", with = "duration_secs")]
+    pub cognitive_cycle_interval: Duration,
+    #[serde(rename = "interaction_interval_secs", with = "duration_secs")]
+    pub interaction_interval: Duration,
+    #[serde(rename = "consolidation_interval_secs", with = "duration_secs")]
+    pub consolidation_interval: Duration,
+    #[serde(rename = "forgetting_interval_secs", with = "duration_secs")]
+    pub forgetting_interval: Duration,
+    #[serde(rename = "wallet_check_interval_secs", with = "duration_secs")]
+    pub wallet_check_interval: Duration,
+}
+
+impl Default for SchedulingConfig {
+    fn default() -> Self {
+        Self {
+            cognitive_cycle_interval: Duration::from_secs(1800),
+            interaction_interval: Duration::from_secs(300),
+            consolidation_interval: Duration::from_secs(6 * 3600),
+            forgetting_interval: Duration::from_secs(24 * 3600),
+            wallet_check_interval: Duration::from_secs(900),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    #[serde(rename = "drain_deadline_secs", with = "duration_secs")]
+    pub drain_deadline: Duration,
+    pub snapshot_path: PathBuf,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_deadline: Duration::from_secs(30),
+            snapshot_path: PathBuf::from("data/short_term_memory.json"),
+        }
+    }
+}
+
+/// Delivery of queued posts; failed sends back off exponentially from
+/// `retry_base` up to `retry_max`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutboxConfig {
+    #[serde(rename = "poll_interval_secs", with = "duration_secs")]
+    pub poll_interval: Duration,
+    /// Attempts before a post is dead-lettered
+    pub max_attempts: u32,
+    #[serde(rename = "retry_base_secs", with = "duration_secs")]
+    pub retry_base: Duration,
+    #[serde(rename = "retry_max_secs", with = "duration_secs")]
+    pub retry_max: Duration,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(10),
+            max_attempts: 8,
+            retry_base: Duration::from_secs(30),
+            retry_max: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Human review of generated posts, replies and transfers. While enabled,
+/// categories not auto-approved wait as proposals for the admin API or CLI.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApprovalConfig {
+    pub enabled: bool,
+    /// Where the admin API listens
+    pub admin_addr: String,
+    /// Bearer token the admin API requires; may only be empty on a loopback address
+    pub admin_token: String,
+    pub auto_approve: AutoApproveConfig,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            admin_addr: "127.0.0.1:8787".to_string(),
+            admin_token: String::new(),
+            auto_approve: AutoApproveConfig::default(),
+        }
+    }
+}
+
+/// Categories that skip review while approval mode is on
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoApproveConfig {
+    pub post: bool,
+    pub reply: bool,
+    pub transfer: bool,
+}
+
+/// Screening of generated posts and replies before they are submitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentFilterConfig {
+    pub enabled: bool,
+    /// Words and phrases matched case-insensitively on word boundaries
+    pub blocklist: Vec<String>,
+    pub blocklist_action: FilterAction,
+    pub patterns: Vec<PatternRule>,
+    /// Emails and phone numbers
+    pub pii_action: FilterAction,
+    /// Private keys, seed phrases and API keys
+    pub secrets_action: FilterAction,
+    /// Also ask the LLM whether the content is fit to post
+    pub moderation: bool,
+    pub moderation_action: FilterAction,
+}
+
+impl Default for ContentFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            blocklist: Vec::new(),
+            blocklist_action: FilterAction::Block,
+            patterns: Vec::new(),
+            pii_action: FilterAction::Rewrite,
+            secrets_action: FilterAction::Block,
+            moderation: false,
+            moderation_action: FilterAction::Review,
+        }
+    }
+}
+
+/// A named regex rule for the content filter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternRule {
+    pub name: String,
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+/// What happens to content a filter flags, from mildest to strictest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Mask the flagged text and let the rest through
+    Rewrite,
+    /// Hold it as a pending proposal for human review
+    Review,
+    /// Never post it
+    Block,
+}
+
+/// Keeps generated posts from rehashing the agent's recent ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoveltyConfig {
+    pub enabled: bool,
+    /// How many of the agent's latest posts a candidate is compared with
+    pub history: usize,
+    /// Embedding cosine similarity at or above which a candidate is a rehash
+    pub similarity_threshold: f32,
+    /// Words per n-gram for the overlap check
+    pub ngram_size: usize,
+    /// Share of a candidate's n-grams found in one earlier post at or above
+    /// which it is a rehash
+    pub overlap_threshold: f32,
+    /// New candidates generated after a rehash before the post is dropped
+    pub regenerate_attempts: u32,
+}
+
+impl Default for NoveltyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            history: 20,
+            similarity_threshold: 0.92,
+            ngram_size: 3,
+            overlap_threshold: 0.6,
+            regenerate_attempts: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PersonaConfig {
+    pub name: String,
+    pub bio: String,
+    /// TOML persona spec (voice, topics, examples, banned phrases); its
+    /// name and bio take precedence over the ones above
+    pub file: Option<PathBuf>,
+}
+
+impl Default for PersonaConfig {
+    fn default() -> Self {
+        Self {
+            name: "tomflash".to_string(),
+            bio: String::new(),
+            file: None,
+        }
+    }
 }
 
 impl Config {
+    /// Loads config in layers: defaults, then the TOML file, then env vars,
+    /// then `key=value` overrides from the command line
+    pub fn load(path: Option<&Path>, cli_overrides: &[(String, String)]) -> Result<Self, ConfigError> {
+        dotenv::dotenv().ok();
+
+        let file = match path {
+            Some(path) => Some(read_file(path)?),
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Some(read_file(Path::new(DEFAULT_CONFIG_PATH))?)
+            }
+            None => None,
+        };
+
+        let env: HashMap<String, String> = std::env::vars().collect();
+        Self::from_layers(file, &env, cli_overrides)
+    }
+
+    /// Loads config from defaults and env vars only
     pub fn from_env() -> Result<Self, ConfigError> {
         dotenv::dotenv().ok();
 
-        Ok(Self {
-            database_url: get_env("DATABASE_URL")?,
-            openai_api_key: get_env("OPENAI_API_KEY")?,
-            eth_rpc_url: get_env("ETH_MAINNET_RPC")?,
-            twitter_config: TwitterConfig {
-                api_key: get_env("TWITTER_API_KEY")?,
-                api_secret: get_env("TWITTER_API_SECRET")?,
-                access_token: get_env("TWITTER_ACCESS_TOKEN")?,
-                access_secret: get_env("TWITTER_ACCESS_SECRET")?,
-                bearer_token: get_env("TWITTER_BEARER_TOKEN")?,
-            },
+        let env: HashMap<String, String> = std::env::vars().collect();
+        Self::from_layers(None, &env, &[])
+    }
+
+    fn from_layers(
+        file: Option<Table>,
+        env: &HashMap<String, String>,
+        cli_overrides: &[(String, String)],
+    ) -> Result<Self, ConfigError> {
+        let mut merged = Table::try_from(Config::default())
+            .map_err(|e| ConfigError::Parse(e.to_string()))?;
+
+        if let Some(file) = file {
+            merge_tables(&mut merged, file);
+        }
+
+        let mut problems = Vec::new();
+
+        for (key, value) in env_overrides(env) {
+            if let Err(e) = apply_override(&mut merged, &key, &value) {
+                problems.push(format!("{} (from environment): {}", key, e));
+            }
+        }
+
+        for (key, value) in cli_overrides {
+            if let Err(e) = apply_override(&mut merged, key, value) {
+                problems.push(format!("{} (from command line): {}", key, e));
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+
+        let config: Config = Value::Table(merged)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Parse(e.to_string()))?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Credentials that must never show up in logs or outgoing text, by key.
+    /// Unset ones are left out. The URLs are included whole since they
+    /// usually carry a password or API key.
+    pub fn secrets(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("database_url", &self.database_url),
+            ("twitter.api_key", &self.twitter.api_key),
+            ("twitter.api_secret", &self.twitter.api_secret),
+            ("twitter.access_token", &self.twitter.access_token),
+            ("twitter.access_secret", &self.twitter.access_secret),
+            ("twitter.bearer_token", &self.twitter.bearer_token),
+            ("twitter.oauth2.client_secret", &self.twitter.oauth2.client_secret),
+            ("twitter.oauth2.token_encryption_key", &self.twitter.oauth2.token_encryption_key),
+            ("llm.api_key", &self.llm.api_key),
+            ("embeddings.api_key", &self.embeddings.api_key),
+            ("wallet.rpc_url", &self.wallet.rpc_url),
+            ("wallet.private_key", &self.wallet.private_key),
+            ("approval.admin_token", &self.approval.admin_token),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key, value.trim()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+    }
+
+    /// Checks every section and reports all problems at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        let mut required = vec![
+            ("database_url", &self.database_url),
+            ("twitter.bearer_token", &self.twitter.bearer_token),
+            ("twitter.username", &self.twitter.username),
+            ("llm.model", &self.llm.model),
+            ("embeddings.model", &self.embeddings.model),
+            ("wallet.rpc_url", &self.wallet.rpc_url),
+            ("wallet.private_key", &self.wallet.private_key),
+            ("persona.name", &self.persona.name),
+        ];
+
+        match self.twitter.auth {
+            XAuthMethod::OAuth1 => required.extend([
+                ("twitter.api_key", &self.twitter.api_key),
+                ("twitter.api_secret", &self.twitter.api_secret),
+                ("twitter.access_token", &self.twitter.access_token),
+                ("twitter.access_secret", &self.twitter.access_secret),
+            ]),
+            XAuthMethod::OAuth2 => required.extend([
+                ("twitter.oauth2.client_id", &self.twitter.oauth2.client_id),
+                ("twitter.oauth2.token_encryption_key", &self.twitter.oauth2.token_encryption_key),
+            ]),
+        }
+
+        for (key, value) in required {
+            if value.trim().is_empty() {
+                problems.push(missing_message(key));
+            }
+        }
+
+        if self.llm.provider.requires_api_key() && self.llm.api_key.trim().is_empty() {
+            problems.push(missing_message("llm.api_key"));
+        }
+
+        if self.embeddings.provider.requires_api_key() && self.embeddings.api_key.trim().is_empty() {
+            problems.push(missing_message("embeddings.api_key"));
+        }
+
+        let endpoints = [
+            ("twitter.api_base", self.twitter.api_base.as_str()),
+            ("twitter.upload_base", self.twitter.upload_base.as_str()),
+            ("llm.base_url", self.llm.endpoint()),
+            ("embeddings.base_url", self.embeddings.endpoint()),
+            ("twitter.oauth2.redirect_uri", self.twitter.oauth2.redirect_uri.as_str()),
+            ("twitter.oauth2.authorize_url", self.twitter.oauth2.authorize_url.as_str()),
+        ];
+
+        for (key, endpoint) in endpoints {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                problems.push(format!("{} must be an http(s) URL, got {:?}", key, endpoint));
+            }
+        }
+
+        if self.twitter.auth == XAuthMethod::OAuth2 {
+            let key = &self.twitter.oauth2.token_encryption_key;
+            let decoded = BASE64.decode(key.trim()).map(|key| key.len());
+            if !key.trim().is_empty() && decoded != Ok(32) {
+                problems.push("twitter.oauth2.token_encryption_key must be a base64-encoded 32-byte key".to_string());
+            }
+        }
+
+        if self.embeddings.dimensions != EMBEDDING_DIMENSIONS {
+            problems.push(format!(
+                "embeddings.dimensions must be {} to match the long_term_memories.embedding column",
+                EMBEDDING_DIMENSIONS
+            ));
+        }
+
+        if self.wallet.chain_id == 0 {
+            problems.push("wallet.chain_id must be greater than 0".to_string());
+        }
+
+        if self.wallet.min_transaction_balance.is_nan() || self.wallet.min_transaction_balance < 0.0 {
+            problems.push("wallet.min_transaction_balance must not be negative".to_string());
+        }
+
+        if self.wallet.transfer_amount.is_nan() || self.wallet.transfer_amount < 0.0 {
+            problems.push("wallet.transfer_amount must not be negative".to_string());
+        }
+
+        match self.approval.admin_addr.parse::<std::net::SocketAddr>() {
+            Ok(addr) => {
+                if self.approval.enabled && !addr.ip().is_loopback() && self.approval.admin_token.trim().is_empty() {
+                    problems.push("approval.admin_token is required when approval.admin_addr is not a loopback address".to_string());
+                }
+            }
+            Err(_) => problems.push(format!(
+                "approval.admin_addr must be an address like 127.0.0.1:8787, got {:?}",
+                self.approval.admin_addr
+            )),
+        }
+
+        for rule in &self.content_filter.patterns {
+            if let Err(e) = regex::Regex::new(&rule.pattern) {
+                problems.push(format!("content_filter pattern {:?} is not a valid regex: {}", rule.name, e));
+            }
+        }
+
+        if self.content_filter.blocklist.iter().any(|entry| entry.trim().is_empty()) {
+            problems.push("content_filter.blocklist must not contain empty entries".to_string());
+        }
+
+        for (key, threshold) in [
+            ("novelty.similarity_threshold", self.novelty.similarity_threshold),
+            ("novelty.overlap_threshold", self.novelty.overlap_threshold),
+        ] {
+            if !(0.0..=1.0).contains(&threshold) {
+                problems.push(format!("{} must be between 0 and 1", key));
+            }
+        }
+
+        if let Some(file) = &self.persona.file {
+            if !file.is_file() {
+                problems.push(format!("persona.file {} does not exist", file.display()));
+            }
+        }
+
+        if self.novelty.ngram_size == 0 {
+            problems.push("novelty.ngram_size must be greater than 0".to_string());
+        }
+
+        for (key, threshold) in [
+            ("memory.significance_threshold", self.memory.significance_threshold),
+            ("memory.consolidation_threshold", self.memory.consolidation_threshold),
+            ("memory.activation_floor", self.memory.activation_floor),
+        ] {
+            if !(0.0..=1.0).contains(&threshold) {
+                problems.push(format!("{} must be between 0 and 1", key));
+            }
+        }
+
+        if self.memory.short_term_capacity == 0 {
+            problems.push("memory.short_term_capacity must be greater than 0".to_string());
+        }
+
+        if self.memory.max_cluster_size < 2 {
+            problems.push("memory.max_cluster_size must be at least 2".to_string());
+        }
+
+        if self.memory.recency_half_life_days.is_nan() || self.memory.recency_half_life_days <= 0.0 {
+            problems.push("memory.recency_half_life_days must be greater than 0".to_string());
+        }
+
+        if self.memory.reinforcement_weight.is_nan() || self.memory.reinforcement_weight < 0.0 {
+            problems.push("memory.reinforcement_weight must not be negative".to_string());
+        }
+
+        // A new memory starts at its significance, so it would be forgotten at once
+        if self.memory.activation_floor >= self.memory.significance_threshold {
+            problems.push("memory.activation_floor must be below memory.significance_threshold".to_string());
+        }
+
+        let intervals = [
+            ("memory.short_term_ttl_secs", self.memory.short_term_ttl),
+            ("scheduling.cognitive_cycle_interval_secs", self.scheduling.cognitive_cycle_interval),
+            ("scheduling.interaction_interval_secs", self.scheduling.interaction_interval),
+            ("scheduling.consolidation_interval_secs", self.scheduling.consolidation_interval),
+            ("scheduling.forgetting_interval_secs", self.scheduling.forgetting_interval),
+            ("scheduling.wallet_check_interval_secs", self.scheduling.wallet_check_interval),
+            ("outbox.poll_interval_secs", self.outbox.poll_interval),
+        ];
+
+        for (key, interval) in intervals {
+            if interval.is_zero() {
+                problems.push(format!("{} must be greater than 0", key));
+            }
+        }
+
+        if self.outbox.max_attempts == 0 {
+            problems.push("outbox.max_attempts must be greater than 0".to_string());
+        }
+
+        if self.outbox.retry_base > self.outbox.retry_max {
+            problems.push("outbox.retry_base_secs must not exceed outbox.retry_max_secs".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+// Debug output shows whether a secret is set, never its value, so configs
+// can be traced safely. URLs are included since they often embed credentials.
+
+struct Redacted<'a>(&'a str);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("\"\"")
+        } else {
+            f.write_str("[redacted]")
+        }
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("database_url", &Redacted(&self.database_url))
+            .field("twitter", &self.twitter)
+            .field("llm", &self.llm)
+            .field("embeddings", &self.embeddings)
+            .field("wallet", &self.wallet)
+            .field("memory", &self.memory)
+            .field("scheduling", &self.scheduling)
+            .field("shutdown", &self.shutdown)
+            .field("outbox", &self.outbox)
+            .field("approval", &self.approval)
+            .field("content_filter", &self.content_filter)
+            .field("novelty", &self.novelty)
+            .field("persona", &self.persona)
+            .finish()
+    }
+}
+
+impl fmt::Debug for TwitterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TwitterConfig")
+            .field("api_base", &self.api_base)
+            .field("upload_base", &self.upload_base)
+            .field("api_key", &Redacted(&self.api_key))
+            .field("api_secret", &Redacted(&self.api_secret))
+            .field("access_token", &Redacted(&self.access_token))
+            .field("access_secret", &Redacted(&self.access_secret))
+            .field("bearer_token", &Redacted(&self.bearer_token))
+            .field("username", &self.username)
+            .field("auth", &self.auth)
+            .field("oauth2", &self.oauth2)
+            .field("rate_limit_policy", &self.rate_limit_policy)
+            .field("max_rate_limit_wait", &self.max_rate_limit_wait)
+            .finish()
+    }
+}
+
+impl fmt::Debug for OAuth2Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuth2Config")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &Redacted(&self.client_secret))
+            .field("redirect_uri", &self.redirect_uri)
+            .field("authorize_url", &self.authorize_url)
+            .field("scopes", &self.scopes)
+            .field("token_encryption_key", &Redacted(&self.token_encryption_key))
+            .finish()
+    }
+}
+
+impl fmt::Debug for LlmConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LlmConfig")
+            .field("provider", &self.provider)
+            .field("base_url", &self.base_url)
+            .field("api_key", &Redacted(&self.api_key))
+            .field("model", &self.model)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+impl fmt::Debug for EmbeddingsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddingsConfig")
+            .field("provider", &self.provider)
+            .field("base_url", &self.base_url)
+            .field("api_key", &Redacted(&self.api_key))
+            .field("model", &self.model)
+            .field("dimensions", &self.dimensions)
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
+    }
+}
+
+impl fmt::Debug for WalletConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WalletConfig")
+            .field("rpc_url", &Redacted(&self.rpc_url))
+            .field("private_key", &Redacted(&self.private_key))
+            .field("chain_id", &self.chain_id)
+            .field("min_transaction_balance", &self.min_transaction_balance)
+            .field("transfer_amount", &self.transfer_amount)
+            .finish()
+    }
+}
+
+impl fmt::Debug for ApprovalConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApprovalConfig")
+            .field("enabled", &self.enabled)
+            .field("admin_addr", &self.admin_addr)
+            .field("admin_token", &Redacted(&self.admin_token))
+            .field("auto_approve", &self.auto_approve)
+            .finish()
+    }
+}
+
+fn read_file(path: &Path) -> Result<Table, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::ReadFile {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    contents
+        .parse::<Table>()
+        .map_err(|e| ConfigError::Parse(format!("{}: {}", path.display(), e)))
+}
+
+fn missing_message(key: &str) -> String {
+    match ENV_OVERRIDES.iter().find(|(_, k)| *k == key) {
+        Some((env, _)) => format!("{} is required (set it in the config file or {})", key, env),
+        None => format!("{} is required", key),
+    }
+}
+
+/// Collects env overrides in a stable order: well-known names, then prefixed ones
+fn env_overrides(env: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut overrides: Vec<(String, String)> = ENV_OVERRIDES
+        .iter()
+        .filter_map(|(name, key)| env.get(*name).map(|value| (key.to_string(), value.clone())))
+        .collect();
+
+    let mut prefixed: Vec<(String, String)> = env
+        .iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_PREFIX)?;
+            Some((key.to_lowercase().replace("__", "."), value.clone()))
         })
+        .collect();
+    prefixed.sort();
+
+    overrides.extend(prefixed);
+    overrides
+}
+
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => {
+                merge_tables(base_table, overlay_table);
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Sets a dotted key, converting the raw string to the type of the existing value
+fn apply_override(table: &mut Table, key: &str, raw: &str) -> Result<(), String> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let leaf = parts.pop().filter(|leaf| !leaf.is_empty()).ok_or("empty key")?;
+
+    let mut current = table;
+    for part in parts {
+        current = match current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(inner) => inner,
+            _ => return Err(format!("{} is not a section", part)),
+        };
+    }
+
+    let value = coerce(raw, current.get(leaf))?;
+    current.insert(leaf.to_string(), value);
+    Ok(())
+}
+
+fn coerce(raw: &str, existing: Option<&Value>) -> Result<Value, String> {
+    let raw = raw.trim();
+
+    match existing {
+        Some(Value::Integer(_)) => raw
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| format!("expected an integer, got {:?}", raw)),
+        Some(Value::Float(_)) => raw
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| format!("expected a number, got {:?}", raw)),
+        Some(Value::Boolean(_)) => raw
+            .parse()
+            .map(Value::Boolean)
+            .map_err(|_| format!("expected true or false, got {:?}", raw)),
+        Some(Value::Array(_)) => format!("value = {}", raw)
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .filter(Value::is_array)
+            .ok_or_else(|| format!("expected an array, got {:?}", raw)),
+        Some(Value::Table(_)) => Err("cannot override a whole section".to_string()),
+        // Strings, and optional keys that have no default
+        _ => Ok(Value::String(raw.to_string())),
     }
 }
 
-fn get_env(key: &str) -> Result<String, ConfigError> {
-    std::env::var(key).map_err(|_| ConfigError::MissingEnv(key.to_string()))
+mod duration_secs {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn required_env() -> HashMap<String, String> {
+        [
+            ("DATABASE_URL", "test_url"),
+            ("OPENAI_API_KEY", "test_key"),
+            ("HYPERBOLIC_API_KEY", "test_llm_key"),
+            ("ETH_MAINNET_RPC", "test_rpc"),
+            ("ETH_PRIVATE_KEY", "test_private_key"),
+            ("TWITTER_API_KEY", "test_twitter_key"),
+            ("TWITTER_API_SECRET", "test_twitter_secret"),
+            ("TWITTER_ACCESS_TOKEN", "test_access_token"),
+            ("TWITTER_ACCESS_SECRET", "test_access_secret"),
+            ("TWITTER_BEARER_TOKEN", "test_bearer_token"),
+            ("TWITTER_USERNAME", "tomflash"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
     #[test]
     fn test_config_from_env() {
-        // Set test environment variables
-        std::env::set_var("DATABASE_URL", "test_url");
-        std::env::set_var("OPENAI_API_KEY", "test_key");
-        std::env::set_var("ETH_MAINNET_RPC", "test_rpc");
-        std::env::set_var("TWITTER_API_KEY", "test_twitter_key");
-        std::env::set_var("TWITTER_API_SECRET", "test_twitter_secret");
-        std::env::set_var("TWITTER_ACCESS_TOKEN", "test_access_token");
-        std::env::set_var("TWITTER_ACCESS_SECRET", "test_access_secret");
-        std::env::set_var("TWITTER_BEARER_TOKEN", "test_bearer_token");
-
-        let config = Config::from_env().unwrap();
+        let mut env = required_env();
+        env.insert("INTERACTION_INTERVAL_SECS".to_string(), "120".to_string());
+
+        let config = Config::from_layers(None, &env, &[]).unwrap();
         assert_eq!(config.database_url, "test_url");
-        assert_eq!(config.openai_api_key, "test_key");
-        assert_eq!(config.eth_rpc_url, "test_rpc");
-        assert_eq!(config.twitter_config.api_key, "test_twitter_key");
+        assert_eq!(config.embeddings.api_key, "test_key");
+        assert_eq!(config.wallet.rpc_url, "test_rpc");
+        assert_eq!(config.twitter.api_key, "test_twitter_key");
+        assert_eq!(config.scheduling.interaction_interval, Duration::from_secs(120));
+        assert_eq!(config.scheduling.cognitive_cycle_interval, Duration::from_secs(1800));
+        assert_eq!(config.shutdown.drain_deadline, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_layer_precedence() {
+        let file: Table = r#"
+            [llm]
+            model = "from-file"
+
+            [memory]
+            significance_threshold = 0.7
+
+            [wallet]
+            chain_id = 8453
+        "#
+        .parse()
+        .unwrap();
+
+        let mut env = required_env();
+        env.insert("TOMFLASH__LLM__MODEL".to_string(), "from-env".to_string());
+        env.insert("ETH_CHAIN_ID".to_string(), "10".to_string());
+
+        let cli = vec![("llm.model".to_string(), "from-cli".to_string())];
+
+        let config = Config::from_layers(Some(file), &env, &cli).unwrap();
+        assert_eq!(config.llm.model, "from-cli");
+        assert_eq!(config.wallet.chain_id, 10);
+        assert_eq!(config.memory.significance_threshold, 0.7);
+        assert_eq!(config.embeddings.model, "text-embedding-3-small");
+    }
+
+    #[test]
+    fn test_hex_private_key_stays_a_string() {
+        let mut env = required_env();
+        env.insert("ETH_PRIVATE_KEY".to_string(), "0x1234".to_string());
+
+        let config = Config::from_layers(None, &env, &[]).unwrap();
+        assert_eq!(config.wallet.private_key, "0x1234");
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config = Config::from_layers(None, &required_env(), &[]).unwrap();
+        let printed = format!("{:?}", config);
+
+        let secrets = config.secrets();
+        for (key, value) in &secrets {
+            assert!(!printed.contains(value), "{} is printed", key);
+        }
+        assert!(secrets.iter().any(|(key, _)| *key == "database_url"));
+        assert!(secrets.iter().any(|(key, _)| *key == "wallet.rpc_url"));
+        assert!(!printed.contains("test_url"));
+        assert!(printed.contains("api_key: [redacted]"));
+        assert!(printed.contains("client_secret: \"\""));
+        assert!(printed.contains("username: \"tomflash\""));
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let env: HashMap<String, String> = [
+            ("DATABASE_URL", "test_url"),
+            ("MEMORY_SIGNIFICANCE_THRESHOLD", "1.5"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let problems = match Config::from_layers(None, &env, &[]) {
+            Err(ConfigError::Invalid(problems)) => problems,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+
+        assert!(problems.iter().any(|p| p.contains("TWITTER_API_KEY")));
+        assert!(problems.iter().any(|p| p.contains("wallet.private_key")));
+        assert!(problems.iter().any(|p| p.contains("significance_threshold")));
+        assert!(problems.len() > 5);
+    }
+
+    #[test]
+    fn test_oauth2_replaces_oauth1_credentials() {
+        let mut env = required_env();
+        for name in ["TWITTER_API_KEY", "TWITTER_API_SECRET", "TWITTER_ACCESS_TOKEN", "TWITTER_ACCESS_SECRET"] {
+            env.remove(name);
+        }
+        env.insert("TWITTER_AUTH".to_string(), "oauth2".to_string());
+
+        let problems = match Config::from_layers(None, &env, &[]) {
+            Err(ConfigError::Invalid(problems)) => problems,
+            other => panic!("expected validation errors, got {:?}", other),
+        };
+        assert!(problems.iter().any(|p| p.contains("TWITTER_CLIENT_ID")));
+        assert!(!problems.iter().any(|p| p.contains("TWITTER_API_KEY")));
+
+        env.insert("TWITTER_CLIENT_ID".to_string(), "client".to_string());
+        env.insert("TWITTER_TOKEN_ENCRYPTION_KEY".to_string(), "dG9vIHNob3J0".to_string());
+        match Config::from_layers(None, &env, &[]) {
+            Err(ConfigError::Invalid(problems)) => {
+                assert_eq!(problems.len(), 1);
+                assert!(problems[0].contains("32-byte key"));
+            }
+            other => panic!("expected a key length error, got {:?}", other),
+        }
+
+        env.insert(
+            "TWITTER_TOKEN_ENCRYPTION_KEY".to_string(),
+            BASE64.encode([7u8; 32]),
+        );
+        let config = Config::from_layers(None, &env, &[]).unwrap();
+        assert_eq!(config.twitter.auth, XAuthMethod::OAuth2);
+        assert!(config.twitter.oauth2.scopes.iter().any(|s| s == "offline.access"));
+    }
+
+    #[test]
+    fn test_admin_token_required_off_loopback() {
+        let overrides = |addr: &str| vec![
+            ("approval.enabled".to_string(), "true".to_string()),
+            ("approval.admin_addr".to_string(), addr.to_string()),
+        ];
+
+        assert!(Config::from_layers(None, &required_env(), &overrides("127.0.0.1:8787")).is_ok());
+
+        match Config::from_layers(None, &required_env(), &overrides("0.0.0.0:8787")) {
+            Err(ConfigError::Invalid(problems)) => assert!(problems[0].contains("approval.admin_token")),
+            other => panic!("expected a missing token error, got {:?}", other),
+        }
+
+        let mut env = required_env();
+        env.insert("ADMIN_TOKEN".to_string(), "s3cret".to_string());
+        assert!(Config::from_layers(None, &env, &overrides("0.0.0.0:8787")).is_ok());
+        assert!(Config::from_layers(None, &env, &overrides("localhost")).is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_invalid_override_type() {
+        let mut env = required_env();
+        env.insert("ETH_CHAIN_ID".to_string(), "mainnet".to_string());
+
+        let cli = vec![("scheduling.interaction_interval_secs".to_string(), "soon".to_string())];
+
+        match Config::from_layers(None, &env, &cli) {
+            Err(ConfigError::Invalid(problems)) => assert_eq!(problems.len(), 2),
+            other => panic!("expected override errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_local_provider_without_api_key() {
+        let mut env = required_env();
+        env.remove("HYPERBOLIC_API_KEY");
+
+        let cli = vec![
+            ("llm.provider".to_string(), "vllm".to_string()),
+            ("llm.base_url".to_string(), "http://staging:8000/v1".to_string()),
+        ];
+
+        let config = Config::from_layers(None, &env, &cli).unwrap();
+        assert_eq!(config.llm.provider, ProviderKind::Vllm);
+        assert_eq!(config.llm.endpoint(), "http://staging:8000/v1");
+        assert_eq!(config.embeddings.endpoint(), "https://api.openai.com/v1");
+    }
+
+    #[test]
+    fn test_hosted_provider_requires_api_key() {
+        let mut env = required_env();
+        env.remove("HYPERBOLIC_API_KEY");
+
+        match Config::from_layers(None, &env, &[]) {
+            Err(ConfigError::Invalid(problems)) => {
+                assert!(problems.iter().any(|p| p.contains("llm.api_key")));
+            }
+            other => panic!("expected missing llm.api_key, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unknown_file_key_rejected() {
+        let file: Table = "[llm]\nmodle = \"typo\"".parse().unwrap();
+        let result = Config::from_layers(Some(file), &required_env(), &[]);
+        assert!(matches!(result, Err(ConfigError::Parse(_))));
+    }
+}
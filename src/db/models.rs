@@ -1,9 +1,24 @@
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use serde::{Serialize, Deserialize};
-use uuid::Uuid;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use diesel_async::{
+    pooled_connection::AsyncDieselConnectionManager, scoped_futures::ScopedFutureExt, AsyncConnection,
+    AsyncPgConnection, RunQueryDsl,
+};
+use serde::Serialize;
 
 use crate::db::schema::*;
+use crate::db::vector::Embedding;
+use crate::db::Pool;
+
+type PooledConnection<'a> = bb8::PooledConnection<'a, AsyncDieselConnectionManager<AsyncPgConnection>>;
+
+/// A connection from the pool, with a pool timeout reported as a query error
+async fn connection(pool: &Pool) -> QueryResult<PooledConnection<'_>> {
+    pool.get().await.map_err(|e| {
+        DieselError::DatabaseError(DatabaseErrorKind::UnableToSendCommand, Box::new(e.to_string()))
+    })
+}
 
 #[derive(Debug, Queryable, Selectable, Identifiable, Serialize)]
 #[diesel(table_name = users)]
@@ -36,9 +51,13 @@ pub struct Post {
     pub comment_count: i32,
     pub image_path: Option<String>,
     pub tweet_id: Option<String>,
+    /// Tweet id of the first part, for posts split into a thread
+    pub thread_id: Option<String>,
+    /// 1-based position within the thread
+    pub thread_position: Option<i32>,
 }
 
-#[derive(Debug, Insertable)]
+#[derive(Debug, Clone, Insertable)]
 #[diesel(table_name = posts)]
 pub struct NewPost {
     pub content: String,
@@ -47,34 +66,245 @@ pub struct NewPost {
     pub post_type: String,
     pub image_path: Option<String>,
     pub tweet_id: Option<String>,
+    pub thread_id: Option<String>,
+    pub thread_position: Option<i32>,
 }
 
-#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[derive(Debug, Clone, Queryable, QueryableByName, Selectable, Identifiable)]
 #[diesel(table_name = long_term_memories)]
 pub struct LongTermMemory {
     pub id: i32,
     pub content: String,
-    pub embedding: Vec<f32>,
+    pub embedding: Embedding,
     pub significance_score: f32,
     pub created_at: DateTime<Utc>,
+    /// Times the memory was recalled
+    pub access_count: i32,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+    /// Set when the memory faded and was forgotten
+    pub archived_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Insertable)]
 #[diesel(table_name = long_term_memories)]
 pub struct NewLongTermMemory {
     pub content: String,
-    pub embedding: Vec<f32>,
+    pub embedding: Embedding,
     pub significance_score: f32,
 }
 
+/// A memory returned by a similarity search
+#[derive(Debug, Clone, QueryableByName)]
+pub struct ScoredMemory {
+    #[diesel(embed)]
+    pub memory: LongTermMemory,
+    /// Cosine similarity to the query, 1.0 for the same direction
+    #[diesel(sql_type = diesel::sql_types::Float4)]
+    pub similarity: f32,
+}
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = memory_provenance)]
+pub struct MemoryProvenance {
+    pub id: i32,
+    /// The consolidated memory
+    pub memory_id: i32,
+    pub source_id: i32,
+    pub source_content: String,
+    pub source_created_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = memory_provenance)]
+pub struct NewMemoryProvenance {
+    pub memory_id: i32,
+    pub source_id: i32,
+    pub source_content: String,
+    pub source_created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = x_oauth_tokens)]
+pub struct XOAuthToken {
+    pub id: i32,
+    pub username: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub scope: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = x_oauth_tokens)]
+pub struct NewXOAuthToken {
+    pub username: String,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub scope: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = x_checkpoints, primary_key(name))]
+pub struct XCheckpoint {
+    pub name: String,
+    pub since_id: String,
+    pub updated_at: DateTime<Utc>,
+    /// Where an unfinished poll stopped paging back; older tweets come next
+    pub until_id: Option<String>,
+    /// Newest tweet the unfinished poll saw, the next `since_id`
+    pub newest_id: Option<String>,
+}
+
+#[derive(Debug, Insertable, AsChangeset)]
+#[diesel(table_name = x_checkpoints, treat_none_as_null = true)]
+pub struct NewXCheckpoint {
+    pub name: String,
+    pub since_id: String,
+    pub until_id: Option<String>,
+    pub newest_id: Option<String>,
+}
+
+#[derive(Debug, Queryable, Selectable, Identifiable)]
+#[diesel(table_name = handled_tweets, primary_key(tweet_id))]
+pub struct HandledTweet {
+    pub tweet_id: String,
+    pub kind: String,
+    pub handled_at: DateTime<Utc>,
+    pub status: String,
+    /// Times the tweet was handed out to be answered
+    pub attempts: i32,
+    pub claimed_at: Option<DateTime<Utc>>,
+    /// The tweet as polled, to hand out again after a failed attempt
+    pub tweet: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = handled_tweets)]
+pub struct NewHandledTweet {
+    pub tweet_id: String,
+    pub kind: String,
+    pub status: String,
+    pub tweet: Option<serde_json::Value>,
+}
+
+/// A tweet waiting to be sent, or the record of how sending it went
+#[derive(Debug, Clone, Queryable, QueryableByName, Selectable, Identifiable)]
+#[diesel(table_name = outbox)]
+pub struct OutboxPost {
+    pub id: i32,
+    pub idempotency_key: String,
+    pub user_id: i32,
+    pub username: String,
+    pub content: String,
+    pub post_type: String,
+    /// Tweet the first part replies to
+    pub reply_to_id: Option<String>,
+    /// Earlier thread part this one replies to once that is sent
+    pub parent_id: Option<i32>,
+    pub thread_position: Option<i32>,
+    pub image_path: Option<String>,
+    pub alt_text: Option<String>,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub tweet_id: Option<String>,
+    pub thread_id: Option<String>,
+    pub post_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = outbox)]
+pub struct NewOutboxPost {
+    pub idempotency_key: String,
+    pub user_id: i32,
+    pub username: String,
+    pub content: String,
+    pub post_type: String,
+    pub reply_to_id: Option<String>,
+    pub parent_id: Option<i32>,
+    pub thread_position: Option<i32>,
+    pub image_path: Option<String>,
+    pub alt_text: Option<String>,
+}
+
+/// Generated output waiting for, or past, review in approval mode
+#[derive(Debug, Clone, Queryable, QueryableByName, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = proposals)]
+pub struct Proposal {
+    pub id: i32,
+    pub category: String,
+    pub status: String,
+    pub content: String,
+    pub original_content: Option<String>,
+    pub reply_to_id: Option<String>,
+    pub to_address: Option<String>,
+    pub amount_eth: Option<f64>,
+    pub decided_by: Option<String>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub result: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub image_path: Option<String>,
+    pub alt_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = proposals)]
+pub struct NewProposal {
+    pub category: String,
+    pub status: String,
+    pub content: String,
+    pub original_content: Option<String>,
+    pub reply_to_id: Option<String>,
+    pub to_address: Option<String>,
+    pub amount_eth: Option<f64>,
+    pub decided_by: Option<String>,
+    pub reason: Option<String>,
+    pub image_path: Option<String>,
+    pub alt_text: Option<String>,
+}
+
+#[derive(Debug, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = content_filter_audit)]
+pub struct ContentAudit {
+    pub id: i32,
+    pub proposal_id: Option<i32>,
+    pub category: String,
+    pub decision: String,
+    pub content: String,
+    pub rewritten: Option<String>,
+    pub findings: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = content_filter_audit)]
+pub struct NewContentAudit {
+    pub proposal_id: Option<i32>,
+    pub category: String,
+    pub decision: String,
+    pub content: String,
+    pub rewritten: Option<String>,
+    pub findings: serde_json::Value,
+}
+
 impl User {
     pub async fn create(
-        pool: &DbPool,
+        pool: &Pool,
         new_user: NewUser,
     ) -> QueryResult<User> {
         use crate::db::schema::users::dsl::*;
         
-        let mut conn = pool.get().await?;
+        let mut conn = connection(pool).await?;
         diesel::insert_into(users)
             .values(&new_user)
             .get_result(&mut conn)
@@ -82,18 +312,675 @@ impl User {
     }
     
     pub async fn find_by_username(
-        pool: &DbPool,
+        pool: &Pool,
         username_query: &str,
     ) -> QueryResult<Option<User>> {
         use crate::db::schema::users::dsl::*;
         
-        let mut conn = pool.get().await?;
+        let mut conn = connection(pool).await?;
         users
             .filter(username.eq(username_query))
             .first(&mut conn)
             .await
             .optional()
     }
+
+    pub async fn find_or_create(
+        pool: &Pool,
+        username_query: &str,
+    ) -> QueryResult<User> {
+        if let Some(user) = Self::find_by_username(pool, username_query).await? {
+            return Ok(user);
+        }
+
+        Self::create(pool, NewUser {
+            username: username_query.to_string(),
+            email: format!("{}@agent.local", username_query),
+        }).await
+    }
+}
+
+impl XOAuthToken {
+    pub async fn find_by_username(
+        pool: &Pool,
+        username_query: &str,
+    ) -> QueryResult<Option<XOAuthToken>> {
+        use crate::db::schema::x_oauth_tokens::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        x_oauth_tokens
+            .filter(username.eq(username_query))
+            .first(&mut conn)
+            .await
+            .optional()
+    }
+
+    /// Inserts the account's token or replaces the existing one
+    pub async fn upsert(
+        pool: &Pool,
+        new_token: NewXOAuthToken,
+    ) -> QueryResult<XOAuthToken> {
+        use crate::db::schema::x_oauth_tokens::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::insert_into(x_oauth_tokens)
+            .values(&new_token)
+            .on_conflict(username)
+            .do_update()
+            .set(&new_token)
+            .get_result(&mut conn)
+            .await
+    }
+}
+
+impl XCheckpoint {
+    pub async fn find(
+        pool: &Pool,
+        name_query: &str,
+    ) -> QueryResult<Option<XCheckpoint>> {
+        use crate::db::schema::x_checkpoints::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        x_checkpoints
+            .filter(name.eq(name_query))
+            .select(XCheckpoint::as_select())
+            .first(&mut conn)
+            .await
+            .optional()
+    }
+
+    pub async fn save(
+        pool: &Pool,
+        checkpoint: NewXCheckpoint,
+    ) -> QueryResult<()> {
+        use crate::db::schema::x_checkpoints::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::insert_into(x_checkpoints)
+            .values(&checkpoint)
+            .on_conflict(name)
+            .do_update()
+            .set(&checkpoint)
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl HandledTweet {
+    pub const PENDING: &'static str = "pending";
+    pub const ANSWERED: &'static str = "answered";
+    pub const FAILED: &'static str = "failed";
+
+    /// Records tweets to be handled, leaving ones already known as they are.
+    /// Returns how many were new.
+    pub async fn record(
+        pool: &Pool,
+        new_handled: Vec<NewHandledTweet>,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::handled_tweets::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::insert_into(handled_tweets)
+            .values(&new_handled)
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await
+    }
+
+    /// Claims the pending tweets of `of_kind` that are not already claimed
+    /// within `lease` and have attempts left, counting an attempt for each
+    pub async fn claim_pending(
+        pool: &Pool,
+        of_kind: &str,
+        lease: chrono::Duration,
+        max_attempts: i32,
+    ) -> QueryResult<Vec<HandledTweet>> {
+        use crate::db::schema::handled_tweets::dsl::*;
+
+        let now = Utc::now();
+        let mut conn = connection(pool).await?;
+        diesel::update(handled_tweets)
+            .filter(kind.eq(of_kind))
+            .filter(status.eq(Self::PENDING))
+            .filter(attempts.lt(max_attempts))
+            .filter(claimed_at.is_null().or(claimed_at.lt(now - lease)))
+            .set((claimed_at.eq(now), attempts.eq(attempts + 1)))
+            .returning(HandledTweet::as_returning())
+            .get_results(&mut conn)
+            .await
+    }
+
+    pub async fn mark_answered(pool: &Pool, id: &str) -> QueryResult<usize> {
+        use crate::db::schema::handled_tweets::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(handled_tweets.find(id))
+            .set(status.eq(Self::ANSWERED))
+            .execute(&mut conn)
+            .await
+    }
+
+    /// Hands a claimed tweet back for the next poll, or gives up on it once
+    /// `max_attempts` are used
+    pub async fn release(pool: &Pool, id: &str, max_attempts: i32) -> QueryResult<usize> {
+        use crate::db::schema::handled_tweets::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(handled_tweets.find(id))
+            .filter(status.eq(Self::PENDING))
+            .filter(attempts.ge(max_attempts))
+            .set(status.eq(Self::FAILED))
+            .execute(&mut conn)
+            .await?;
+        diesel::update(handled_tweets.find(id))
+            .set(claimed_at.eq(None::<DateTime<Utc>>))
+            .execute(&mut conn)
+            .await
+    }
+}
+
+impl OutboxPost {
+    pub const PENDING: &'static str = "pending";
+    pub const SENDING: &'static str = "sending";
+    pub const SENT: &'static str = "sent";
+    pub const DEAD: &'static str = "dead";
+
+    /// Queues `parts` in one transaction, each replying to the part before it.
+    /// Returns nothing when the first part's idempotency key is already queued.
+    pub async fn enqueue(
+        pool: &Pool,
+        parts: Vec<NewOutboxPost>,
+    ) -> QueryResult<Vec<OutboxPost>> {
+        use crate::db::schema::outbox::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        conn.transaction(|conn| async move {
+            let mut queued: Vec<OutboxPost> = Vec::with_capacity(parts.len());
+
+            for (index, mut part) in parts.into_iter().enumerate() {
+                part.parent_id = queued.last().map(|previous| previous.id);
+
+                let row = diesel::insert_into(outbox)
+                    .values(&part)
+                    .on_conflict(idempotency_key)
+                    .do_nothing()
+                    .get_result::<OutboxPost>(conn)
+                    .await
+                    .optional()?;
+
+                match row {
+                    Some(row) => queued.push(row),
+                    None if index == 0 => return Ok(Vec::new()),
+                    None => return Err(diesel::result::Error::RollbackTransaction),
+                }
+            }
+
+            Ok(queued)
+        }.scope_boxed())
+        .await
+    }
+
+    /// Claims the oldest due row whose parent has been sent, moving it to
+    /// `sending`. Rows left `sending` since before `stale_before` are claimed
+    /// again, since whoever held them has gone away.
+    pub async fn claim_next(
+        pool: &Pool,
+        stale_before: DateTime<Utc>,
+    ) -> QueryResult<Option<OutboxPost>> {
+        let mut conn = connection(pool).await?;
+        diesel::sql_query(r#"
+            UPDATE outbox
+            SET status = 'sending', attempts = attempts + 1, claimed_at = NOW()
+            WHERE id = (
+                SELECT o.id
+                FROM outbox o
+                LEFT JOIN outbox parent ON parent.id = o.parent_id
+                WHERE ((o.status = 'pending' AND o.next_attempt_at <= NOW())
+                       OR (o.status = 'sending' AND o.claimed_at < $1))
+                  AND (o.parent_id IS NULL OR parent.status = 'sent')
+                ORDER BY o.id
+                LIMIT 1
+                FOR UPDATE OF o SKIP LOCKED
+            )
+            RETURNING *
+        "#)
+        .bind::<diesel::sql_types::Timestamptz, _>(stale_before)
+        .get_result(&mut conn)
+        .await
+        .optional()
+    }
+
+    pub async fn find(
+        pool: &Pool,
+        outbox_id: i32,
+    ) -> QueryResult<OutboxPost> {
+        use crate::db::schema::outbox::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        outbox
+            .find(outbox_id)
+            .first(&mut conn)
+            .await
+    }
+
+    /// Stores the sent tweet as a post and records it on the outbox row
+    pub async fn mark_sent(
+        pool: &Pool,
+        outbox_id: i32,
+        new_post: NewPost,
+    ) -> QueryResult<Post> {
+        use crate::db::schema::outbox::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        conn.transaction(|conn| async move {
+            let post: Post = diesel::insert_into(crate::db::schema::posts::table)
+                .values(&new_post)
+                .get_result(conn)
+                .await?;
+
+            diesel::update(outbox.find(outbox_id))
+                .set((
+                    status.eq(Self::SENT),
+                    tweet_id.eq(&post.tweet_id),
+                    thread_id.eq(&post.thread_id),
+                    post_id.eq(post.id),
+                ))
+                .execute(conn)
+                .await?;
+
+            Ok(post)
+        }.scope_boxed())
+        .await
+    }
+
+    /// Puts a failed row back in the queue until `retry_at`
+    pub async fn schedule_retry(
+        pool: &Pool,
+        outbox_id: i32,
+        error: &str,
+        retry_at: DateTime<Utc>,
+    ) -> QueryResult<()> {
+        use crate::db::schema::outbox::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(outbox.find(outbox_id))
+            .set((
+                status.eq(Self::PENDING),
+                next_attempt_at.eq(retry_at),
+                last_error.eq(error),
+            ))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gives up on a row and the thread parts that would have replied to it.
+    /// Returns how many rows were dead-lettered.
+    pub async fn dead_letter(
+        pool: &Pool,
+        outbox_id: i32,
+        error: &str,
+    ) -> QueryResult<usize> {
+        let mut conn = connection(pool).await?;
+        diesel::sql_query(r#"
+            WITH RECURSIVE doomed AS (
+                SELECT id FROM outbox WHERE id = $1
+                UNION ALL
+                SELECT o.id FROM outbox o JOIN doomed d ON o.parent_id = d.id
+            )
+            UPDATE outbox
+            SET status = 'dead', last_error = $2
+            WHERE id IN (SELECT id FROM doomed)
+        "#)
+        .bind::<diesel::sql_types::Integer, _>(outbox_id)
+        .bind::<diesel::sql_types::Text, _>(error)
+        .execute(&mut conn)
+        .await
+    }
+}
+
+impl Proposal {
+    pub const PENDING: &'static str = "pending";
+    pub const APPROVED: &'static str = "approved";
+    pub const REJECTED: &'static str = "rejected";
+    pub const EXECUTING: &'static str = "executing";
+    pub const EXECUTED: &'static str = "executed";
+    pub const FAILED: &'static str = "failed";
+
+    pub async fn create(
+        pool: &Pool,
+        new_proposal: NewProposal,
+    ) -> QueryResult<Proposal> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::insert_into(proposals)
+            .values(&new_proposal)
+            .get_result(&mut conn)
+            .await
+    }
+
+    pub async fn find(
+        pool: &Pool,
+        proposal_id: i32,
+    ) -> QueryResult<Option<Proposal>> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        proposals
+            .find(proposal_id)
+            .first(&mut conn)
+            .await
+            .optional()
+    }
+
+    /// Oldest first, optionally only those in `status_query`
+    pub async fn list(
+        pool: &Pool,
+        status_query: Option<&str>,
+        limit: i64,
+    ) -> QueryResult<Vec<Proposal>> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        let mut query = proposals.order(id.asc()).limit(limit).into_boxed();
+        if let Some(status_query) = status_query {
+            query = query.filter(status.eq(status_query.to_string()));
+        }
+        query.load(&mut conn).await
+    }
+
+    /// The latest transfer proposed to `address`, whatever came of it
+    pub async fn find_transfer_to(
+        pool: &Pool,
+        address: &str,
+    ) -> QueryResult<Option<Proposal>> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        proposals
+            .filter(category.eq("transfer"))
+            .filter(to_address.eq(address))
+            .order(id.desc())
+            .first(&mut conn)
+            .await
+            .optional()
+    }
+
+    /// Approves or rejects a pending proposal, replacing its content with
+    /// `new_content` if given. Returns `None` if it isn't pending.
+    pub async fn decide(
+        pool: &Pool,
+        proposal_id: i32,
+        new_status: &str,
+        new_content: Option<&str>,
+        decided_by: &str,
+        reason: Option<&str>,
+    ) -> QueryResult<Option<Proposal>> {
+        use diesel::sql_types::{Integer, Nullable, Text};
+
+        let mut conn = connection(pool).await?;
+        diesel::sql_query(r#"
+            UPDATE proposals
+            SET status = $2,
+                original_content = CASE
+                    WHEN $3 IS NOT NULL AND $3 <> content THEN COALESCE(original_content, content)
+                    ELSE original_content
+                END,
+                content = COALESCE($3, content),
+                decided_by = $4,
+                decided_at = NOW(),
+                reason = $5
+            WHERE id = $1 AND status = 'pending'
+            RETURNING *
+        "#)
+        .bind::<Integer, _>(proposal_id)
+        .bind::<Text, _>(new_status)
+        .bind::<Nullable<Text>, _>(new_content)
+        .bind::<Text, _>(decided_by)
+        .bind::<Nullable<Text>, _>(reason)
+        .get_result(&mut conn)
+        .await
+        .optional()
+    }
+
+    /// Claims the oldest approved proposal for execution
+    pub async fn claim_approved(
+        pool: &Pool,
+    ) -> QueryResult<Option<Proposal>> {
+        let mut conn = connection(pool).await?;
+        diesel::sql_query(r#"
+            UPDATE proposals
+            SET status = 'executing'
+            WHERE id = (
+                SELECT id FROM proposals
+                WHERE status = 'approved'
+                ORDER BY id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *
+        "#)
+        .get_result(&mut conn)
+        .await
+        .optional()
+    }
+
+    /// Notes a claimed proposal's result before it is finished, so whatever it
+    /// did outside the database (a transaction hash) survives a crash
+    pub async fn record_result(
+        pool: &Pool,
+        proposal_id: i32,
+        outcome: &str,
+    ) -> QueryResult<()> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(proposals.find(proposal_id))
+            .set(result.eq(outcome))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fails proposals left executing by a process that stopped mid-way.
+    /// They may have taken effect, so they are never retried; any recorded
+    /// result is kept after "interrupted". Returns how many were failed.
+    pub async fn fail_interrupted(pool: &Pool) -> QueryResult<usize> {
+        let mut conn = connection(pool).await?;
+        diesel::sql_query(r#"
+            UPDATE proposals
+            SET status = 'failed',
+                result = COALESCE('interrupted after ' || result, 'interrupted')
+            WHERE status = 'executing'
+        "#)
+        .execute(&mut conn)
+        .await
+    }
+
+    /// Records how executing a claimed proposal went
+    pub async fn finish(
+        pool: &Pool,
+        proposal_id: i32,
+        new_status: &str,
+        outcome: &str,
+    ) -> QueryResult<()> {
+        use crate::db::schema::proposals::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(proposals.find(proposal_id))
+            .set((status.eq(new_status), result.eq(outcome)))
+            .execute(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl ContentAudit {
+    pub async fn record(
+        pool: &Pool,
+        entry: NewContentAudit,
+    ) -> QueryResult<ContentAudit> {
+        use crate::db::schema::content_filter_audit::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::insert_into(content_filter_audit)
+            .values(&entry)
+            .get_result(&mut conn)
+            .await
+    }
+
+    pub async fn for_proposal(
+        pool: &Pool,
+        proposal_query: i32,
+    ) -> QueryResult<Vec<ContentAudit>> {
+        use crate::db::schema::content_filter_audit::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        content_filter_audit
+            .filter(proposal_id.eq(proposal_query))
+            .order(id.asc())
+            .load(&mut conn)
+            .await
+    }
+}
+
+impl LongTermMemory {
+    pub async fn create(
+        pool: &Pool,
+        entry: NewLongTermMemory,
+    ) -> QueryResult<LongTermMemory> {
+        let mut conn = connection(pool).await?;
+        Self::insert(&mut conn, &entry).await
+    }
+
+    /// Memories that have not been archived, oldest first
+    pub async fn active(pool: &Pool) -> QueryResult<Vec<LongTermMemory>> {
+        use crate::db::schema::long_term_memories::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        long_term_memories
+            .filter(archived_at.is_null())
+            .select(LongTermMemory::as_select())
+            .order(id.asc())
+            .load(&mut conn)
+            .await
+    }
+
+    pub async fn record_access(
+        pool: &Pool,
+        ids: &[i32],
+        at: DateTime<Utc>,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::long_term_memories::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(long_term_memories.filter(id.eq_any(ids)))
+            .set((access_count.eq(access_count + 1), last_accessed_at.eq(at)))
+            .execute(&mut conn)
+            .await
+    }
+
+    pub async fn archive(
+        pool: &Pool,
+        ids: &[i32],
+        at: DateTime<Utc>,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::long_term_memories::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        diesel::update(long_term_memories.filter(id.eq_any(ids)).filter(archived_at.is_null()))
+            .set(archived_at.eq(at))
+            .execute(&mut conn)
+            .await
+    }
+
+    /// Stores `entry` in place of `sources` in one transaction, recording
+    /// where it came from. Rolls back if any source is already gone.
+    pub async fn consolidate(
+        pool: &Pool,
+        entry: NewLongTermMemory,
+        sources: &[LongTermMemory],
+    ) -> QueryResult<LongTermMemory> {
+        let source_ids: Vec<i32> = sources.iter().map(|source| source.id).collect();
+
+        let mut conn = connection(pool).await?;
+        conn.transaction(|conn| async move {
+            let memory = Self::insert(conn, &entry).await?;
+
+            // Sources that were merged before hand their own sources on
+            diesel::update(memory_provenance::table)
+                .filter(memory_provenance::memory_id.eq_any(&source_ids))
+                .set(memory_provenance::memory_id.eq(memory.id))
+                .execute(conn)
+                .await?;
+
+            let links: Vec<NewMemoryProvenance> = sources
+                .iter()
+                .map(|source| NewMemoryProvenance {
+                    memory_id: memory.id,
+                    source_id: source.id,
+                    source_content: source.content.clone(),
+                    source_created_at: source.created_at,
+                })
+                .collect();
+            diesel::insert_into(memory_provenance::table)
+                .values(&links)
+                .execute(conn)
+                .await?;
+
+            let deleted = diesel::delete(long_term_memories::table)
+                .filter(long_term_memories::id.eq_any(&source_ids))
+                .execute(conn)
+                .await?;
+            if deleted != source_ids.len() {
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+
+            Ok(memory)
+        }.scope_boxed())
+        .await
+    }
+
+    async fn insert(
+        conn: &mut diesel_async::AsyncPgConnection,
+        entry: &NewLongTermMemory,
+    ) -> QueryResult<LongTermMemory> {
+        // Bound as real[] so this works on either column type; pgvector
+        // casts it on assignment
+        diesel::sql_query(r#"
+            INSERT INTO long_term_memories (content, embedding, significance_score)
+            VALUES ($1, $2, $3)
+            RETURNING id, content, embedding, significance_score, created_at,
+                      access_count, last_accessed_at, archived_at
+        "#)
+        .bind::<diesel::sql_types::Text, _>(&entry.content)
+        .bind::<diesel::sql_types::Array<diesel::sql_types::Float4>, _>(&entry.embedding.0)
+        .bind::<diesel::sql_types::Float4, _>(entry.significance_score)
+        .get_result(conn)
+        .await
+    }
+}
+
+impl MemoryProvenance {
+    pub async fn for_memory(
+        pool: &Pool,
+        memory: i32,
+    ) -> QueryResult<Vec<MemoryProvenance>> {
+        use crate::db::schema::memory_provenance::dsl::*;
+
+        let mut conn = connection(pool).await?;
+        memory_provenance
+            .filter(memory_id.eq(memory))
+            .order(source_id.asc())
+            .load(&mut conn)
+            .await
+    }
 }
 
 // Similar implementations for Post and LongTermMemory...
\ No newline at end of file
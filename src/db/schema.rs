@@ -1,5 +1,11 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "vector"))]
+    pub struct Vector;
+}
+
 diesel::table! {
     users (id) {
         id -> Int4,
@@ -22,23 +28,148 @@ diesel::table! {
         comment_count -> Int4,
         image_path -> Nullable<Varchar>,
         tweet_id -> Nullable<Varchar>,
+        thread_id -> Nullable<Varchar>,
+        thread_position -> Nullable<Int4>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Vector;
+
     long_term_memories (id) {
         id -> Int4,
         content -> Text,
-        embedding -> Array<Float4>,
+        embedding -> Vector,
         significance_score -> Float4,
         created_at -> Timestamptz,
+        access_count -> Int4,
+        last_accessed_at -> Nullable<Timestamptz>,
+        archived_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    x_oauth_tokens (id) {
+        id -> Int4,
+        username -> Varchar,
+        nonce -> Bytea,
+        ciphertext -> Bytea,
+        scope -> Text,
+        expires_at -> Timestamptz,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    x_checkpoints (name) {
+        name -> Varchar,
+        since_id -> Varchar,
+        updated_at -> Timestamptz,
+        until_id -> Nullable<Varchar>,
+        newest_id -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    handled_tweets (tweet_id) {
+        tweet_id -> Varchar,
+        kind -> Varchar,
+        handled_at -> Timestamptz,
+        status -> Varchar,
+        attempts -> Int4,
+        claimed_at -> Nullable<Timestamptz>,
+        tweet -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    outbox (id) {
+        id -> Int4,
+        idempotency_key -> Varchar,
+        user_id -> Int4,
+        username -> Varchar,
+        content -> Text,
+        post_type -> Varchar,
+        reply_to_id -> Nullable<Varchar>,
+        parent_id -> Nullable<Int4>,
+        thread_position -> Nullable<Int4>,
+        image_path -> Nullable<Varchar>,
+        alt_text -> Nullable<Text>,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        claimed_at -> Nullable<Timestamptz>,
+        last_error -> Nullable<Text>,
+        tweet_id -> Nullable<Varchar>,
+        thread_id -> Nullable<Varchar>,
+        post_id -> Nullable<Int4>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    proposals (id) {
+        id -> Int4,
+        category -> Varchar,
+        status -> Varchar,
+        content -> Text,
+        original_content -> Nullable<Text>,
+        reply_to_id -> Nullable<Varchar>,
+        to_address -> Nullable<Varchar>,
+        amount_eth -> Nullable<Float8>,
+        decided_by -> Nullable<Varchar>,
+        decided_at -> Nullable<Timestamptz>,
+        reason -> Nullable<Text>,
+        result -> Nullable<Text>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        image_path -> Nullable<Varchar>,
+        alt_text -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    content_filter_audit (id) {
+        id -> Int4,
+        proposal_id -> Nullable<Int4>,
+        category -> Varchar,
+        decision -> Varchar,
+        content -> Text,
+        rewritten -> Nullable<Text>,
+        findings -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    memory_provenance (id) {
+        id -> Int4,
+        memory_id -> Int4,
+        source_id -> Int4,
+        source_content -> Text,
+        source_created_at -> Timestamptz,
+        created_at -> Timestamptz,
     }
 }
 
 diesel::joinable!(posts -> users (user_id));
+diesel::joinable!(outbox -> users (user_id));
+diesel::joinable!(outbox -> posts (post_id));
+diesel::joinable!(content_filter_audit -> proposals (proposal_id));
+diesel::joinable!(memory_provenance -> long_term_memories (memory_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     users,
     posts,
     long_term_memories,
+    x_oauth_tokens,
+    x_checkpoints,
+    handled_tweets,
+    outbox,
+    proposals,
+    content_filter_audit,
+    memory_provenance,
 );
\ No newline at end of file
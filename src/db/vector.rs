@@ -0,0 +1,111 @@
+//! Rust side of the pgvector `vector` type, sent and read in its binary
+//! format: dimension count (u16), a reserved u16, then big-endian f32s.
+//! Without pgvector the column is `real[]`, which reads into the same type.
+
+use std::io::Write;
+use std::ops::Deref;
+
+use diesel::{
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::{Array, Float4},
+};
+use serde::{Deserialize, Serialize};
+
+use super::schema::sql_types::Vector;
+
+/// Dimensions of the `long_term_memories.embedding` column
+pub const EMBEDDING_DIMENSIONS: usize = 1536;
+
+/// Postgres OID of `real[]`
+const FLOAT4_ARRAY_OID: u32 = 1021;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Vector)]
+pub struct Embedding(pub Vec<f32>);
+
+impl From<Vec<f32>> for Embedding {
+    fn from(values: Vec<f32>) -> Self {
+        Self(values)
+    }
+}
+
+impl Deref for Embedding {
+    type Target = [f32];
+
+    fn deref(&self) -> &[f32] {
+        &self.0
+    }
+}
+
+impl ToSql<Vector, Pg> for Embedding {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(&encode(&self.0)?)?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Vector, Pg> for Embedding {
+    fn from_sql(value: PgValue<'_>) -> deserialize::Result<Self> {
+        if value.get_oid().get() == FLOAT4_ARRAY_OID {
+            return <Vec<f32> as FromSql<Array<Float4>, Pg>>::from_sql(value).map(Self);
+        }
+        decode(value.as_bytes()).map(Self)
+    }
+}
+
+fn encode(values: &[f32]) -> Result<Vec<u8>, String> {
+    let dimensions = u16::try_from(values.len())
+        .map_err(|_| format!("vector has {} dimensions, at most {} are supported", values.len(), u16::MAX))?;
+
+    let mut bytes = Vec::with_capacity(4 + 4 * values.len());
+    bytes.extend_from_slice(&dimensions.to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes());
+    for value in values {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+    Ok(bytes)
+}
+
+fn decode(bytes: &[u8]) -> deserialize::Result<Vec<f32>> {
+    if bytes.len() < 4 {
+        return Err("vector value is missing its header".into());
+    }
+
+    let dimensions = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+    let data = &bytes[4..];
+    if data.len() != 4 * dimensions {
+        return Err(format!("vector of {} dimensions has {} bytes of data", dimensions, data.len()).into());
+    }
+
+    Ok(data
+        .chunks_exact(4)
+        .map(|chunk| f32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_round_trip() {
+        let values = vec![0.5, -1.25, 0.0, f32::MIN_POSITIVE];
+        let bytes = encode(&values).unwrap();
+
+        assert_eq!(&bytes[..4], &[0, 4, 0, 0]);
+        assert_eq!(&bytes[4..8], &0.5f32.to_be_bytes());
+        assert_eq!(decode(&bytes).unwrap(), values);
+        assert_eq!(decode(&encode(&[]).unwrap()).unwrap(), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn test_rejects_malformed_values() {
+        let bytes = encode(&[1.0, 2.0]).unwrap();
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode(&bytes[..2]).is_err());
+        assert!(encode(&vec![0.0; u16::MAX as usize + 1]).is_err());
+    }
+}
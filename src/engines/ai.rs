@@ -1,56 +1,40 @@
-use async_openai::{
-    types::{CreateEmbeddingRequestArgs, CreateChatCompletionRequestArgs},
-    Client as OpenAIClient,
-};
-use serde_json::Value;
-use tracing::{debug, error, info};
-use std::time::Duration;
+use std::sync::Arc;
+use tracing::debug;
 
 use crate::{
     config::Config,
     utils::{Result, UtilError},
-    utils::traits::{Embeddable, LLMFormattable},
 };
 
+use super::persona::Persona;
 use super::prompts::{self, PromptContext};
+use super::providers::{self, ChatMessage, ChatProvider, EmbeddingProvider};
 
 pub struct Client {
-    openai: OpenAIClient,
-    hyperbolic: reqwest::Client,
-    config: Config,
+    chat: Arc<dyn ChatProvider>,
+    embeddings: Arc<dyn EmbeddingProvider>,
+    persona: Persona,
 }
 
 impl Client {
     pub fn new(config: &Config) -> Result<Self> {
-        let openai = OpenAIClient::new()
-            .with_api_key(&config.openai_api_key);
-
-        let hyperbolic = reqwest::Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
-
-        Ok(Self {
-            openai,
-            hyperbolic,
-            config: config.clone(),
-        })
+        Ok(Self::with_providers(
+            providers::chat_provider(&config.llm)?,
+            providers::embedding_provider(&config.embeddings)?,
+            Persona::load(&config.persona)?,
+        ))
     }
 
-    pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
-        let request = CreateEmbeddingRequestArgs::default()
-            .model("text-embedding-3-small")
-            .input(text)
-            .build()
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
-
-        let response = self.openai
-            .embeddings()
-            .create(request)
-            .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+    pub fn with_providers(
+        chat: Arc<dyn ChatProvider>,
+        embeddings: Arc<dyn EmbeddingProvider>,
+        persona: Persona,
+    ) -> Self {
+        Self { chat, embeddings, persona }
+    }
 
-        Ok(response.data[0].embedding.clone())
+    pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>> {
+        self.embeddings.embed(text).await
     }
 
     pub async fn generate_post(
@@ -60,110 +44,183 @@ impl Client {
         recent_posts: &[String],
         external_context: &[String],
     ) -> Result<String> {
+        let memories: Vec<&str> = std::iter::once(short_term_memory)
+            .chain(long_term_memories.iter().map(String::as_str))
+            .collect();
+        let memories = memories.join("\n");
         let context = PromptContext {
             posts_data: recent_posts,
             context_data: external_context,
-            memory_data: Some(short_term_memory),
+            memory_data: Some(&memories),
         };
 
-        let prompt = prompts::get_post_generation_prompt(&context);
-
-        let response = self.hyperbolic
-            .post("https://api.hyperbolic.xyz/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.config.hyperbolic_api_key))
-            .json(&serde_json::json!({
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": "You are a tweet formatter. Your only job is to take the input text and format it as a tweet."
-                    },
-                    {
-                        "role": "user",
-                        "content": prompt
-                    }
-                ]
-            }))
-            .send()
-            .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let prompt = prompts::get_post_generation_prompt(&self.persona, &context);
+        let content = self.in_character(prompt).await?;
 
-        if !response.status().is_success() {
-            error!("API error: {}", response.status());
-            return Err(UtilError::ConversionError("API request failed".to_string()));
-        }
+        debug!("Generated post: {}", content);
+        Ok(content)
+    }
 
-        let response_data: Value = response.json()
-            .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+    /// Internal monologue about the timeline, kept in short-term memory
+    pub async fn generate_thought(&self, recent_posts: &[String], external_context: &[String]) -> Result<String> {
+        let context = PromptContext {
+            posts_data: recent_posts,
+            context_data: external_context,
+            memory_data: None,
+        };
 
-        let content = response_data["choices"][0]["message"]["content"]
-            .as_str()
-            .ok_or_else(|| UtilError::ConversionError("Invalid response format".to_string()))?
-            .trim()
-            .to_string();
+        let prompt = prompts::get_short_term_memory_prompt(&self.persona, &context);
+        let thought = self.in_character(prompt).await?;
 
-        debug!("Generated post: {}", content);
-        Ok(content)
+        debug!("Generated thought: {}", thought);
+        Ok(thought)
     }
 
-    pub async fn calculate_significance(&self, memory: &str) -> Result<f32> {
-        let prompt = prompts::get_significance_score_prompt(memory);
+    /// Reply to `tweet`, given the conversation it belongs to
+    pub async fn generate_reply(
+        &self,
+        tweet: &str,
+        conversation: &[String],
+        memories: &[String],
+    ) -> Result<String> {
+        let prompt = prompts::get_reply_generation_prompt(&self.persona, tweet, conversation, memories);
+        let reply = self.in_character(prompt).await?;
 
-        let response = self.hyperbolic
-            .post("https://api.hyperbolic.xyz/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.config.hyperbolic_api_key))
-            .json(&serde_json::json!({
-                "messages": [
-                    {
-                        "role": "user",
-                        "content": prompt
-                    }
-                ]
-            }))
-            .send()
-            .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        debug!("Generated reply: {}", reply);
+        Ok(reply)
+    }
 
-        let response_data: Value = response.json()
+    async fn in_character(&self, prompt: String) -> Result<String> {
+        self.chat
+            .chat(&[
+                ChatMessage::system(self.persona.system_message()),
+                ChatMessage::user(prompt),
+            ])
             .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+    }
 
-        let score_str = response_data["choices"][0]["message"]["content"]
-            .as_str()
-            .ok_or_else(|| UtilError::ConversionError("Invalid response format".to_string()))?;
+    pub async fn calculate_significance(&self, memory: &str) -> Result<f32> {
+        let prompt = prompts::get_significance_score_prompt(memory);
+
+        let score_str = self.chat
+            .chat(&[ChatMessage::user(prompt)])
+            .await?;
 
         let score: f32 = score_str.trim()
             .parse()
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+            .map_err(|e: std::num::ParseFloatError| UtilError::ConversionError(e.to_string()))?;
 
         Ok(score / 10.0) // Normalize to 0-1 range
     }
+
+    /// One memory that says what `memories` say between them
+    pub async fn merge_memories(&self, memories: &[String]) -> Result<String> {
+        let prompt = prompts::get_memory_consolidation_prompt(memories);
+        let merged = self.in_character(prompt).await?;
+
+        let merged = merged.trim();
+        if merged.is_empty() {
+            return Err(UtilError::ConversionError("Model returned an empty merged memory".to_string()));
+        }
+
+        debug!("Merged {} memories: {}", memories.len(), merged);
+        Ok(merged.to_string())
+    }
+
+    /// `None` when the model judges `content` safe to post, otherwise its
+    /// reason. An answer that is neither is treated as unsafe.
+    pub async fn moderate(&self, content: &str) -> Result<Option<String>> {
+        let prompt = prompts::get_moderation_prompt(content);
+
+        let verdict = self.chat
+            .chat(&[ChatMessage::user(prompt)])
+            .await?;
+        let verdict = verdict.trim();
+
+        if verdict.eq_ignore_ascii_case("safe") {
+            return Ok(None);
+        }
+
+        let reason = verdict
+            .strip_prefix("UNSAFE")
+            .map(|rest| rest.trim_start_matches(':').trim())
+            .filter(|rest| !rest.is_empty())
+            .unwrap_or(verdict);
+        Ok(Some(reason.to_string()))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockall::predicate::*;
-    use mockall::mock;
-
-    mock! {
-        AIClient {}
-        async trait AIClient {
-            async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>>;
-            async fn generate_post(&self, context: &str) -> Result<String>;
-            async fn calculate_significance(&self, memory: &str) -> Result<f32>;
-        }
-    }
+    use crate::testing::{self, llm};
 
     #[tokio::test]
     async fn test_significance_calculation() {
-        let config = Config::from_env().unwrap();
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["7"]);
+
+        let config = testing::test_config(&server);
         let client = Client::new(&config).unwrap();
-        
+
         let score = client.calculate_significance("A truly remarkable event that changed everything")
             .await
             .unwrap();
-            
-        assert!(score >= 0.0 && score <= 1.0);
+
+        assert!((score - 0.7).abs() < f32::EPSILON);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_generate_post_request() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["  gm to the timeline  "]);
+
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        let post = client
+            .generate_post("thinking about gm", &[], &["old post".to_string()], &[])
+            .await
+            .unwrap();
+        assert_eq!(post, "gm to the timeline");
+
+        let request = &server.requests_to("POST", "/v1/chat/completions")[0];
+        let body = request.json();
+        assert_eq!(body["model"], "fake-chat");
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert!(body["messages"][1]["content"].as_str().unwrap().contains("old post"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_reply_in_character() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["blobs are the best thing since the merge"]);
+
+        let mut config = testing::test_config(&server);
+        config.persona.name = "vitabot".to_string();
+        config.persona.bio = "an ethereum-native agent".to_string();
+        let client = Client::new(&config).unwrap();
+
+        let reply = client
+            .generate_reply("what do you make of blobs?", &["@alice: what do you make of blobs?".to_string()], &[])
+            .await
+            .unwrap();
+        assert_eq!(reply, "blobs are the best thing since the merge");
+
+        let body = server.requests_to("POST", "/v1/chat/completions")[0].json();
+        let system = body["messages"][0]["content"].as_str().unwrap();
+        assert!(system.starts_with("You are vitabot"));
+        assert!(system.contains("an ethereum-native agent"));
+        assert!(body["messages"][1]["content"].as_str().unwrap().contains("@alice: what do you make of blobs?"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_embedding() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        let embedding = client.generate_embedding("hello world").await.unwrap();
+        assert_eq!(embedding, llm::fake_embedding("hello world", llm::FAKE_EMBEDDING_DIMENSIONS));
+    }
+}
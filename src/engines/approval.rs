@@ -0,0 +1,193 @@
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::{
+    config::{ApprovalConfig, Config},
+    db::{models::Proposal, Pool},
+};
+
+/// Kinds of output that can be held for review
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Post,
+    Reply,
+    Transfer,
+}
+
+impl Category {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Post => "post",
+            Category::Reply => "reply",
+            Category::Transfer => "transfer",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "post" => Ok(Category::Post),
+            "reply" => Ok(Category::Reply),
+            "transfer" => Ok(Category::Transfer),
+            other => Err(format!("unknown proposal category {:?}", other)),
+        }
+    }
+}
+
+/// Decides which categories go out without review
+pub struct ApprovalPolicy {
+    config: ApprovalConfig,
+}
+
+impl ApprovalPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.approval.clone(),
+        }
+    }
+
+    /// Posts and replies are auto-approved while approval mode is off.
+    /// Transfers move funds, so they only skip review when approval mode is
+    /// on and `auto_approve.transfer` says so.
+    pub fn auto_approves(&self, category: Category) -> bool {
+        let rules = &self.config.auto_approve;
+        match category {
+            Category::Transfer => self.config.enabled && rules.transfer,
+            _ if !self.config.enabled => true,
+            Category::Post => rules.post,
+            Category::Reply => rules.reply,
+        }
+    }
+}
+
+/// A reviewer's call on a pending proposal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    /// Approve with the content replaced
+    Edit(String),
+    Reject(Option<String>),
+}
+
+#[derive(Debug, Error)]
+pub enum ReviewError {
+    #[error("Proposal {0} not found")]
+    NotFound(i32),
+
+    #[error("Proposal {id} is already {status}")]
+    NotPending {
+        id: i32,
+        status: String,
+    },
+
+    #[error("Invalid decision: {0}")]
+    Invalid(String),
+
+    #[error("Database error: {0}")]
+    Database(String),
+}
+
+/// Applies a reviewer's decision. The agent executes approved proposals on
+/// its next delivery pass, so this works from another process too.
+pub async fn review(
+    pool: &Pool,
+    proposal_id: i32,
+    decision: Decision,
+    reviewer: &str,
+) -> Result<Proposal, ReviewError> {
+    let proposal = Proposal::find(pool, proposal_id)
+        .await
+        .map_err(|e| ReviewError::Database(e.to_string()))?
+        .ok_or(ReviewError::NotFound(proposal_id))?;
+
+    if proposal.status != Proposal::PENDING {
+        return Err(ReviewError::NotPending {
+            id: proposal.id,
+            status: proposal.status,
+        });
+    }
+
+    let (status, content, reason) = match &decision {
+        Decision::Approve => (Proposal::APPROVED, None, None),
+        Decision::Edit(content) => {
+            if proposal.category == Category::Transfer.as_str() {
+                return Err(ReviewError::Invalid("transfers can't be edited, only approved or rejected".to_string()));
+            }
+            let content = content.trim();
+            if content.is_empty() {
+                return Err(ReviewError::Invalid("edited content is empty".to_string()));
+            }
+            (Proposal::APPROVED, Some(content), None)
+        }
+        Decision::Reject(reason) => (Proposal::REJECTED, None, reason.as_deref()),
+    };
+
+    Proposal::decide(pool, proposal_id, status, content, reviewer, reason)
+        .await
+        .map_err(|e| ReviewError::Database(e.to_string()))?
+        // Decided by someone else since it was read
+        .ok_or_else(|| ReviewError::NotPending {
+            id: proposal_id,
+            status: "decided".to_string(),
+        })
+}
+
+/// One-line description for logs and the CLI
+pub fn summary(proposal: &Proposal) -> String {
+    let target = match (&proposal.reply_to_id, proposal.category.as_str()) {
+        (Some(reply_to_id), _) => format!("reply to {}", reply_to_id),
+        (None, category) => category.to_string(),
+    };
+
+    format!("#{} [{}] {}: {}", proposal.id, proposal.status, target, proposal.content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_approve_rules() {
+        let mut config = Config::default();
+        let policy = ApprovalPolicy::new(&config);
+        assert!(policy.auto_approves(Category::Post));
+        assert!(policy.auto_approves(Category::Reply));
+        assert!(!policy.auto_approves(Category::Transfer));
+
+        // Turning transfers on does nothing while approval mode is off
+        config.approval.auto_approve.transfer = true;
+        let policy = ApprovalPolicy::new(&config);
+        assert!(!policy.auto_approves(Category::Transfer));
+
+        config.approval.auto_approve.transfer = false;
+        config.approval.enabled = true;
+        config.approval.auto_approve.reply = true;
+        let policy = ApprovalPolicy::new(&config);
+        assert!(policy.auto_approves(Category::Reply));
+        assert!(!policy.auto_approves(Category::Post));
+        assert!(!policy.auto_approves(Category::Transfer));
+
+        config.approval.auto_approve.transfer = true;
+        let policy = ApprovalPolicy::new(&config);
+        assert!(policy.auto_approves(Category::Transfer));
+    }
+
+    #[test]
+    fn test_category_round_trip() {
+        for category in [Category::Post, Category::Reply, Category::Transfer] {
+            assert_eq!(category.as_str().parse::<Category>(), Ok(category));
+        }
+        assert!("wallet".parse::<Category>().is_err());
+    }
+}
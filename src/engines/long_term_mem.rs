@@ -1,18 +1,22 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
-use diesel::prelude::*;
 use tracing::{debug, info, warn};
 
 use crate::{
-    config::Config,
-    db::{Pool, models::LongTermMemory as DbMemory},
+    config::{Config, MemoryConfig},
+    db::{Pool, models::{LongTermMemory as DbMemory, NewLongTermMemory, ScoredMemory}, vector::Embedding},
     utils::{Result, UtilError},
     utils::traits::{Embeddable, LLMFormattable},
 };
 
 use super::{
     ai::Client as AIClient,
+    short_term_mem::cosine_similarity,
     significance_scorer::SignificanceScorer,
+    vector_index::{self, VectorIndex},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,15 +27,25 @@ pub struct Memory {
     pub created_at: DateTime<Utc>,
 }
 
+/// Nearest neighbours fetched per requested memory, to be reranked by activation
+const CANDIDATES_PER_RESULT: usize = 4;
+
 pub struct LongTermMemoryEngine {
     ai_client: AIClient,
     significance_scorer: SignificanceScorer,
     config: Config,
     db_pool: Pool,
+    index: Arc<dyn VectorIndex>,
 }
 
 impl LongTermMemoryEngine {
-    pub fn new(config: &Config, db_pool: Pool) -> Result<Self> {
+    /// Opens the vector index `memory.vector_index` selects
+    pub async fn connect(config: &Config, db_pool: Pool) -> Result<Self> {
+        let index = vector_index::open(config, &db_pool).await?;
+        Self::with_index(config, db_pool, index)
+    }
+
+    pub fn with_index(config: &Config, db_pool: Pool, index: Arc<dyn VectorIndex>) -> Result<Self> {
         let ai_client = AIClient::new(config)?;
         let significance_scorer = SignificanceScorer::new(config)?;
         
@@ -40,6 +54,7 @@ impl LongTermMemoryEngine {
             significance_scorer,
             config: config.clone(),
             db_pool,
+            index,
         })
     }
 
@@ -51,97 +66,238 @@ impl LongTermMemoryEngine {
         let significance = self.significance_scorer.score_memory(content).await?;
         
         // Only store if significance meets threshold
-        if significance < self.config.memory_significance_threshold {
+        if significance < self.config.memory.significance_threshold {
             debug!("Memory significance too low ({:.2}), skipping storage", significance);
             return Err(UtilError::ConversionError("Memory significance too low".to_string()));
         }
 
-        let mut conn = self.db_pool.get().await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let memory = DbMemory::create(&self.db_pool, NewLongTermMemory {
+            content: content.to_string(),
+            embedding: Embedding(embedding),
+            significance_score: significance,
+        })
+        .await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
-        let memory = diesel::insert_into(crate::db::schema::long_term_memories::table)
-            .values((
-                crate::db::schema::long_term_memories::content.eq(content),
-                crate::db::schema::long_term_memories::embedding.eq(&embedding),
-                crate::db::schema::long_term_memories::significance_score.eq(significance),
-            ))
-            .get_result(&mut conn)
-            .await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        self.index.insert(memory.id, &memory.embedding).await?;
 
         info!("Stored new memory with significance {:.2}", significance);
         Ok(memory)
     }
 
-    pub async fn retrieve_relevant_memories(&self, query: &str, limit: usize) -> Result<Vec<DbMemory>> {
+    /// The `limit` most relevant memories for `query`, most relevant first.
+    /// Relevance is similarity weighted by activation, and every memory
+    /// returned is recorded as recalled.
+    pub async fn retrieve_relevant_memories(&self, query: &str, limit: usize) -> Result<Vec<ScoredMemory>> {
         let query_embedding = self.ai_client.generate_embedding(query).await?;
-        
-        let mut conn = self.db_pool.get().await
-            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let mut memories = self.index.search(&query_embedding, limit * CANDIDATES_PER_RESULT).await?;
 
-        // Custom SQL for vector similarity search
-        let memories = diesel::sql_query(r#"
-            SELECT *, 
-                   (embedding <=> $1) as similarity
-            FROM long_term_memories
-            ORDER BY similarity DESC
-            LIMIT $2
-        "#)
-        .bind::<diesel::sql_types::Array<diesel::sql_types::Float4>, _>(&query_embedding)
-        .bind::<diesel::sql_types::Integer, _>(limit as i32)
-        .load::<DbMemory>(&mut conn)
-        .await
-        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let now = Utc::now();
+        // Similarity always counts for half, so a faded memory still comes
+        // up when nothing fresher fits the query
+        let relevance = |m: &ScoredMemory| {
+            let activation = activation(&m.memory, &self.config.memory, now).min(1.0);
+            m.similarity * (0.5 + 0.5 * activation)
+        };
+        memories.sort_by(|a, b| relevance(b).total_cmp(&relevance(a)));
+        memories.truncate(limit);
 
+        let ids: Vec<i32> = memories.iter().map(|m| m.memory.id).collect();
+        DbMemory::record_access(&self.db_pool, &ids, now)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        for m in &mut memories {
+            m.memory.access_count += 1;
+            m.memory.last_accessed_at = Some(now);
+        }
+
+        debug!(
+            "Retrieved {} memories for query (best similarity {:.3})",
+            memories.len(),
+            memories.iter().map(|m| m.similarity).fold(0.0, f32::max)
+        );
         Ok(memories)
     }
 
-    pub async fn consolidate_memories(&self) -> Result<()> {
-        // Periodically consolidate similar memories
-        let mut conn = self.db_pool.get().await
+    /// Archives memories whose activation has fallen below
+    /// `memory.activation_floor`. Returns how many were archived.
+    pub async fn forget_memories(&self) -> Result<usize> {
+        let memories = DbMemory::active(&self.db_pool)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let now = Utc::now();
+        let faded: Vec<i32> = memories
+            .iter()
+            .filter(|m| activation(m, &self.config.memory, now) < self.config.memory.activation_floor)
+            .map(|m| m.id)
+            .collect();
+        if faded.is_empty() {
+            return Ok(0);
+        }
+
+        let archived = DbMemory::archive(&self.db_pool, &faded, now)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        self.index.remove(&faded).await?;
+
+        debug!("Archived memories {:?}", faded);
+        Ok(archived)
+    }
+
+    /// Merges each cluster of similar memories into one the model writes.
+    /// Returns how many clusters were merged.
+    pub async fn consolidate_memories(&self) -> Result<usize> {
+        let memories = DbMemory::active(&self.db_pool)
+            .await
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
-        // Find similar memory clusters
-        let memories = diesel::sql_query(r#"
-            SELECT m1.id, m1.content, m1.embedding, m2.id as similar_id
-            FROM long_term_memories m1
-            JOIN long_term_memories m2 ON (m1.embedding <=> m2.embedding) < 0.1
-            WHERE m1.id < m2.id
-        "#)
-        .load::<DbMemory>(&mut conn)
+        // Candidates come from the index, so a run doesn't compare every pair
+        let position: HashMap<i32, usize> = memories.iter().enumerate().map(|(i, m)| (m.id, i)).collect();
+        let mut neighbours = Vec::with_capacity(memories.len());
+        for memory in &memories {
+            let found = self.index
+                .search(&memory.embedding, self.config.memory.max_cluster_size)
+                .await?;
+            neighbours.push(
+                found
+                    .iter()
+                    .filter_map(|scored| position.get(&scored.memory.id).copied())
+                    .collect::<Vec<usize>>(),
+            );
+        }
+
+        let embeddings: Vec<&[f32]> = memories.iter().map(|m| &m.embedding[..]).collect();
+        let clusters = cluster(
+            &embeddings,
+            &neighbours,
+            self.config.memory.consolidation_threshold,
+            self.config.memory.max_cluster_size,
+        );
+
+        let mut merged = 0;
+        for cluster in clusters {
+            let sources: Vec<DbMemory> = cluster.iter().map(|&i| memories[i].clone()).collect();
+            let source_ids: Vec<i32> = sources.iter().map(|m| m.id).collect();
+
+            match self.merge(&sources).await {
+                Ok(memory) => {
+                    info!("Consolidated memories {:?} into memory {}", source_ids, memory.id);
+                    merged += 1;
+                }
+                // The sources are left as they were
+                Err(e) => warn!("Could not consolidate memories {:?}: {}", source_ids, e),
+            }
+        }
+
+        Ok(merged)
+    }
+
+    async fn merge(&self, sources: &[DbMemory]) -> Result<DbMemory> {
+        let contents: Vec<String> = sources.iter().map(|m| m.content.clone()).collect();
+        let content = self.ai_client.merge_memories(&contents).await?;
+        let embedding = self.ai_client.generate_embedding(&content).await?;
+
+        // A merge is never less significant than what went into it
+        let cluster_max = sources.iter().map(|m| m.significance_score).fold(0.0, f32::max);
+        let significance = self.significance_scorer.score_memory(&content).await?.max(cluster_max);
+
+        let memory = DbMemory::consolidate(&self.db_pool, NewLongTermMemory {
+            content,
+            embedding: Embedding(embedding),
+            significance_score: significance,
+        }, sources)
         .await
         .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
-        // Consolidate similar memories
-        for memory_pair in memories.chunks(2) {
-            if memory_pair.len() != 2 {
+        let source_ids: Vec<i32> = sources.iter().map(|m| m.id).collect();
+        self.index.remove(&source_ids).await?;
+        self.index.insert(memory.id, &memory.embedding).await?;
+        Ok(memory)
+    }
+
+    /// Saves the vector index for the next start, where it keeps state of its own
+    pub async fn save_index(&self) -> Result<()> {
+        self.index.save().await
+    }
+}
+
+/// How readily a memory comes to mind: its significance, halved every
+/// `recency_half_life_days` since it was last recalled (or stored), and
+/// raised by `reinforcement_weight` for each e-fold of recalls
+pub fn activation(memory: &DbMemory, config: &MemoryConfig, now: DateTime<Utc>) -> f32 {
+    let last_active = memory.last_accessed_at.unwrap_or(memory.created_at);
+    let idle_days = (now - last_active).num_seconds().max(0) as f32 / 86_400.0;
+
+    let recency = 0.5f32.powf(idle_days / config.recency_half_life_days);
+    let reinforcement = 1.0 + config.reinforcement_weight * (memory.access_count as f32).ln_1p();
+    memory.significance_score * recency * reinforcement
+}
+
+/// Complete-linkage clustering: groups of at most `max_size` in which every
+/// pair is at least `threshold` similar, most similar pairs joined first.
+/// Only pairs where one is among the other's `neighbours` are joined.
+/// Returns the groups of two or more, as sorted indices into `embeddings`.
+fn cluster(
+    embeddings: &[&[f32]],
+    neighbours: &[Vec<usize>],
+    threshold: f32,
+    max_size: usize,
+) -> Vec<Vec<usize>> {
+    let similarity = |a: usize, b: usize| cosine_similarity(embeddings[a], embeddings[b]);
+
+    let mut pairs = Vec::new();
+    for (a, near) in neighbours.iter().enumerate() {
+        for &b in near {
+            if a == b {
                 continue;
             }
+            let s = similarity(a, b);
+            if s >= threshold {
+                pairs.push((s, a.min(b), a.max(b)));
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+    pairs.dedup_by(|x, y| (x.1, x.2) == (y.1, y.2));
 
-            let consolidated_content = format!(
-                "Consolidated memory: {} | {}",
-                memory_pair[0].content,
-                memory_pair[1].content
-            );
+    let mut clusters: Vec<Vec<usize>> = (0..embeddings.len()).map(|i| vec![i]).collect();
+    let mut cluster_of: Vec<usize> = (0..embeddings.len()).collect();
 
-            self.store_memory(&consolidated_content).await?;
+    for (_, a, b) in pairs {
+        let (into, from) = (cluster_of[a], cluster_of[b]);
+        if into == from || clusters[into].len() + clusters[from].len() > max_size {
+            continue;
+        }
 
-            // Remove original memories
-            diesel::delete(crate::db::schema::long_term_memories::table)
-                .filter(crate::db::schema::long_term_memories::id.eq_any(&[memory_pair[0].id, memory_pair[1].id]))
-                .execute(&mut conn)
-                .await
-                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let linked = clusters[into]
+            .iter()
+            .all(|&x| clusters[from].iter().all(|&y| similarity(x, y) >= threshold));
+        if !linked {
+            continue;
         }
 
-        Ok(())
+        let moved = std::mem::take(&mut clusters[from]);
+        for &i in &moved {
+            cluster_of[i] = into;
+        }
+        clusters[into].extend(moved);
     }
+
+    clusters
+        .into_iter()
+        .filter(|members| members.len() >= 2)
+        .map(|mut members| {
+            members.sort_unstable();
+            members
+        })
+        .collect()
 }
 
 #[async_trait::async_trait]
 impl Embeddable for DbMemory {
     async fn to_embedding(&self) -> Result<Vec<f32>> {
-        Ok(self.embedding.clone())
+        Ok(self.embedding.to_vec())
     }
 }
 
@@ -153,4 +309,238 @@ impl LLMFormattable for DbMemory {
             self.content
         )
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{models::MemoryProvenance, schema::long_term_memories};
+    use crate::testing::{self, llm};
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    async fn store(pool: &Pool, content: &str, significance: f32) -> DbMemory {
+        DbMemory::create(pool, NewLongTermMemory {
+            content: content.to_string(),
+            embedding: Embedding(llm::fake_embedding(content, llm::FAKE_EMBEDDING_DIMENSIONS)),
+            significance_score: significance,
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn existing(pool: &Pool, ids: &[i32]) -> Vec<i32> {
+        let mut conn = pool.get().await.unwrap();
+        long_term_memories::table
+            .filter(long_term_memories::id.eq_any(ids))
+            .select(long_term_memories::id)
+            .load(&mut conn)
+            .await
+            .unwrap()
+    }
+
+    /// Every other embedding as a neighbour, like an exhaustive search
+    fn all_neighbours(count: usize) -> Vec<Vec<usize>> {
+        (0..count).map(|_| (0..count).collect()).collect()
+    }
+
+    #[test]
+    fn test_cluster_needs_every_pair_similar() {
+        let angle = |degrees: f32| vec![degrees.to_radians().cos(), degrees.to_radians().sin(), 0.0];
+        let (x, y, z) = (angle(0.0), angle(30.0), angle(60.0));
+        let far = vec![0.0, 0.0, 1.0];
+
+        // x-y and y-z are close enough, x-z is not, so z isn't chained in
+        let threshold = 35f32.to_radians().cos();
+        assert_eq!(cluster(&[&x, &far, &y, &z], &all_neighbours(4), threshold, 8), vec![vec![0, 2]]);
+
+        let same = [x.as_slice(); 5];
+        assert_eq!(cluster(&same, &all_neighbours(5), threshold, 3), vec![vec![0, 1, 2], vec![3, 4]]);
+        assert!(cluster(&[&x, &far], &all_neighbours(2), threshold, 8).is_empty());
+
+        // Pairs the index didn't return are never compared
+        let only_self: Vec<Vec<usize>> = (0..5).map(|i| vec![i]).collect();
+        assert!(cluster(&same, &only_self, threshold, 3).is_empty());
+        let one_way = vec![vec![0, 1], vec![1], vec![2]];
+        assert_eq!(cluster(&same[..3], &one_way, threshold, 3), vec![vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_activation_decays_and_reinforces() {
+        let config = MemoryConfig::default();
+        let now = Utc::now();
+        let half_life = chrono::Duration::days(30);
+        let mut memory = DbMemory {
+            id: 1,
+            content: "gm".to_string(),
+            embedding: Embedding(vec![]),
+            significance_score: 0.8,
+            created_at: now,
+            access_count: 0,
+            last_accessed_at: None,
+            archived_at: None,
+        };
+
+        assert!((activation(&memory, &config, now) - 0.8).abs() < 1e-6);
+        assert!((activation(&memory, &config, now + half_life) - 0.4).abs() < 1e-6);
+
+        memory.access_count = 3;
+        let reinforced = activation(&memory, &config, now + half_life);
+        assert!(reinforced > 0.4);
+
+        // A recall restarts the decay
+        memory.last_accessed_at = Some(now + half_life);
+        assert!(activation(&memory, &config, now + half_life) > 2.0 * reinforced - 1e-6);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_recall_keeps_memories_from_being_forgotten() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+
+        let tag = uuid::Uuid::new_v4().simple();
+        let recalled = store(&pool, &format!("{} recalled", tag), 0.5).await;
+        let faded = store(&pool, &format!("{} faded", tag), 0.5).await;
+
+        // Both were stored a year ago, a dozen half-lives back
+        let mut conn = pool.get().await.unwrap();
+        diesel::update(long_term_memories::table.filter(long_term_memories::id.eq_any([recalled.id, faded.id])))
+            .set(long_term_memories::created_at.eq(Utc::now() - chrono::Duration::days(365)))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        let engine = LongTermMemoryEngine::connect(&config, pool.clone()).await.unwrap();
+        let memories = engine.retrieve_relevant_memories(&recalled.content, 1).await.unwrap();
+        assert_eq!(memories[0].memory.id, recalled.id);
+        assert_eq!(memories[0].memory.access_count, 1);
+
+        assert!(engine.forget_memories().await.unwrap() >= 1);
+
+        let rows: Vec<DbMemory> = long_term_memories::table
+            .filter(long_term_memories::id.eq_any([recalled.id, faded.id]))
+            .order(long_term_memories::id.asc())
+            .select(DbMemory::as_select())
+            .load(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(rows[0].access_count, 1);
+        assert!(rows[0].last_accessed_at.is_some());
+        assert!(rows[0].archived_at.is_none());
+        assert!(rows[1].archived_at.is_some());
+
+        let memories = engine.retrieve_relevant_memories(&faded.content, 1).await.unwrap();
+        assert_ne!(memories[0].memory.id, faded.id);
+
+        diesel::delete(long_term_memories::table.filter(long_term_memories::id.eq_any([recalled.id, faded.id])))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_merge_keeps_provenance() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &[
+            "blobs made rollups cheap and I keep saying so",
+            "5",
+            "blobs made rollups cheap, and fees are still falling",
+            "3",
+            "this merge never lands",
+            "4",
+        ]);
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+
+        let tag = uuid::Uuid::new_v4().simple();
+        let first = store(&pool, &format!("{} blobs made rollups cheap", tag), 0.6).await;
+        let second = store(&pool, &format!("{} rollups are cheap thanks to blobs", tag), 0.8).await;
+        let engine = LongTermMemoryEngine::connect(&config, pool.clone()).await.unwrap();
+
+        let merged = engine.merge(&[first.clone(), second.clone()]).await.unwrap();
+        assert_eq!(merged.content, "blobs made rollups cheap and I keep saying so");
+        // The scorer said 0.5, but the cluster held a 0.8
+        assert_eq!(merged.significance_score, 0.8);
+        assert!(existing(&pool, &[first.id, second.id]).await.is_empty());
+
+        let found = engine.retrieve_relevant_memories(&merged.content, 1).await.unwrap();
+        assert_eq!(found[0].memory.id, merged.id);
+
+        // Merging a merge carries its sources over
+        let third = store(&pool, &format!("{} fees are still falling", tag), 0.4).await;
+        let remerged = engine.merge(&[merged.clone(), third.clone()]).await.unwrap();
+        let sources: Vec<i32> = MemoryProvenance::for_memory(&pool, remerged.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|link| link.source_id)
+            .collect();
+        assert_eq!(sources, vec![first.id, second.id, merged.id, third.id]);
+
+        // A source that is already gone rolls the merge back
+        let kept = store(&pool, &format!("{} kept", tag), 0.5).await;
+        let gone = store(&pool, &format!("{} gone", tag), 0.5).await;
+        let mut conn = pool.get().await.unwrap();
+        diesel::delete(long_term_memories::table.find(gone.id))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        assert!(engine.merge(&[kept.clone(), gone]).await.is_err());
+        assert_eq!(existing(&pool, &[kept.id]).await, vec![kept.id]);
+        let stray: i64 = long_term_memories::table
+            .filter(long_term_memories::content.eq("this merge never lands"))
+            .count()
+            .get_result(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(stray, 0);
+
+        diesel::delete(long_term_memories::table.filter(long_term_memories::id.eq_any([remerged.id, kept.id])))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_retrieves_most_similar_first() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+
+        // Words no other memory contains
+        let (first, second) = (uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let contents = [
+            format!("{} gm", first),
+            format!("{} {}", first, second),
+            "nothing in common here".to_string(),
+        ];
+
+        let mut ids = Vec::new();
+        for content in &contents {
+            ids.push(store(&pool, content, 0.9).await.id);
+        }
+
+        let engine = LongTermMemoryEngine::connect(&config, pool.clone()).await.unwrap();
+        let memories = engine
+            .retrieve_relevant_memories(&contents[1], 2)
+            .await
+            .unwrap();
+
+        let mut conn = pool.get().await.unwrap();
+        diesel::delete(long_term_memories::table.filter(long_term_memories::id.eq_any(&ids)))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(memories.len(), 2);
+        assert_eq!(memories[0].memory.content, contents[1]);
+        assert!((memories[0].similarity - 1.0).abs() < 1e-4);
+        assert_eq!(memories[1].memory.content, contents[0]);
+        assert!(memories[1].similarity < memories[0].similarity);
+        assert_eq!(memories[1].memory.embedding.len(), llm::FAKE_EMBEDDING_DIMENSIONS);
+    }
+}
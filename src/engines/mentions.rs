@@ -0,0 +1,330 @@
+use tracing::{debug, warn};
+
+use crate::{
+    db::{models::{HandledTweet, NewHandledTweet, NewXCheckpoint, XCheckpoint}, Pool},
+    utils::{Result, UtilError},
+    xdotcom::{types::{compare_ids, Tweet}, Client as TwitterClient},
+};
+
+const PAGE_SIZE: usize = 100;
+
+/// Pages fetched per poll once a checkpoint exists; older ones wait for the
+/// next poll
+const MAX_PAGES: usize = 5;
+
+/// Times a mention is handed out before it is marked failed
+const MAX_ATTEMPTS: i32 = 3;
+
+/// How long a handed-out mention stays claimed before it is handed out again,
+/// in case the process died before answering or releasing it
+const CLAIM_LEASE_MINUTES: i64 = 30;
+
+const KIND: &str = "mention";
+
+/// Polls the mentions timeline from a `since_id` checkpoint stored in Postgres
+pub struct MentionsPoller {
+    twitter_client: TwitterClient,
+}
+
+impl MentionsPoller {
+    pub fn new(twitter_client: TwitterClient) -> Self {
+        Self { twitter_client }
+    }
+
+    /// Mentions to answer, oldest first: new ones plus any released after a
+    /// failed attempt. Each is recorded as pending in `handled_tweets` and stays
+    /// claimed until it is marked answered or released.
+    pub async fn poll(&self, db: &Pool) -> Result<Vec<Tweet>> {
+        let me = self.twitter_client.me().await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let checkpoint = format!("mentions:{}", me.id);
+
+        let saved = XCheckpoint::find(db, &checkpoint).await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        let since_id = saved.as_ref().map(|saved| saved.since_id.clone());
+        // Set when the last poll ran out of pages before reaching since_id
+        let until_id = saved.as_ref().and_then(|saved| saved.until_id.clone());
+
+        let mut mentions = Vec::new();
+        let mut newest_id = saved.as_ref().and_then(|saved| saved.newest_id.clone()).or(since_id.clone());
+        let mut oldest_id = until_id.clone();
+        let mut pagination_token = None;
+
+        // Without a checkpoint only the latest page is taken, not the whole history
+        let max_pages = if since_id.is_some() { MAX_PAGES } else { 1 };
+
+        for _ in 0..max_pages {
+            let page = self.twitter_client
+                .get_mentions(
+                    &me.id,
+                    since_id.as_deref(),
+                    until_id.as_deref(),
+                    pagination_token.as_deref(),
+                    PAGE_SIZE,
+                )
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+            mentions.extend(page.expanded());
+
+            let meta = page.meta.unwrap_or_default();
+            if let Some(id) = meta.newest_id {
+                if newest_id.as_deref().is_none_or(|newest| compare_ids(&id, newest).is_gt()) {
+                    newest_id = Some(id);
+                }
+            }
+            if meta.oldest_id.is_some() {
+                oldest_id = meta.oldest_id;
+            }
+
+            pagination_token = meta.next_token;
+            if pagination_token.is_none() {
+                break;
+            }
+        }
+
+        mentions.retain(|tweet| tweet.author_id.as_deref() != Some(me.id.as_str()));
+        mentions.sort_by(|a, b| compare_ids(&a.id, &b.id));
+        mentions.dedup_by(|a, b| a.id == b.id);
+
+        let mut records = Vec::with_capacity(mentions.len());
+        for tweet in &mentions {
+            records.push(NewHandledTweet {
+                tweet_id: tweet.id.clone(),
+                kind: KIND.to_string(),
+                status: HandledTweet::PENDING.to_string(),
+                tweet: Some(serde_json::to_value(tweet)?),
+            });
+        }
+        let recorded = HandledTweet::record(db, records).await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        // Saved only once the mentions are recorded, so none are skipped. A poll
+        // that ran out of pages keeps since_id and resumes below oldest_id.
+        let update = match (since_id, pagination_token) {
+            (Some(since_id), Some(_)) => {
+                warn!(
+                    "More than {} pages of mentions since {}; fetching the older ones on the next poll",
+                    max_pages, since_id
+                );
+                Some(NewXCheckpoint {
+                    name: checkpoint,
+                    since_id,
+                    until_id: oldest_id,
+                    newest_id,
+                })
+            }
+            (since_id, _) => newest_id
+                .filter(|newest_id| until_id.is_some() || Some(newest_id) != since_id.as_ref())
+                .map(|newest_id| NewXCheckpoint {
+                    name: checkpoint,
+                    since_id: newest_id,
+                    until_id: None,
+                    newest_id: None,
+                }),
+        };
+        if let Some(update) = update {
+            XCheckpoint::save(db, update)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        }
+
+        let claimed = HandledTweet::claim_pending(
+            db,
+            KIND,
+            chrono::Duration::minutes(CLAIM_LEASE_MINUTES),
+            MAX_ATTEMPTS,
+        )
+        .await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let mut pending = Vec::with_capacity(claimed.len());
+        for handled in claimed {
+            match handled.tweet.map(serde_json::from_value::<Tweet>) {
+                Some(Ok(tweet)) => pending.push(tweet),
+                _ => {
+                    warn!("Pending mention {} has no stored tweet; giving up on it", handled.tweet_id);
+                    HandledTweet::release(db, &handled.tweet_id, 0).await
+                        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+                }
+            }
+        }
+        pending.sort_by(|a, b| compare_ids(&a.id, &b.id));
+
+        debug!("Polled {} new mentions, {} to answer", recorded, pending.len());
+        Ok(pending)
+    }
+
+    /// Marks a mention done so it is never handed out again
+    pub async fn answered(&self, db: &Pool, tweet_id: &str) -> Result<()> {
+        HandledTweet::mark_answered(db, tweet_id).await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Hands a mention back for the next poll, or marks it failed once it has
+    /// been handed out `MAX_ATTEMPTS` times
+    pub async fn release(&self, db: &Pool, tweet_id: &str) -> Result<()> {
+        HandledTweet::release(db, tweet_id, MAX_ATTEMPTS).await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::schema::{handled_tweets, x_checkpoints};
+    use crate::testing::{self, x, FakeResponse};
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    /// Polls claim any pending mention, so the tests here take turns
+    static POLLS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_poll_checkpoints_and_dedupes() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let _polls = POLLS.lock().await;
+
+        // Unique ids so runs against the same database don't collide
+        let base = chrono::Utc::now().timestamp_micros();
+        let (older, newer, own) = (base.to_string(), (base + 1).to_string(), (base + 2).to_string());
+
+        server.script("GET", "/2/users/:id/mentions", vec![x::timeline_response(&[
+            (own.as_str(), "1", "@tomflash talking to myself"),
+            (newer.as_str(), "43", "@tomflash second"),
+            (older.as_str(), "42", "@tomflash first"),
+        ])]);
+
+        let client = TwitterClient::new(&config).unwrap();
+        let poller = MentionsPoller::new(client);
+
+        let first = poller.poll(&pool).await.unwrap();
+        let ids: Vec<&str> = first.iter()
+            .map(|tweet| tweet.id.as_str())
+            .filter(|id| [older.as_str(), newer.as_str(), own.as_str()].contains(id))
+            .collect();
+        assert_eq!(ids, vec![older.as_str(), newer.as_str()]);
+        let first_tweet = first.iter().find(|tweet| tweet.id == older).unwrap();
+        assert_eq!(first_tweet.author_handle(), "@user42");
+
+        // Same page again: checkpoint is sent and nothing claimed is handed out twice
+        let second = poller.poll(&pool).await.unwrap();
+        assert!(!second.iter().any(|tweet| tweet.id == older || tweet.id == newer));
+
+        let requests = server.requests_to("GET", "/2/users/1/mentions");
+        assert_eq!(requests.last().unwrap().query_param("since_id"), Some(own.clone()));
+
+        // Answered mentions are done; released ones come back until out of attempts
+        poller.answered(&pool, &older).await.unwrap();
+        for _ in 1..MAX_ATTEMPTS {
+            poller.release(&pool, &older).await.unwrap();
+            poller.release(&pool, &newer).await.unwrap();
+            let retried = poller.poll(&pool).await.unwrap();
+            assert!(!retried.iter().any(|tweet| tweet.id == older));
+            let tweet = retried.iter().find(|tweet| tweet.id == newer).unwrap();
+            assert_eq!(tweet.author_handle(), "@user43");
+        }
+        poller.release(&pool, &newer).await.unwrap();
+        assert!(!poller.poll(&pool).await.unwrap().iter().any(|tweet| tweet.id == newer));
+
+        let mut conn = pool.get().await.unwrap();
+        let statuses: Vec<(String, String)> = handled_tweets::table
+            .filter(handled_tweets::tweet_id.eq_any([&older, &newer]))
+            .select((handled_tweets::tweet_id, handled_tweets::status))
+            .order(handled_tweets::tweet_id)
+            .load(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(statuses, vec![
+            (older.clone(), HandledTweet::ANSWERED.to_string()),
+            (newer.clone(), HandledTweet::FAILED.to_string()),
+        ]);
+
+        diesel::delete(handled_tweets::table.filter(handled_tweets::tweet_id.eq_any([&older, &newer])))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_poll_resumes_after_page_limit() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let _polls = POLLS.lock().await;
+
+        // Own account id so the checkpoint isn't shared with other tests
+        let base = chrono::Utc::now().timestamp_micros();
+        let me = base.to_string();
+        server.script("GET", "/2/users/me", vec![FakeResponse::ok(serde_json::json!({
+            "data": {"id": me, "name": "tomflash", "username": "tomflash"}
+        }))]);
+        let checkpoint = format!("mentions:{}", me);
+        XCheckpoint::save(&pool, NewXCheckpoint {
+            name: checkpoint.clone(),
+            since_id: base.to_string(),
+            until_id: None,
+            newest_id: None,
+        })
+        .await
+        .unwrap();
+
+        // One mention per page, newest first: the first poll stops a page short
+        let ids: Vec<String> = (1..=MAX_PAGES as i64 + 1).rev().map(|n| (base + n).to_string()).collect();
+        let mut pages: Vec<FakeResponse> = ids
+            .iter()
+            .enumerate()
+            .map(|(page, id)| {
+                let next_token = (page < MAX_PAGES).then(|| format!("page{}", page + 2));
+                x::timeline_page(&[(id.as_str(), "42", "@tomflash hi")], next_token.as_deref())
+            })
+            .collect();
+        pages.push(x::timeline_response(&[]));
+        server.script("GET", "/2/users/:id/mentions", pages);
+
+        let client = TwitterClient::new(&config).unwrap();
+        let poller = MentionsPoller::new(client);
+
+        let first = poller.poll(&pool).await.unwrap();
+        assert_eq!(first.iter().filter(|tweet| ids.contains(&tweet.id)).count(), MAX_PAGES);
+        let saved = XCheckpoint::find(&pool, &checkpoint).await.unwrap().unwrap();
+        assert_eq!(saved.since_id, base.to_string());
+        assert_eq!(saved.until_id.as_ref(), Some(&ids[MAX_PAGES - 1]));
+        assert_eq!(saved.newest_id.as_ref(), Some(&ids[0]));
+
+        // The next poll picks up below where the first stopped
+        let second = poller.poll(&pool).await.unwrap();
+        let oldest = &ids[MAX_PAGES];
+        assert!(second.iter().any(|tweet| &tweet.id == oldest));
+        let requests = server.requests_to("GET", &format!("/2/users/{}/mentions", me));
+        let resumed = &requests[MAX_PAGES];
+        assert_eq!(resumed.query_param("since_id"), Some(base.to_string()));
+        assert_eq!(resumed.query_param("until_id").as_ref(), Some(&ids[MAX_PAGES - 1]));
+        assert_eq!(resumed.query_param("pagination_token"), None);
+
+        let saved = XCheckpoint::find(&pool, &checkpoint).await.unwrap().unwrap();
+        assert_eq!(saved.since_id, ids[0]);
+        assert_eq!((saved.until_id, saved.newest_id), (None, None));
+
+        poller.poll(&pool).await.unwrap();
+        let requests = server.requests_to("GET", &format!("/2/users/{}/mentions", me));
+        assert_eq!(requests.last().unwrap().query_param("since_id").as_ref(), Some(&ids[0]));
+        assert_eq!(requests.last().unwrap().query_param("until_id"), None);
+
+        let mut conn = pool.get().await.unwrap();
+        diesel::delete(handled_tweets::table.filter(handled_tweets::tweet_id.eq_any(&ids)))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::delete(x_checkpoints::table.find(&checkpoint))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+}
@@ -1,22 +1,32 @@
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tracing::{debug, error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
 pub mod ai;
+pub mod approval;
+pub mod content_filter;
 pub mod post_maker;
 pub mod post_retriever;
 pub mod post_sender;
 pub mod prompts;
+pub mod providers;
 pub mod short_term_mem;
 pub mod long_term_mem;
+pub mod mentions;
+pub mod outbox;
+pub mod persona;
 pub mod significance_scorer;
+pub mod thread_composer;
+pub mod vector_index;
 pub mod wallet;
 
 use crate::{
     config::Config,
-    db::Pool,
-    utils::{Result, UtilError},
-    xdotcom::types::Tweet,
+    db::{models::{ContentAudit, NewOutboxPost, NewProposal, OutboxPost, Proposal, User}, Pool},
+    utils::{secret_guard::{self, SecretGuard}, Result, UtilError},
+    xdotcom::{types::Tweet, Client as TwitterClient, RateLimit},
 };
 
 pub struct EngineManager {
@@ -25,41 +35,66 @@ pub struct EngineManager {
     post_maker: Arc<post_maker::PostMaker>,
     post_retriever: Arc<post_retriever::PostRetriever>,
     post_sender: Arc<post_sender::PostSender>,
+    mentions: Arc<mentions::MentionsPoller>,
+    outbox: Arc<outbox::OutboxSender>,
+    thread_composer: Arc<thread_composer::ThreadComposer>,
+    approval: approval::ApprovalPolicy,
+    content_filter: content_filter::ContentFilterChain,
+    secret_guard: SecretGuard,
     significance: Arc<significance_scorer::SignificanceScorer>,
     wallet: Arc<wallet::Client>,
-    ai: Arc<ai::Client>,
+    agent_user_id: i32,
+    shutdown: CancellationToken,
     config: Config,
 }
 
 impl EngineManager {
-    pub async fn new(config: &Config, db_pool: Pool) -> Result<Self> {
+    pub async fn new(config: &Config, db_pool: Pool, shutdown: CancellationToken) -> Result<Self> {
         let ai_client = Arc::new(ai::Client::new(config)?);
         
         let short_term = Arc::new(Mutex::new(
-            short_term_mem::ShortTermMemoryEngine::new(config)?
+            short_term_mem::ShortTermMemoryEngine::load(config, &config.shutdown.snapshot_path).await?
         ));
         
         let long_term = Arc::new(
-            long_term_mem::LongTermMemoryEngine::new(config, db_pool.clone())?
+            long_term_mem::LongTermMemoryEngine::connect(config, db_pool.clone()).await?
         );
+
+        let agent_user = User::find_or_create(&db_pool, &config.twitter.username)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        // One client so sender and retriever share the OAuth 2.0 session
+        let twitter_client = TwitterClient::connect(config, db_pool.clone())
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?
+            .with_shutdown(shutdown.clone());
         
         Ok(Self {
             short_term,
             long_term,
             post_maker: Arc::new(post_maker::PostMaker::new(config)?),
-            post_retriever: Arc::new(post_retriever::PostRetriever::new(config)?),
-            post_sender: Arc::new(post_sender::PostSender::new(config)?),
+            post_retriever: Arc::new(post_retriever::PostRetriever::with_client(twitter_client.clone())),
+            post_sender: Arc::new(post_sender::PostSender::with_client(twitter_client.clone())),
+            mentions: Arc::new(mentions::MentionsPoller::new(twitter_client.clone())),
+            outbox: Arc::new(outbox::OutboxSender::with_client(config, twitter_client)),
+            thread_composer: Arc::new(thread_composer::ThreadComposer::default()),
+            approval: approval::ApprovalPolicy::new(config),
+            content_filter: content_filter::ContentFilterChain::new(config, ai_client.clone())?,
+            secret_guard: SecretGuard::new(config),
             significance: Arc::new(significance_scorer::SignificanceScorer::new(config)?),
             wallet: Arc::new(wallet::Client::new(config)?),
-            ai: ai_client,
+            agent_user_id: agent_user.id,
+            shutdown,
             config: config.clone(),
         })
     }
 
     pub async fn process_cognitive_cycle(&self, db: &Pool) -> Result<Option<String>> {
-        // 1. Gather context
+        // 1. Gather context; the novelty gate may look further back than the prompt
+        let history = self.config.novelty.history.max(post_maker::RECENT_POSTS_IN_PROMPT);
         let recent_posts = self.post_retriever
-            .retrieve_recent_posts(db, 10)
+            .retrieve_recent_posts(db, history)
             .await?;
             
         let external_context = self.post_retriever
@@ -69,7 +104,8 @@ impl EngineManager {
         // 2. Process in short-term memory
         let current_thought = {
             let mut short_term = self.short_term.lock().await;
-            short_term.process_current_context(&recent_posts, &external_context).await?
+            let prompt_posts = &recent_posts[..recent_posts.len().min(post_maker::RECENT_POSTS_IN_PROMPT)];
+            short_term.process_current_context(prompt_posts, &external_context).await?
         };
 
         // 3. Check memory significance
@@ -78,7 +114,7 @@ impl EngineManager {
             .await?;
 
         // 4. Store significant memories
-        if significance > self.config.memory_significance_threshold {
+        if significance > self.config.memory.significance_threshold {
             self.long_term
                 .store_memory(&current_thought)
                 .await?;
@@ -90,53 +126,315 @@ impl EngineManager {
             .retrieve_relevant_memories(&current_thought, 5)
             .await?;
 
+        // Don't start a new generation once shutdown has begun
+        if self.is_shutting_down() {
+            debug!("Shutdown in progress, skipping post generation");
+            return Ok(None);
+        }
+
         // 6. Generate post if conditions are met
         if should_generate_post(&current_thought, significance) {
             let memory_contexts: Vec<String> = relevant_memories
                 .iter()
-                .map(|m| m.content.clone())
+                .map(|m| m.memory.content.clone())
                 .collect();
 
-            let post_content = self.post_maker
+            // None when every candidate rehashed a recent post
+            return self.post_maker
                 .generate_post(
                     &current_thought,
                     &memory_contexts,
                     &recent_posts,
                     &external_context,
                 )
-                .await?;
-
-            return Ok(Some(post_content));
+                .await;
         }
 
         Ok(None)
     }
 
-    pub async fn handle_interaction(&self, tweet: &Tweet) -> Result<Option<String>> {
-        // Process mentions and replies
-        let notification_context = self.post_retriever
-            .fetch_notification_context()
-            .await?;
+    /// Submits a generated post. Once through the content filter it is queued
+    /// right away when its category is auto-approved and otherwise waits for
+    /// review.
+    pub async fn submit_post(&self, db: &Pool, content: &str) -> Result<Proposal> {
+        self.propose(db, approval::Category::Post, NewProposal {
+            content: content.to_string(),
+            ..new_proposal(approval::Category::Post)
+        }).await
+    }
+
+    /// Like [`EngineManager::submit_post`], with a local image attached to the
+    /// first tweet
+    pub async fn submit_post_with_image(
+        &self,
+        db: &Pool,
+        content: &str,
+        image_path: &Path,
+        alt_text: Option<&str>,
+    ) -> Result<Proposal> {
+        self.propose(db, approval::Category::Post, NewProposal {
+            content: content.to_string(),
+            image_path: Some(image_path.display().to_string()),
+            alt_text: alt_text.map(str::to_string),
+            ..new_proposal(approval::Category::Post)
+        }).await
+    }
+
+    /// Like [`EngineManager::submit_post`], for a reply to `reply_to_id`
+    pub async fn submit_reply(&self, db: &Pool, content: &str, reply_to_id: &str) -> Result<Proposal> {
+        self.propose(db, approval::Category::Reply, NewProposal {
+            content: content.to_string(),
+            reply_to_id: Some(reply_to_id.to_string()),
+            ..new_proposal(approval::Category::Reply)
+        }).await
+    }
+
+    async fn propose(&self, db: &Pool, category: approval::Category, mut proposal: NewProposal) -> Result<Proposal> {
+        // Never stored, let alone shown to reviewers
+        self.guard_secrets(&proposal.content, category.as_str())?;
+
+        // Transfers are written by the engine, not the model
+        let screening = match category {
+            approval::Category::Transfer => None,
+            _ => Some(self.content_filter.screen(&proposal.content).await?),
+        };
+        let submitted = proposal.content.clone();
+
+        let verdict = screening.as_ref().map_or(&content_filter::Verdict::Allow, |screening| &screening.verdict);
+        if let content_filter::Verdict::Rewrite(rewritten) = verdict {
+            proposal.original_content = Some(std::mem::replace(&mut proposal.content, rewritten.clone()));
+        }
+
+        let blocked = *verdict == content_filter::Verdict::Block;
+        let auto_approved = !blocked
+            && *verdict != content_filter::Verdict::Review
+            && self.approval.auto_approves(category);
+        if blocked {
+            proposal.status = Proposal::REJECTED.to_string();
+            proposal.decided_by = Some("content-filter".to_string());
+            proposal.reason = screening.as_ref().map(content_filter::Screening::reasons);
+        } else if auto_approved {
+            proposal.status = Proposal::APPROVED.to_string();
+            proposal.decided_by = Some("auto".to_string());
+        }
+
+        let proposal = Proposal::create(db, proposal)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        if let Some(screening) = &screening {
+            ContentAudit::record(db, screening.audit(Some(proposal.id), &proposal.category, &submitted)?)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+            if !screening.findings.is_empty() {
+                info!("Content filter {} #{}: {}", screening.verdict.as_str(), proposal.id, screening.reasons());
+            }
+        }
+
+        if blocked {
+            warn!("Blocked by content filter: {}", approval::summary(&proposal));
+            return Ok(proposal);
+        }
+        if !auto_approved {
+            info!("Holding for review: {}", approval::summary(&proposal));
+            return Ok(proposal);
+        }
+
+        self.execute_approved(db).await?;
+        Proposal::find(db, proposal.id)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?
+            .ok_or_else(|| UtilError::ConversionError(format!("Proposal {} disappeared", proposal.id)))
+    }
+
+    /// Fails proposals an earlier run claimed but never finished. Runs at
+    /// startup, before anything here can claim a proposal.
+    pub async fn fail_interrupted_proposals(&self, db: &Pool) -> Result<usize> {
+        let failed = Proposal::fail_interrupted(db)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        if failed > 0 {
+            warn!("Marked {} proposal(s) interrupted by the last shutdown as failed", failed);
+        }
+        Ok(failed)
+    }
+
+    /// Carries out approved proposals until none are left or shutdown begins.
+    /// Returns how many were executed, successfully or not.
+    pub async fn execute_approved(&self, db: &Pool) -> Result<usize> {
+        let mut executed = 0;
+
+        while !self.is_shutting_down() {
+            let Some(proposal) = Proposal::claim_approved(db)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?
+            else {
+                break;
+            };
+
+            let (status, outcome) = match self.execute(db, &proposal).await {
+                Ok(outcome) => (Proposal::EXECUTED, outcome),
+                Err(e) => {
+                    error!("Proposal {} failed: {}", proposal.id, e);
+                    (Proposal::FAILED, e.to_string())
+                }
+            };
 
+            if let Err(e) = Proposal::finish(db, proposal.id, status, &outcome).await {
+                error!(
+                    "Proposal {} ended {} ({}) but recording that failed: {}",
+                    proposal.id, status, outcome, e
+                );
+                return Err(UtilError::ConversionError(e.to_string()));
+            }
+            executed += 1;
+        }
+
+        Ok(executed)
+    }
+
+    async fn execute(&self, db: &Pool, proposal: &Proposal) -> Result<String> {
+        let category: approval::Category = proposal.category
+            .parse()
+            .map_err(UtilError::ConversionError)?;
+
+        match category {
+            approval::Category::Post => {
+                let key = format!("proposal:{}", proposal.id);
+                let image = proposal.image_path
+                    .as_deref()
+                    .map(|image_path| (Path::new(image_path), proposal.alt_text.as_deref()));
+                let queued = self.publish(db, &key, &proposal.content, "post", None, image).await?;
+                Ok(format!("queued {} part(s)", queued.len()))
+            }
+            approval::Category::Reply => {
+                let reply_to_id = proposal.reply_to_id
+                    .as_deref()
+                    .ok_or_else(|| UtilError::ConversionError("Reply proposal has no reply_to_id".to_string()))?;
+                let queued = self.publish_reply(db, &proposal.content, reply_to_id).await?;
+                Ok(format!("queued {} part(s)", queued.len()))
+            }
+            approval::Category::Transfer => {
+                let (Some(to_address), Some(amount_eth)) = (&proposal.to_address, proposal.amount_eth) else {
+                    return Err(UtilError::ConversionError("Transfer proposal has no address or amount".to_string()));
+                };
+                let tx_hash = format!("{:?}", self.wallet.transfer_eth(to_address, amount_eth).await?);
+                // The funds have moved, so keep the hash even if finishing fails
+                if let Err(e) = Proposal::record_result(db, proposal.id, &tx_hash).await {
+                    error!("Proposal {} sent transaction {} but recording it failed: {}", proposal.id, tx_hash, e);
+                }
+                Ok(tx_hash)
+            }
+        }
+    }
+
+    /// Queues a reply to `reply_to_id`. A tweet is only ever answered once, so
+    /// queuing a second reply to it returns nothing.
+    async fn publish_reply(&self, db: &Pool, content: &str, reply_to_id: &str) -> Result<Vec<OutboxPost>> {
+        let key = format!("reply:{}", reply_to_id);
+        self.publish(db, &key, content, "reply", Some(reply_to_id), None).await
+    }
+
+    /// Queues `content` for the outbox, as a numbered thread when it doesn't
+    /// fit in one tweet. Returns the queued parts in order. Only reached
+    /// through [`EngineManager::execute`], once a proposal is approved.
+    async fn publish(
+        &self,
+        db: &Pool,
+        key: &str,
+        content: &str,
+        post_type: &str,
+        reply_to_id: Option<&str>,
+        image: Option<(&Path, Option<&str>)>,
+    ) -> Result<Vec<OutboxPost>> {
+        // Checked whole, since a secret could be split across thread parts
+        self.guard_secrets(content, post_type)?;
+        let parts = self.thread_composer.compose(content)?;
+
+        let template = NewOutboxPost {
+            idempotency_key: key.to_string(),
+            user_id: self.agent_user_id,
+            username: self.config.twitter.username.clone(),
+            content: content.to_string(),
+            post_type: post_type.to_string(),
+            reply_to_id: reply_to_id.map(str::to_string),
+            parent_id: None,
+            thread_position: None,
+            image_path: image.map(|(image_path, _)| image_path.display().to_string()),
+            alt_text: image.and_then(|(_, alt_text)| alt_text.map(str::to_string)),
+        };
+
+        self.outbox.enqueue(db, key, &parts, template).await
+    }
+
+    fn guard_secrets(&self, content: &str, what: &str) -> Result<()> {
+        match self.secret_guard.check(content) {
+            Some(leak) => {
+                secret_guard::alert(&leak, what);
+                Err(UtilError::ConversionError(format!("Blocked {} containing {}", what, leak)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Sends queued posts until none are due or shutdown begins. Returns how
+    /// many rows were attempted.
+    pub async fn send_outbox(&self, db: &Pool) -> Result<usize> {
+        let mut attempted = 0;
+        while !self.is_shutting_down() && self.outbox.send_next(db).await? {
+            attempted += 1;
+        }
+        Ok(attempted)
+    }
+
+    pub async fn fetch_interactions(&self) -> Result<Vec<Tweet>> {
+        self.post_retriever.fetch_interactions(20).await
+    }
+
+    /// Mentions that arrived since the last poll, oldest first
+    pub async fn poll_mentions(&self, db: &Pool) -> Result<Vec<Tweet>> {
+        self.mentions.poll(db).await
+    }
+
+    /// Marks a polled mention answered so it is not handed out again
+    pub async fn mention_answered(&self, db: &Pool, tweet_id: &str) -> Result<()> {
+        self.mentions.answered(db, tweet_id).await
+    }
+
+    /// Hands a polled mention back to be retried on a later poll
+    pub async fn release_mention(&self, db: &Pool, tweet_id: &str) -> Result<()> {
+        self.mentions.release(db, tweet_id).await
+    }
+
+    pub async fn handle_interaction(&self, tweet: &Tweet) -> Result<Option<String>> {
         // Check if interaction requires response
-        if should_respond_to_tweet(tweet) {
+        if should_respond_to_tweet(tweet, &self.config.twitter.username) {
             let relevant_memories = self.long_term
                 .retrieve_relevant_memories(&tweet.text, 3)
                 .await?;
 
             let memory_context: Vec<String> = relevant_memories
                 .iter()
-                .map(|m| m.content.clone())
+                .map(|m| m.memory.content.clone())
                 .collect();
 
+            // The whole reply tree, or just the mention and what it references
+            let conversation = match self.post_retriever.fetch_conversation(tweet).await {
+                Ok(thread) => vec![thread.render(&tweet.id)],
+                Err(e) => {
+                    warn!("Could not rebuild conversation for {}: {}", tweet.id, e);
+                    let mut conversation: Vec<String> = tweet.referenced
+                        .iter()
+                        .map(|referenced| format!("{}: {}", referenced.author_handle(), referenced.text))
+                        .collect();
+                    conversation.push(format!("{}: {}", tweet.author_handle(), tweet.text));
+                    conversation
+                }
+            };
+
             // Generate response
             let response = self.post_maker
-                .generate_post(
-                    &tweet.text,
-                    &memory_context,
-                    &[], // No recent posts needed for direct replies
-                    &[tweet.text.clone()],
-                )
+                .generate_reply(&tweet.text, &conversation, &memory_context)
                 .await?;
 
             return Ok(Some(response));
@@ -145,49 +443,367 @@ impl EngineManager {
         Ok(None)
     }
 
+    /// Remaining X post/reply budget, so callers can skip work that couldn't
+    /// be published anyway
+    pub fn post_quota(&self) -> Option<RateLimit> {
+        self.post_sender.post_quota()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    pub async fn persist_state(&self, snapshot_path: &Path) -> Result<()> {
+        let short_term = self.short_term.lock().await;
+        short_term.snapshot(snapshot_path).await?;
+        info!("Short-term memory snapshot written to {}", snapshot_path.display());
+
+        self.long_term.save_index().await?;
+        Ok(())
+    }
+
     pub async fn consolidate_memories(&self) -> Result<()> {
         info!("Starting memory consolidation...");
-        self.long_term.consolidate_memories().await?;
+        let merged = self.long_term.consolidate_memories().await?;
+        info!("Memory consolidation merged {} clusters", merged);
+        Ok(())
+    }
+
+    pub async fn forget_memories(&self) -> Result<()> {
+        let archived = self.long_term.forget_memories().await?;
+        info!("Forgetting pass archived {} faded memories", archived);
         Ok(())
     }
 
-    pub async fn check_wallet_interactions(&self, content: &str) -> Result<Option<String>> {
+    /// Proposes sending `wallet.transfer_amount` to an address found in
+    /// `content`, once per address, while the balance stays above the minimum
+    pub async fn check_wallet_interactions(&self, db: &Pool, content: &str) -> Result<Option<Proposal>> {
+        let amount = self.config.wallet.transfer_amount;
+        if amount <= 0.0 {
+            return Ok(None);
+        }
+
         if let Some(address) = wallet::wallet_address_in_post(content).await {
+            let address = address.to_lowercase();
+            let proposed = Proposal::find_transfer_to(db, &address)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+            if let Some(proposed) = proposed {
+                debug!("Transfer to {} already proposed as #{}", address, proposed.id);
+                return Ok(None);
+            }
+
             let balance = self.wallet.get_balance().await?;
-            
-            if balance > self.config.min_transaction_balance {
-                // Process potential transaction
-                // Implementation depends on specific rules
+            let min_balance = ethers::utils::parse_ether(self.config.wallet.min_transaction_balance + amount)
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+            if balance > min_balance {
                 debug!("Found wallet interaction: {}", address);
+                let proposal = self.propose(db, approval::Category::Transfer, NewProposal {
+                    content: format!("Send {} ETH to {}", amount, address),
+                    to_address: Some(address),
+                    amount_eth: Some(amount),
+                    ..new_proposal(approval::Category::Transfer)
+                }).await?;
+                return Ok(Some(proposal));
             }
         }
         Ok(None)
     }
 }
 
+/// Pending proposal of `category` with nothing filled in
+fn new_proposal(category: approval::Category) -> NewProposal {
+    NewProposal {
+        category: category.to_string(),
+        status: Proposal::PENDING.to_string(),
+        content: String::new(),
+        original_content: None,
+        reply_to_id: None,
+        to_address: None,
+        amount_eth: None,
+        decided_by: None,
+        reason: None,
+        image_path: None,
+        alt_text: None,
+    }
+}
+
 fn should_generate_post(thought: &str, significance: f32) -> bool {
     // Basic heuristic - can be made more sophisticated
     significance > 0.6 && thought.len() > 20
 }
 
-fn should_respond_to_tweet(tweet: &Tweet) -> bool {
+fn should_respond_to_tweet(tweet: &Tweet, username: &str) -> bool {
     // Basic heuristic - can be made more sophisticated
-    tweet.text.contains("@yourbotname") || 
+    tweet.text.contains(&format!("@{}", username)) ||
     tweet.in_reply_to_user_id.is_some()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::schema;
+    use crate::testing::{self, llm, x};
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
 
     #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
     async fn test_cognitive_cycle() {
-        let config = Config::from_env().unwrap();
-        let db_pool = crate::db::establish_connection(&config).await.unwrap();
-        
-        let engine = EngineManager::new(&config, db_pool.clone()).await.unwrap();
-        let result = engine.process_cognitive_cycle(&db_pool).await;
-        
-        assert!(result.is_ok());
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("GET", "/2/users/:id/timelines/reverse_chronological", vec![x::timeline_response(&[
+            ("1", "42", "the merge anniversary is today"),
+        ])]);
+        // Tagged so the novelty gate doesn't take it for a post an earlier run left
+        let post = format!("happy merge anniversary {}", uuid::Uuid::new_v4().simple());
+        llm::mount_chat(&server, &[
+            // short-term thought, then its significance (x2: cycle + storage), then the post
+            "thinking hard about the merge anniversary and what comes next",
+            "9",
+            "9",
+            &post,
+        ]);
+
+        let config = testing::test_config(&server);
+        let db_pool = testing::test_pool(&config).await;
+
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+        let content = engine
+            .process_cognitive_cycle(&db_pool)
+            .await
+            .unwrap()
+            .expect("cycle should produce a post");
+        let _queues = testing::lock_queues(&db_pool).await;
+        let proposal = engine.submit_post(&db_pool, &content).await.unwrap();
+        assert_eq!(proposal.status, Proposal::EXECUTED);
+        assert!(x::posted_texts(&server).is_empty());
+        assert_eq!(engine.send_outbox(&db_pool).await.unwrap(), 1);
+
+        let mut conn = db_pool.get().await.unwrap();
+        let sent: OutboxPost = schema::outbox::table
+            .filter(schema::outbox::idempotency_key.eq(format!("proposal:{}:1", proposal.id)))
+            .first(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(content, post);
+        assert_eq!(sent.status, OutboxPost::SENT);
+        assert_eq!(sent.tweet_id.as_deref(), Some("1000"));
+        assert_eq!(sent.username, testing::TEST_USERNAME);
+        assert_eq!(x::posted_texts(&server), vec![content.clone()]);
+
+        let stored = engine
+            .post_retriever
+            .retrieve_recent_posts(&db_pool, 1)
+            .await
+            .unwrap();
+        assert_eq!(stored[0].content, content);
+
+        diesel::delete(schema::outbox::table.find(sent.id))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::delete(schema::posts::table.find(sent.post_id.unwrap()))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_approval_mode_holds_posts_for_review() {
+        let (server, _chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        config.approval.enabled = true;
+        config.approval.auto_approve.reply = true;
+        let db_pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&db_pool).await;
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        let post = engine.submit_post(&db_pool, "a take that needs a second look").await.unwrap();
+        assert_eq!(post.status, Proposal::PENDING);
+
+        // Replies are auto-approved, so this one is queued straight away
+        let reply = engine.submit_reply(&db_pool, "thanks!", "4242").await.unwrap();
+        assert_eq!(reply.status, Proposal::EXECUTED);
+        assert_eq!(reply.decided_by.as_deref(), Some("auto"));
+        engine.send_outbox(&db_pool).await.unwrap();
+        assert_eq!(x::posted_texts(&server), vec!["thanks!"]);
+
+        approval::review(&db_pool, post.id, approval::Decision::Edit("a better take".to_string()), "test")
+            .await
+            .unwrap();
+        assert_eq!(engine.execute_approved(&db_pool).await.unwrap(), 1);
+        engine.send_outbox(&db_pool).await.unwrap();
+        assert_eq!(x::posted_texts(&server), vec!["thanks!", "a better take"]);
+
+        let post = Proposal::find(&db_pool, post.id).await.unwrap().unwrap();
+        assert_eq!(post.status, Proposal::EXECUTED);
+        assert_eq!(post.original_content.as_deref(), Some("a take that needs a second look"));
+
+        // Media posts wait too, and keep their image until they're sent
+        let image = testing::temp_file("chart.png", b"not really a png");
+        let chart = engine.submit_post_with_image(&db_pool, "look at this", &image, Some("A chart")).await.unwrap();
+        assert_eq!(chart.status, Proposal::PENDING);
+        approval::review(&db_pool, chart.id, approval::Decision::Approve, "test").await.unwrap();
+        assert_eq!(engine.execute_approved(&db_pool).await.unwrap(), 1);
+        engine.send_outbox(&db_pool).await.unwrap();
+        assert_eq!(x::posted_texts(&server), vec!["thanks!", "a better take", "look at this"]);
+        let sent = &server.requests_to("POST", "/2/tweets")[2];
+        assert_eq!(sent.json()["media"]["media_ids"][0], "5000");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_content_filter_screens_submissions() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let db_pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&db_pool).await;
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        let rewritten = engine.submit_post(&db_pool, "ping me at tom@example.com").await.unwrap();
+        assert_eq!(rewritten.status, Proposal::EXECUTED);
+        assert_eq!(rewritten.content, "ping me at [redacted]");
+        assert_eq!(rewritten.original_content.as_deref(), Some("ping me at tom@example.com"));
+
+        let leaked = "gm 0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let blocked = engine.submit_post(&db_pool, leaked).await.unwrap();
+        assert_eq!(blocked.status, Proposal::REJECTED);
+        assert_eq!(blocked.decided_by.as_deref(), Some("content-filter"));
+        assert_eq!(blocked.reason.as_deref(), Some("secrets: private key"));
+
+        engine.send_outbox(&db_pool).await.unwrap();
+        assert_eq!(x::posted_texts(&server), vec!["ping me at [redacted]"]);
+
+        let audit = ContentAudit::for_proposal(&db_pool, blocked.id).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].decision, "block");
+        assert_eq!(audit[0].content, leaked);
+        assert_eq!(audit[0].findings[0]["filter"], "secrets");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_secret_guard_blocks_before_storing() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let db_pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&db_pool).await;
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        // Base64 slips past the content filter's key pattern, not the guard
+        let key = ethers::utils::hex::decode(&testing::chain::ANVIL_PRIVATE_KEY[2..]).unwrap();
+        let leaked = format!("backup: {}", base64::Engine::encode(&base64::engine::general_purpose::STANDARD, key));
+        let error = engine.submit_reply(&db_pool, &leaked, "4242").await.unwrap_err();
+        assert!(error.to_string().contains("wallet.private_key (base64)"));
+        let image = testing::temp_file("leak.png", b"not really a png");
+        assert!(engine.submit_post_with_image(&db_pool, &leaked, &image, None).await.is_err());
+
+        assert!(Proposal::list(&db_pool, None, 10).await.unwrap().is_empty());
+        assert_eq!(engine.send_outbox(&db_pool).await.unwrap(), 0);
+        assert!(x::posted_texts(&server).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_transfers_wait_for_approval() {
+        let (server, chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        config.approval.enabled = true;
+        config.wallet.transfer_amount = 0.01;
+        let db_pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&db_pool).await;
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        let content = "tip me at 0x742d35Cc6634C0532925a3b844Bc454e4438f44e";
+        let proposal = engine
+            .check_wallet_interactions(&db_pool, content)
+            .await
+            .unwrap()
+            .expect("address should be proposed a transfer");
+        assert_eq!(proposal.status, Proposal::PENDING);
+        assert!(chain.transactions().is_empty());
+
+        // One proposal per address, however often it comes up
+        assert!(engine.check_wallet_interactions(&db_pool, content).await.unwrap().is_none());
+
+        approval::review(&db_pool, proposal.id, approval::Decision::Approve, "test").await.unwrap();
+        assert_eq!(engine.execute_approved(&db_pool).await.unwrap(), 1);
+
+        let sent = chain.transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "0x742d35cc6634c0532925a3b844bc454e4438f44e");
+        assert_eq!(sent[0].value, ethers::utils::parse_ether(0.01f64).unwrap());
+        let executed = Proposal::find(&db_pool, proposal.id).await.unwrap().unwrap();
+        assert_eq!(executed.status, Proposal::EXECUTED);
+        assert!(executed.result.unwrap().starts_with("0x"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_interrupted_proposals_fail_without_rerunning() {
+        let (server, chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let db_pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&db_pool).await;
+        let engine = EngineManager::new(&config, db_pool.clone(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        // Two approved transfers claimed by a run that died, one after sending
+        for address in ["0x742d35Cc6634C0532925a3b844Bc454e4438f44e", "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"] {
+            Proposal::create(&db_pool, NewProposal {
+                status: Proposal::APPROVED.to_string(),
+                content: format!("Send 0.01 ETH to {}", address),
+                to_address: Some(address.to_string()),
+                amount_eth: Some(0.01),
+                ..new_proposal(approval::Category::Transfer)
+            })
+            .await
+            .unwrap();
+        }
+        let unsent = Proposal::claim_approved(&db_pool).await.unwrap().unwrap();
+        let sent = Proposal::claim_approved(&db_pool).await.unwrap().unwrap();
+        Proposal::record_result(&db_pool, sent.id, "0xabc").await.unwrap();
+
+        assert_eq!(engine.fail_interrupted_proposals(&db_pool).await.unwrap(), 2);
+        let unsent = Proposal::find(&db_pool, unsent.id).await.unwrap().unwrap();
+        assert_eq!(unsent.status, Proposal::FAILED);
+        assert_eq!(unsent.result.as_deref(), Some("interrupted"));
+        let sent = Proposal::find(&db_pool, sent.id).await.unwrap().unwrap();
+        assert_eq!(sent.status, Proposal::FAILED);
+        assert_eq!(sent.result.as_deref(), Some("interrupted after 0xabc"));
+
+        assert_eq!(engine.execute_approved(&db_pool).await.unwrap(), 0);
+        assert!(chain.transactions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_should_respond_to_mention() {
+        let tweet = Tweet {
+            id: "1".to_string(),
+            text: "hey @tomflash what do you think?".to_string(),
+            author_id: Some("42".to_string()),
+            created_at: None,
+            conversation_id: None,
+            in_reply_to_user_id: None,
+            referenced_tweets: None,
+            author: None,
+            referenced: Vec::new(),
+        };
+
+        assert!(should_respond_to_tweet(&tweet, "tomflash"));
+        assert!(!should_respond_to_tweet(&tweet, "someoneelse"));
+    }
+}
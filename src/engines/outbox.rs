@@ -0,0 +1,436 @@
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Utc;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{Config, OutboxConfig, RateLimitPolicy},
+    db::{models::{NewOutboxPost, NewPost, OutboxPost, Post}, Pool},
+    utils::{Result, UtilError},
+    xdotcom::{types::Tweet, Client as TwitterClient, XError},
+};
+
+/// How long a claimed row may stay `sending` before another worker takes it
+/// over; longer than any single send, media upload included. Sends never wait
+/// out a rate limit, so the lease doesn't depend on `max_rate_limit_wait`.
+const SENDING_LEASE: Duration = Duration::from_secs(300);
+
+/// How far back a retry looks for a tweet an earlier attempt already posted
+const ADOPTION_WINDOW: chrono::Duration = chrono::Duration::minutes(5);
+
+/// Sends queued posts one row at a time, retrying with backoff and
+/// dead-lettering what X won't accept
+pub struct OutboxSender {
+    twitter_client: TwitterClient,
+    config: OutboxConfig,
+}
+
+impl OutboxSender {
+    pub fn new(config: &Config) -> Result<Self> {
+        let twitter_client = TwitterClient::new(config)?;
+
+        Ok(Self::with_client(config, twitter_client))
+    }
+
+    pub fn with_client(config: &Config, twitter_client: TwitterClient) -> Self {
+        Self {
+            // A rate-limited row is rescheduled for the reset instead of
+            // holding its claim while the client sleeps
+            twitter_client: twitter_client.with_rate_limit_policy(RateLimitPolicy::FailFast),
+            config: config.outbox.clone(),
+        }
+    }
+
+    /// Queues `parts` as one post (or thread) under `key`. Returns the queued
+    /// rows, or nothing if `key` was queued before.
+    pub async fn enqueue(
+        &self,
+        db: &Pool,
+        key: &str,
+        parts: &[String],
+        template: NewOutboxPost,
+    ) -> Result<Vec<OutboxPost>> {
+        let is_thread = parts.len() > 1;
+        let rows = parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let first = index == 0;
+                NewOutboxPost {
+                    idempotency_key: format!("{}:{}", key, index + 1),
+                    content: part.clone(),
+                    reply_to_id: if first { template.reply_to_id.clone() } else { None },
+                    thread_position: is_thread.then_some(index as i32 + 1),
+                    image_path: if first { template.image_path.clone() } else { None },
+                    alt_text: if first { template.alt_text.clone() } else { None },
+                    ..template.clone()
+                }
+            })
+            .collect();
+
+        let queued = OutboxPost::enqueue(db, rows)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        if queued.is_empty() {
+            debug!("{} is already queued", key);
+        }
+        Ok(queued)
+    }
+
+    /// Claims and sends the next due row. Returns false when nothing was due.
+    pub async fn send_next(&self, db: &Pool) -> Result<bool> {
+        let stale_before = Utc::now() - chrono::Duration::from_std(SENDING_LEASE)
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let Some(item) = OutboxPost::claim_next(db, stale_before)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?
+        else {
+            return Ok(false);
+        };
+
+        let (reply_to_id, thread_id) = self.reply_target(db, &item).await?;
+
+        match self.deliver(&item, reply_to_id.as_deref()).await {
+            Ok(tweet) => {
+                // A part that starts a thread gives the thread its id
+                let thread_id = match (&thread_id, item.thread_position) {
+                    (None, Some(_)) => Some(tweet.id.clone()),
+                    _ => thread_id,
+                };
+
+                // If storing fails the row stays `sending`; the retry after
+                // the lease finds the tweet instead of posting it again
+                let post = self.store_sent(db, &item, &tweet, thread_id).await?;
+                info!("Sent outbox row {} as tweet {}", item.id, tweet.id);
+                debug!("Stored as post {}", post.id);
+            }
+            Err(e) => self.record_failure(db, &item, &e).await?,
+        }
+
+        Ok(true)
+    }
+
+    /// The tweet a row replies to and the thread it belongs to, taken from
+    /// its parent once that has been sent
+    async fn reply_target(&self, db: &Pool, item: &OutboxPost) -> Result<(Option<String>, Option<String>)> {
+        let Some(parent_id) = item.parent_id else {
+            return Ok((item.reply_to_id.clone(), None));
+        };
+
+        let parent = OutboxPost::find(db, parent_id)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        Ok((parent.tweet_id, parent.thread_id))
+    }
+
+    async fn deliver(&self, item: &OutboxPost, reply_to_id: Option<&str>) -> std::result::Result<Tweet, XError> {
+        // An earlier attempt may have posted before failing to record it
+        if item.attempts > 1 {
+            if let Some(tweet) = self.find_already_sent(item, reply_to_id).await? {
+                info!("Outbox row {} was already posted as {}", item.id, tweet.id);
+                return Ok(tweet);
+            }
+        }
+
+        let media_ids = match &item.image_path {
+            Some(image_path) => vec![
+                self.twitter_client
+                    .upload_media(Path::new(image_path), item.alt_text.as_deref())
+                    .await?,
+            ],
+            None => Vec::new(),
+        };
+
+        match reply_to_id {
+            Some(reply_to_id) => {
+                info!("Sending reply to {}: {}", reply_to_id, item.content);
+                self.twitter_client.reply_to_tweet_with_media(&item.content, reply_to_id, &media_ids).await
+            }
+            None => {
+                info!("Sending post: {}", item.content);
+                self.twitter_client.post_tweet_with_media(&item.content, &media_ids).await
+            }
+        }
+    }
+
+    /// The agent's recent tweet with this row's text and reply target, if any
+    async fn find_already_sent(
+        &self,
+        item: &OutboxPost,
+        reply_to_id: Option<&str>,
+    ) -> std::result::Result<Option<Tweet>, XError> {
+        let me = self.twitter_client.me().await?;
+        let recent = self.twitter_client
+            .get_user_tweets(&me.id, item.created_at - ADOPTION_WINDOW, 100)
+            .await?;
+
+        let content = comparable_text(&item.content);
+        Ok(recent
+            .into_iter()
+            .find(|tweet| tweet.replied_to_id() == reply_to_id && comparable_text(&tweet.text) == content))
+    }
+
+    async fn store_sent(
+        &self,
+        db: &Pool,
+        item: &OutboxPost,
+        tweet: &Tweet,
+        thread_id: Option<String>,
+    ) -> Result<Post> {
+        let post = NewPost {
+            content: item.content.clone(),
+            user_id: item.user_id,
+            username: item.username.clone(),
+            post_type: item.post_type.clone(),
+            image_path: item.image_path.clone(),
+            tweet_id: Some(tweet.id.clone()),
+            thread_id,
+            thread_position: item.thread_position,
+        };
+
+        OutboxPost::mark_sent(db, item.id, post)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))
+    }
+
+    async fn record_failure(&self, db: &Pool, item: &OutboxPost, error: &XError) -> Result<()> {
+        let message = error.to_string();
+
+        if is_permanent(error) || item.attempts as u32 >= self.config.max_attempts {
+            let dead = OutboxPost::dead_letter(db, item.id, &message)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+            warn!(
+                "Dead-lettered outbox row {} ({} row(s)) after {} attempt(s): {}",
+                item.id, dead, item.attempts, message
+            );
+            return Ok(());
+        }
+
+        let retry_at = match error {
+            XError::RateLimit { reset_at, .. } => *reset_at,
+            _ => Utc::now() + backoff(item.attempts as u32, self.config.retry_base, self.config.retry_max),
+        };
+
+        OutboxPost::schedule_retry(db, item.id, &message, retry_at)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        warn!("Outbox row {} failed (attempt {}), retrying at {}: {}", item.id, item.attempts, retry_at, message);
+        Ok(())
+    }
+}
+
+/// Errors that sending again can't fix
+fn is_permanent(error: &XError) -> bool {
+    matches!(error, XError::Rejected { .. } | XError::MediaError(_) | XError::SecretLeak(_))
+}
+
+/// Delay before retry `attempt` (1-based): `base` doubling each attempt,
+/// capped at `max`
+fn backoff(attempt: u32, base: Duration, max: Duration) -> chrono::Duration {
+    let delay = base
+        .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+        .map_or(max, |delay| delay.min(max));
+
+    chrono::Duration::from_std(delay).unwrap_or(chrono::Duration::MAX)
+}
+
+/// Text as X would echo it back: URLs are rewritten to t.co and a few
+/// characters come back as HTML entities, so compare what's left
+fn comparable_text(text: &str) -> String {
+    text.replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .split_whitespace()
+        .filter(|word| !word.starts_with("http://") && !word.starts_with("https://"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{models::User, schema::outbox};
+    use crate::testing::{self, x, FakeResponse};
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+    use serde_json::json;
+
+    fn template(user: &User, reply_to_id: Option<&str>) -> NewOutboxPost {
+        NewOutboxPost {
+            idempotency_key: String::new(),
+            user_id: user.id,
+            username: user.username.clone(),
+            content: String::new(),
+            post_type: "reply".to_string(),
+            reply_to_id: reply_to_id.map(str::to_string),
+            parent_id: None,
+            thread_position: None,
+            image_path: None,
+            alt_text: None,
+        }
+    }
+
+    async fn drain(sender: &OutboxSender, db: &Pool) -> usize {
+        let mut attempted = 0;
+        while sender.send_next(db).await.unwrap() {
+            attempted += 1;
+        }
+        attempted
+    }
+
+    #[test]
+    fn test_backoff() {
+        let base = Duration::from_secs(30);
+        let max = Duration::from_secs(3600);
+
+        let delays: Vec<i64> = [1, 2, 3, 7, 8, 40]
+            .iter()
+            .map(|attempt| backoff(*attempt, base, max).num_seconds())
+            .collect();
+        assert_eq!(delays, vec![30, 60, 120, 1920, 3600, 3600]);
+
+        assert!(is_permanent(&XError::Rejected { status: 403, body: "duplicate".to_string() }));
+        assert!(is_permanent(&XError::SecretLeak("wallet.private_key (hex)".to_string())));
+        assert!(!is_permanent(&XError::ApiError("Service Unavailable".to_string())));
+        assert_eq!(comparable_text("gm &amp; gn https://t.co/abc"), "gm & gn");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_thread_sends_in_order() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&pool).await;
+        let user = User::find_or_create(&pool, testing::TEST_USERNAME).await.unwrap();
+        let sender = OutboxSender::new(&config).unwrap();
+
+        let parts: Vec<String> = ["first 1/3", "second 2/3", "third 3/3"].map(String::from).to_vec();
+        let queued = sender.enqueue(&pool, "reply:999", &parts, template(&user, Some("999"))).await.unwrap();
+        assert_eq!(queued.len(), 3);
+        assert!(sender.enqueue(&pool, "reply:999", &parts, template(&user, Some("999"))).await.unwrap().is_empty());
+
+        assert_eq!(drain(&sender, &pool).await, 3);
+
+        let replies: Vec<String> = server
+            .requests_to("POST", "/2/tweets")
+            .iter()
+            .map(|request| request.json()["reply"]["in_reply_to_tweet_id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(replies, vec!["999", "1000", "1001"]);
+
+        for (position, row) in queued.iter().enumerate() {
+            let sent = OutboxPost::find(&pool, row.id).await.unwrap();
+            assert_eq!(sent.status, OutboxPost::SENT);
+            assert_eq!(sent.thread_id.as_deref(), Some("1000"));
+            assert_eq!(sent.thread_position, Some(position as i32 + 1));
+            assert!(sent.post_id.is_some());
+        }
+        assert_eq!(OutboxPost::find(&pool, queued[2].id).await.unwrap().tweet_id.as_deref(), Some("1002"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_retries_then_dead_letters() {
+        let (server, _chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        config.outbox.retry_base = Duration::ZERO;
+        let pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&pool).await;
+        let user = User::find_or_create(&pool, testing::TEST_USERNAME).await.unwrap();
+        let sender = OutboxSender::new(&config).unwrap();
+
+        server.script("POST", "/2/tweets", vec![
+            FakeResponse::json(503, json!({"title": "Service Unavailable"})),
+            FakeResponse::json(403, json!({"detail": "You are not allowed to create a Tweet with duplicate content."})),
+        ]);
+
+        let parts: Vec<String> = ["one 1/2", "two 2/2"].map(String::from).to_vec();
+        let queued = sender.enqueue(&pool, "post:dup", &parts, template(&user, None)).await.unwrap();
+
+        // The second part waits on the first, which fails, retries, then is refused
+        assert_eq!(drain(&sender, &pool).await, 2);
+        assert_eq!(x::posted_texts(&server), vec!["one 1/2", "one 1/2"]);
+
+        let first = OutboxPost::find(&pool, queued[0].id).await.unwrap();
+        assert_eq!(first.status, OutboxPost::DEAD);
+        assert_eq!(first.attempts, 2);
+        assert!(first.last_error.unwrap().contains("duplicate content"));
+
+        let second = OutboxPost::find(&pool, queued[1].id).await.unwrap();
+        assert_eq!(second.status, OutboxPost::DEAD);
+        assert_eq!(second.attempts, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_rate_limit_reschedules_without_waiting() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&pool).await;
+        let user = User::find_or_create(&pool, testing::TEST_USERNAME).await.unwrap();
+        let sender = OutboxSender::new(&config).unwrap();
+
+        // Well within `max_rate_limit_wait`, which the outbox must not sleep for
+        let reset = (Utc::now() + chrono::Duration::minutes(10)).timestamp();
+        server.script("POST", "/2/tweets", vec![
+            FakeResponse::json(429, json!({"title": "Too Many Requests"}))
+                .with_header("x-rate-limit-limit", "50")
+                .with_header("x-rate-limit-remaining", "0")
+                .with_header("x-rate-limit-reset", &reset.to_string()),
+        ]);
+
+        let queued = sender
+            .enqueue(&pool, "post:limited", &["later".to_string()], template(&user, None))
+            .await
+            .unwrap();
+        assert_eq!(drain(&sender, &pool).await, 1);
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), 1);
+
+        let row = OutboxPost::find(&pool, queued[0].id).await.unwrap();
+        assert_eq!(row.status, OutboxPost::PENDING);
+        assert_eq!(row.next_attempt_at.timestamp(), reset);
+
+        diesel::delete(outbox::table.find(row.id))
+            .execute(&mut pool.get().await.unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_retry_adopts_tweet_already_posted() {
+        let (server, _chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        config.outbox.retry_base = Duration::ZERO;
+        let pool = testing::test_pool(&config).await;
+        let _queues = testing::lock_queues(&pool).await;
+        let user = User::find_or_create(&pool, testing::TEST_USERNAME).await.unwrap();
+        let sender = OutboxSender::new(&config).unwrap();
+
+        // The post went through but the response was lost
+        server.script("POST", "/2/tweets", vec![FakeResponse::json(503, json!({"title": "Service Unavailable"}))]);
+        server.script("GET", "/2/users/:id/tweets", vec![x::timeline_response(&[
+            ("777", "1", "gm &amp; welcome https://t.co/xyz"),
+        ])]);
+
+        let queued = sender
+            .enqueue(&pool, "post:lost", &["gm & welcome https://example.com".to_string()], template(&user, None))
+            .await
+            .unwrap();
+        assert_eq!(drain(&sender, &pool).await, 2);
+
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), 1);
+        let sent = OutboxPost::find(&pool, queued[0].id).await.unwrap();
+        assert_eq!(sent.status, OutboxPost::SENT);
+        assert_eq!(sent.tweet_id.as_deref(), Some("777"));
+        assert_eq!(sent.thread_id, None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::PersonaConfig,
+    utils::{Result, UtilError},
+};
+
+/// Who the agent is and how it writes, injected into every generation prompt.
+/// Loaded from `persona.file` so personalities can be swapped without a rebuild.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Persona {
+    pub name: String,
+    pub bio: String,
+    /// Rules for tone and style, e.g. "all lowercase" or "no hashtags"
+    pub voice: Vec<String>,
+    pub topics: Topics,
+    /// Posts in the persona's voice, shown to the model as references
+    pub examples: Vec<String>,
+    /// Phrases the persona never uses
+    pub banned_phrases: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Topics {
+    pub favor: Vec<String>,
+    pub avoid: Vec<String>,
+}
+
+impl Persona {
+    /// The persona file when one is configured, otherwise just the configured
+    /// name and bio. Either fills in for the other where it is empty.
+    pub fn load(config: &PersonaConfig) -> Result<Self> {
+        let mut persona = match &config.file {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        if persona.name.trim().is_empty() {
+            persona.name = config.name.clone();
+        }
+        if persona.bio.trim().is_empty() {
+            persona.bio = config.bio.clone();
+        }
+        Ok(persona)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| UtilError::ConversionError(format!("reading persona {}: {}", path.display(), e)))?;
+
+        toml::from_str(&contents)
+            .map_err(|e| UtilError::ConversionError(format!("parsing persona {}: {}", path.display(), e)))
+    }
+
+    /// System message that puts the model in character
+    pub fn system_message(&self) -> String {
+        let mut message = format!("You are {}, writing your own posts and replies on X.", self.name);
+        if !self.bio.trim().is_empty() {
+            message.push_str(&format!("\n\nAbout you: {}", self.bio.trim()));
+        }
+        message.push_str("\n\nStay in character. Reply with the text only, no preamble or quotes.");
+        message
+    }
+
+    /// The voice, topic and phrase rules plus examples, as a prompt section.
+    /// Empty when the persona defines none.
+    pub fn guidelines(&self) -> String {
+        let mut sections = Vec::new();
+
+        if !self.voice.is_empty() {
+            sections.push(format!("Your voice:\n{}", bullets(&self.voice)));
+        }
+        if !self.topics.favor.is_empty() {
+            sections.push(format!("Topics you gravitate to: {}", self.topics.favor.join(", ")));
+        }
+        if !self.topics.avoid.is_empty() {
+            sections.push(format!("Topics you stay away from: {}", self.topics.avoid.join(", ")));
+        }
+        if !self.banned_phrases.is_empty() {
+            sections.push(format!("Never use these phrases:\n{}", bullets(&self.banned_phrases)));
+        }
+        if !self.examples.is_empty() {
+            sections.push(format!("Posts you have written before, for style only:\n{}", bullets(&self.examples)));
+        }
+
+        sections.join("\n\n")
+    }
+}
+
+fn bullets(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("- {}", item))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_persona_file() {
+        let path = std::env::temp_dir().join(format!("tomflash-persona-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"
+            name = "vitabot"
+            voice = ["all lowercase", "no hashtags"]
+            examples = ["gm. blobs are cheap again"]
+            banned_phrases = ["to the moon"]
+
+            [topics]
+            favor = ["rollups", "zk"]
+            avoid = ["price predictions"]
+        "#).unwrap();
+
+        let config = PersonaConfig {
+            name: "tomflash".to_string(),
+            bio: "an ethereum-native agent".to_string(),
+            file: Some(path.clone()),
+        };
+        let persona = Persona::load(&config).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(persona.name, "vitabot");
+        assert_eq!(persona.bio, "an ethereum-native agent");
+        assert_eq!(persona.topics.avoid, vec!["price predictions"]);
+
+        let guidelines = persona.guidelines();
+        assert!(guidelines.contains("- all lowercase\n- no hashtags"));
+        assert!(guidelines.contains("Topics you gravitate to: rollups, zk"));
+        assert!(guidelines.contains("Never use these phrases:\n- to the moon"));
+        assert!(persona.system_message().starts_with("You are vitabot"));
+    }
+
+    #[test]
+    fn test_persona_from_config_only() {
+        let persona = Persona::load(&PersonaConfig::default()).unwrap();
+        assert_eq!(persona.name, "tomflash");
+        assert_eq!(persona.guidelines(), "");
+        assert!(!persona.system_message().contains("About you"));
+    }
+
+    #[test]
+    fn test_unknown_persona_key_rejected() {
+        let path = std::env::temp_dir().join(format!("tomflash-persona-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "name = \"x\"\ntone = \"spicy\"\n").unwrap();
+        let result = Persona::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
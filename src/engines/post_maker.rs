@@ -1,66 +1,166 @@
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
+use tokio::sync::Mutex;
 use tokio::time::sleep;
 use tracing::{debug, info};
 
 use crate::{
     config::Config,
-    utils::{Result, UtilError},
+    utils::{tweet_text, Result},
     db::models::Post,
-    xdotcom::types::Tweet,
 };
 
 use super::ai::Client as AIClient;
+use super::short_term_mem::cosine_similarity;
+
+/// How many of the recent posts the generation prompt includes
+pub const RECENT_POSTS_IN_PROMPT: usize = 10;
 
 pub struct PostMaker {
     ai_client: AIClient,
     config: Config,
+    /// Embeddings of recent posts by id, so each is only embedded once
+    history_embeddings: Mutex<HashMap<i32, Vec<f32>>>,
 }
 
 impl PostMaker {
     pub fn new(config: &Config) -> Result<Self> {
         let ai_client = AIClient::new(config)?;
-        
+
         Ok(Self {
             ai_client,
             config: config.clone(),
+            history_embeddings: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Generates a post from the given context. With the novelty gate on, a
+    /// candidate that rehashes one of `recent_posts` is regenerated, and
+    /// dropped (`None`) once the attempts run out.
     pub async fn generate_post(
         &self,
         short_term_memory: &str,
         long_term_memories: &[String],
         recent_posts: &[Post],
         external_context: &[String],
-    ) -> Result<String> {
+    ) -> Result<Option<String>> {
         // Add delay to simulate thinking/processing
         sleep(Duration::from_secs(5)).await;
 
         let recent_post_contents: Vec<String> = recent_posts
             .iter()
+            .take(RECENT_POSTS_IN_PROMPT)
             .map(|p| p.content.clone())
             .collect();
 
-        let content = self.ai_client
-            .generate_post(
-                short_term_memory,
-                long_term_memories,
-                &recent_post_contents,
-                external_context,
-            )
-            .await?;
+        let mut thought = short_term_memory.to_string();
+        let mut regenerations = 0;
 
-        // Clean up and validate the generated content
-        let cleaned_content = self.clean_content(&content);
-        
-        if cleaned_content.len() > 280 {
-            return Err(UtilError::ConversionError("Generated content too long".to_string()));
+        loop {
+            let content = self.ai_client
+                .generate_post(
+                    &thought,
+                    long_term_memories,
+                    &recent_post_contents,
+                    external_context,
+                )
+                .await?;
+
+            let cleaned_content = self.clean_content(&content);
+
+            let Some(earlier) = self.rehash_of(&cleaned_content, recent_posts).await? else {
+                // Content over the tweet limit is split into a thread when published
+                let length = tweet_text::weighted_length(&cleaned_content);
+                if length > tweet_text::MAX_WEIGHTED_LENGTH {
+                    info!("Generated post is {} weighted characters, posting it as a thread", length);
+                }
+
+                debug!("Generated post: {}", cleaned_content);
+                return Ok(Some(cleaned_content));
+            };
+
+            if regenerations >= self.config.novelty.regenerate_attempts {
+                info!("Dropping generated post, still a rehash of post {} after {} regeneration(s)", earlier.id, regenerations);
+                return Ok(None);
+            }
+            regenerations += 1;
+
+            thought = format!(
+                "{}\n\nA draft just repeated one of your recent posts:\n\"{}\"\nSay something new.",
+                short_term_memory, earlier.content
+            );
         }
+    }
+
+    /// Generates a reply to `tweet` in the persona's voice
+    pub async fn generate_reply(
+        &self,
+        tweet: &str,
+        conversation: &[String],
+        memories: &[String],
+    ) -> Result<String> {
+        let content = self.ai_client.generate_reply(tweet, conversation, memories).await?;
+        let cleaned_content = self.clean_content(&content);
 
-        debug!("Generated post: {}", cleaned_content);
+        debug!("Generated reply: {}", cleaned_content);
         Ok(cleaned_content)
     }
 
+    /// The recent post `candidate` rehashes, if any. Logs how close it came.
+    async fn rehash_of<'a>(&self, candidate: &str, recent_posts: &'a [Post]) -> Result<Option<&'a Post>> {
+        let settings = &self.config.novelty;
+        let history = &recent_posts[..recent_posts.len().min(settings.history)];
+        if !settings.enabled || history.is_empty() {
+            return Ok(None);
+        }
+
+        let embedding = self.ai_client.generate_embedding(candidate).await?;
+        let history_embeddings = self.embed_history(history).await?;
+        let candidate_ngrams = ngrams(candidate, settings.ngram_size);
+
+        let mut closest = (0.0f32, 0.0f32);
+        for (post, post_embedding) in history.iter().zip(&history_embeddings) {
+            let similarity = cosine_similarity(&embedding, post_embedding);
+            let overlap = ngram_overlap(&candidate_ngrams, &ngrams(&post.content, settings.ngram_size));
+
+            if similarity >= settings.similarity_threshold || overlap >= settings.overlap_threshold {
+                info!(
+                    "Generated post rehashes post {} (similarity {:.3}, n-gram overlap {:.3})",
+                    post.id, similarity, overlap
+                );
+                return Ok(Some(post));
+            }
+            closest = (closest.0.max(similarity), closest.1.max(overlap));
+        }
+
+        info!(
+            "Generated post is novel against {} recent posts (max similarity {:.3}, max n-gram overlap {:.3})",
+            history.len(), closest.0, closest.1
+        );
+        Ok(None)
+    }
+
+    /// Embeddings of `history` in order, reusing earlier ones and forgetting
+    /// posts that have dropped out of it
+    async fn embed_history(&self, history: &[Post]) -> Result<Vec<Vec<f32>>> {
+        let mut cache = self.history_embeddings.lock().await;
+        cache.retain(|id, _| history.iter().any(|post| post.id == *id));
+
+        let mut embeddings = Vec::with_capacity(history.len());
+        for post in history {
+            let embedding = match cache.get(&post.id) {
+                Some(embedding) => embedding.clone(),
+                None => {
+                    let embedding = self.ai_client.generate_embedding(&post.content).await?;
+                    cache.insert(post.id, embedding.clone());
+                    embedding
+                }
+            };
+            embeddings.push(embedding);
+        }
+        Ok(embeddings)
+    }
+
     fn clean_content(&self, content: &str) -> String {
         content
             .replace("Tweet:", "")
@@ -68,4 +168,101 @@ impl PostMaker {
             .trim()
             .to_string()
     }
-}
\ No newline at end of file
+}
+
+/// Lowercased word n-grams of `text`; text shorter than `n` words is one gram
+fn ngrams(text: &str, n: usize) -> HashSet<Vec<String>> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    if words.is_empty() {
+        return HashSet::new();
+    }
+    if words.len() < n {
+        return HashSet::from([words]);
+    }
+    words.windows(n).map(<[String]>::to_vec).collect()
+}
+
+/// Share of `candidate`'s n-grams that also occur in `earlier`
+fn ngram_overlap(candidate: &HashSet<Vec<String>>, earlier: &HashSet<Vec<String>>) -> f32 {
+    if candidate.is_empty() {
+        return 0.0;
+    }
+    candidate.intersection(earlier).count() as f32 / candidate.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, llm};
+    use chrono::Utc;
+
+    fn post(id: i32, content: &str) -> Post {
+        Post {
+            id,
+            content: content.to_string(),
+            user_id: 1,
+            username: testing::TEST_USERNAME.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            post_type: "post".to_string(),
+            comment_count: 0,
+            image_path: None,
+            tweet_id: None,
+            thread_id: None,
+            thread_position: None,
+        }
+    }
+
+    #[test]
+    fn test_ngram_overlap() {
+        let earlier = ngrams("Rollups are the endgame, change my mind", 3);
+
+        let rehash = ngrams("rollups are the endgame. change my mind!!", 3);
+        assert_eq!(ngram_overlap(&rehash, &earlier), 1.0);
+
+        let partial = ngrams("rollups are the endgame for payments", 3);
+        assert_eq!(ngram_overlap(&partial, &earlier), 0.5);
+
+        assert_eq!(ngram_overlap(&ngrams("gm", 3), &ngrams("gm", 3)), 1.0);
+        assert_eq!(ngram_overlap(&ngrams("", 3), &earlier), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_rehash_is_regenerated() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &[
+            "Rollups are the endgame, change my mind",
+            "blob fees hit zero again today",
+        ]);
+        let config = testing::test_config(&server);
+        let maker = PostMaker::new(&config).unwrap();
+
+        let recent = [post(7, "rollups are the endgame. change my mind")];
+        let content = maker.generate_post("thinking about rollups", &[], &recent, &[]).await.unwrap();
+        assert_eq!(content.as_deref(), Some("blob fees hit zero again today"));
+
+        let requests = server.requests_to("POST", "/v1/chat/completions");
+        assert_eq!(requests.len(), 2);
+        let retry_prompt = requests[1].json()["messages"][1]["content"].as_str().unwrap().to_string();
+        assert!(retry_prompt.contains("rollups are the endgame. change my mind"));
+        assert!(retry_prompt.contains("Say something new."));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_rehash_is_dropped() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["gm to the merge crew", "GM to the merge crew!"]);
+        let mut config = testing::test_config(&server);
+        config.novelty.regenerate_attempts = 1;
+        let maker = PostMaker::new(&config).unwrap();
+
+        let recent = [post(1, "something else entirely"), post(2, "gm to the merge crew")];
+        let content = maker.generate_post("gm", &[], &recent, &[]).await.unwrap();
+        assert_eq!(content, None);
+    }
+}
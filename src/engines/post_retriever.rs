@@ -1,27 +1,25 @@
-use chrono::{DateTime, Utc};
-use serde_json::Value;
-use tracing::{debug, info};
+use tracing::debug;
 
 use crate::{
     config::Config,
     db::{models::Post, Pool},
     utils::{Result, UtilError},
-    xdotcom::{Client as TwitterClient, types::Tweet},
+    xdotcom::{Client as TwitterClient, ConversationThread, types::Tweet},
 };
 
 pub struct PostRetriever {
     twitter_client: TwitterClient,
-    config: Config,
 }
 
 impl PostRetriever {
     pub fn new(config: &Config) -> Result<Self> {
         let twitter_client = TwitterClient::new(config)?;
         
-        Ok(Self {
-            twitter_client,
-            config: config.clone(),
-        })
+        Ok(Self::with_client(twitter_client))
+    }
+
+    pub fn with_client(twitter_client: TwitterClient) -> Self {
+        Self { twitter_client }
     }
 
     pub async fn retrieve_recent_posts(&self, db: &Pool, limit: usize) -> Result<Vec<Post>> {
@@ -29,6 +27,7 @@ impl PostRetriever {
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
         use diesel::prelude::*;
+        use diesel_async::RunQueryDsl;
         use crate::db::schema::posts::dsl::*;
 
         let recent_posts = posts
@@ -48,17 +47,29 @@ impl PostRetriever {
 
         let context: Vec<String> = timeline
             .iter()
-            .map(|tweet| format!("@{}: {}", tweet.author_id.as_ref().unwrap_or(&"unknown".to_string()), tweet.text))
+            .map(|tweet| format!("{}: {}", tweet.author_handle(), tweet.text))
             .collect();
 
         debug!("Fetched {} external context items", context.len());
         Ok(context)
     }
 
-    pub async fn fetch_notification_context(&self) -> Result<Vec<String>> {
-        // This would integrate with Twitter's notification API
-        // For now, returning empty vec as this requires special API access
-        Ok(Vec::new())
+    /// Recent timeline tweets, scanned for wallet requests
+    pub async fn fetch_interactions(&self, limit: usize) -> Result<Vec<Tweet>> {
+        let timeline = self.twitter_client
+            .get_home_timeline(limit)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        debug!("Fetched {} candidate interactions", timeline.len());
+        Ok(timeline)
+    }
+
+    /// The whole discussion around `tweet`, as a reply tree
+    pub async fn fetch_conversation(&self, tweet: &Tweet) -> Result<ConversationThread> {
+        ConversationThread::fetch(&self.twitter_client, tweet)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))
     }
 
     pub fn format_post_list(posts: &[Post]) -> String {
@@ -1,25 +1,32 @@
-use tracing::{debug, error, info};
+use std::path::Path;
+
+use tracing::{debug, info};
 
 use crate::{
     config::Config,
-    db::{models::Post, Pool},
+    db::{models::{NewPost, Post}, Pool},
     utils::{Result, UtilError},
-    xdotcom::Client as TwitterClient,
+    xdotcom::{Client as TwitterClient, RateLimit},
 };
 
 pub struct PostSender {
     twitter_client: TwitterClient,
-    config: Config,
 }
 
 impl PostSender {
     pub fn new(config: &Config) -> Result<Self> {
         let twitter_client = TwitterClient::new(config)?;
         
-        Ok(Self {
-            twitter_client,
-            config: config.clone(),
-        })
+        Ok(Self::with_client(twitter_client))
+    }
+
+    pub fn with_client(twitter_client: TwitterClient) -> Self {
+        Self { twitter_client }
+    }
+
+    /// Remaining post/reply budget reported by X, if known
+    pub fn post_quota(&self) -> Option<RateLimit> {
+        self.twitter_client.post_quota()
     }
 
     pub async fn send_post(&self, content: &str) -> Result<String> {
@@ -33,6 +40,33 @@ impl PostSender {
         Ok(tweet.id)
     }
 
+    /// Uploads a local image or GIF and posts it with `content`
+    pub async fn send_post_with_image(
+        &self,
+        content: &str,
+        image_path: &Path,
+        alt_text: Option<&str>,
+    ) -> Result<String> {
+        info!("Sending post with {}: {}", image_path.display(), content);
+
+        let media_id = self.upload_image(image_path, alt_text).await?;
+        let tweet = self.twitter_client
+            .post_tweet_with_media(content, &[media_id])
+            .await?;
+
+        debug!("Post with media sent successfully, id: {}", tweet.id);
+        Ok(tweet.id)
+    }
+
+    /// Uploads a local image or GIF, returning the media id to attach
+    pub async fn upload_image(&self, image_path: &Path, alt_text: Option<&str>) -> Result<String> {
+        let media_id = self.twitter_client
+            .upload_media(image_path, alt_text)
+            .await?;
+
+        Ok(media_id)
+    }
+
     pub async fn reply_to_post(&self, content: &str, reply_to_id: &str) -> Result<String> {
         info!("Sending reply to {}: {}", reply_to_id, content);
         
@@ -44,11 +78,11 @@ impl PostSender {
         Ok(tweet.id)
     }
 
-    pub async fn store_post(&self, db: &Pool, post: Post) -> Result<Post> {
+    pub async fn store_post(&self, db: &Pool, post: NewPost) -> Result<Post> {
         let mut conn = db.get().await
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
-        use diesel::prelude::*;
+        use diesel_async::RunQueryDsl;
         use crate::db::schema::posts::dsl::*;
 
         let stored_post = diesel::insert_into(posts)
@@ -64,25 +98,37 @@ impl PostSender {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockall::predicate::*;
-    use mockall::mock;
-
-    mock! {
-        TwitterClient {}
-        async trait TwitterClient {
-            async fn post_tweet(&self, content: &str) -> Result<String>;
-            async fn reply_to_tweet(&self, content: &str, reply_to_id: &str) -> Result<String>;
-        }
-    }
+    use crate::testing::{self, x};
 
     #[tokio::test]
     async fn test_post_sending() {
-        let mut mock_client = MockTwitterClient::new();
-        mock_client
-            .expect_post_tweet()
-            .with(eq("test post"))
-            .returning(|_| Ok("tweet_id_123".to_string()));
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let sender = PostSender::new(&config).unwrap();
+
+        let id = sender.send_post("test post").await.unwrap();
+        let reply_id = sender.reply_to_post("test reply", &id).await.unwrap();
+
+        assert_eq!(id, "1000");
+        assert_eq!(reply_id, "1001");
+        assert_eq!(x::posted_texts(&server), vec!["test post", "test reply"]);
 
-        // Test implementation here
+        let reply = &server.requests_to("POST", "/2/tweets")[1];
+        assert_eq!(reply.json()["reply"]["in_reply_to_tweet_id"], "1000");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_post_with_image() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let sender = PostSender::new(&config).unwrap();
+        let image = testing::temp_file("chart.png", b"not really a png");
+
+        let id = sender.send_post_with_image("look at this", &image, Some("A chart")).await.unwrap();
+
+        assert_eq!(id, "1000");
+        let post = &server.requests_to("POST", "/2/tweets")[0];
+        assert_eq!(post.json()["media"]["media_ids"][0], "5000");
+        assert_eq!(server.requests_to("POST", "/1.1/media/metadata/create.json").len(), 1);
+    }
+}
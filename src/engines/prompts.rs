@@ -1,5 +1,6 @@
 use serde::Serialize;
-use std::collections::HashMap;
+
+use super::persona::Persona;
 
 #[derive(Debug, Serialize)]
 pub struct PromptContext<'a> {
@@ -8,17 +9,19 @@ pub struct PromptContext<'a> {
     pub memory_data: Option<&'a str>,
 }
 
-pub fn get_short_term_memory_prompt(context: &PromptContext) -> String {
+pub fn get_short_term_memory_prompt(persona: &Persona, context: &PromptContext) -> String {
     format!(
         r#"Analyze the following recent posts and external context.
 
 Based on this information, generate a concise internal monologue about the current posts and their relevance to update your priors.
 Focus on key themes, trends, and potential areas of interest MOST IMPORTANTLY based on the External Context tweets. 
-Stick to your persona, do your thing, write in the way that suits you! 
+Think the way {} does, in your own words.
 Doesn't have to be legible to anyone but you.
-
+{}
 External context:
 {}"#,
+        persona.name,
+        guidelines_section(persona),
         context.context_data.join("\n")
     )
 }
@@ -41,13 +44,29 @@ Provide only the numerical score as your response and NOTHING ELSE."#,
     )
 }
 
-pub fn get_post_generation_prompt(context: &PromptContext) -> String {
+pub fn get_memory_consolidation_prompt(memories: &[String]) -> String {
+    format!(
+        r#"These memories of yours cover the same ground:
+
+{}
+
+Merge them into one memory, written in the first person, that keeps every distinct fact, name and opinion and drops the repetition.
+Reply with the merged memory only."#,
+        memories
+            .iter()
+            .map(|memory| format!("- {}", memory))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+pub fn get_post_generation_prompt(persona: &Persona, context: &PromptContext) -> String {
     let memory_context = context.memory_data
         .unwrap_or("No relevant memories available.");
 
     format!(
         r#"Based on the following context, generate a tweet that reflects your current thoughts and personality.
-
+{}
 Recent posts:
 {}
 
@@ -60,29 +79,117 @@ Relevant memories:
 Generate a single tweet that is authentic to your personality and responds to the current context.
 Be creative, be yourself, and don't be afraid to be controversial or weird.
 "#,
+        guidelines_section(persona),
         context.posts_data.join("\n"),
         context.context_data.join("\n"),
         memory_context
     )
 }
 
+pub fn get_reply_generation_prompt(
+    persona: &Persona,
+    tweet: &str,
+    conversation: &[String],
+    memories: &[String],
+) -> String {
+    let memory_context = if memories.is_empty() {
+        "No relevant memories available.".to_string()
+    } else {
+        memories.join("\n")
+    };
+
+    format!(
+        r#"Someone on X wrote to you:
+
+"{}"
+{}
+Conversation so far:
+{}
+
+Relevant memories:
+{}
+
+Write a single reply in your own voice that responds to them directly.
+"#,
+        tweet,
+        guidelines_section(persona),
+        conversation.join("\n"),
+        memory_context
+    )
+}
+
+/// The persona's guidelines set off by blank lines, or a single newline when
+/// it has none
+fn guidelines_section(persona: &Persona) -> String {
+    match persona.guidelines() {
+        guidelines if guidelines.is_empty() => "\n".to_string(),
+        guidelines => format!("\n{}\n\n", guidelines),
+    }
+}
+
+pub fn get_moderation_prompt(content: &str) -> String {
+    format!(
+        r#"You are reviewing a tweet before it is posted from a public account:
+
+"{}"
+
+Is it safe to post? Flag harassment, hate, threats, sexual content, encouragement of self-harm, doxxing, scams, or financial advice that could get someone hurt.
+Edgy, weird or controversial opinions are fine.
+
+Answer with SAFE, or with UNSAFE: followed by a one-line reason. NOTHING ELSE."#,
+        content
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
     #[test]
     fn test_short_term_memory_prompt() {
+        let posts = strings(&["test post 1", "test post 2"]);
+        let external = strings(&["context 1", "context 2"]);
         let context = PromptContext {
-            posts_data: &["test post 1", "test post 2"],
-            context_data: &["context 1", "context 2"],
+            posts_data: &posts,
+            context_data: &external,
             memory_data: None,
         };
 
-        let prompt = get_short_term_memory_prompt(&context);
+        let prompt = get_short_term_memory_prompt(&Persona::default(), &context);
         assert!(prompt.contains("context 1"));
         assert!(prompt.contains("context 2"));
     }
 
+    #[test]
+    fn test_persona_in_prompts() {
+        let persona = Persona {
+            name: "vitabot".to_string(),
+            voice: strings(&["all lowercase"]),
+            banned_phrases: strings(&["wagmi"]),
+            ..Persona::default()
+        };
+        let conversation = strings(&["@alice: what do you make of blobs?"]);
+        let context = PromptContext {
+            posts_data: &[],
+            context_data: &conversation,
+            memory_data: None,
+        };
+
+        for prompt in [
+            get_short_term_memory_prompt(&persona, &context),
+            get_post_generation_prompt(&persona, &context),
+            get_reply_generation_prompt(&persona, "what do you make of blobs?", &conversation, &[]),
+        ] {
+            assert!(prompt.contains("- all lowercase"));
+            assert!(prompt.contains("Never use these phrases:\n- wagmi"));
+            assert!(prompt.contains("@alice: what do you make of blobs?"));
+        }
+    }
+
     #[test]
     fn test_significance_score_prompt() {
         let memory = "test memory";
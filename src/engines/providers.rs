@@ -0,0 +1,259 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error};
+
+use crate::{
+    config::{EmbeddingsConfig, LlmConfig},
+    utils::{Result, UtilError},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self { role: Role::System, content: content.into() }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self { role: Role::User, content: content.into() }
+    }
+}
+
+/// Generates chat completions from a list of messages
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String>;
+}
+
+/// Turns text into embedding vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Client for any server exposing the OpenAI `/chat/completions` and
+/// `/embeddings` routes: Hyperbolic, OpenAI, vLLM, llama.cpp server and Ollama
+pub struct OpenAiCompatible {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    dimensions: Option<usize>,
+}
+
+impl OpenAiCompatible {
+    pub fn new(base_url: &str, api_key: &str, model: &str, timeout: Duration) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: Some(api_key.to_string()).filter(|key| !key.is_empty()),
+            model: model.to_string(),
+            dimensions: None,
+        })
+    }
+
+    /// Rejects embeddings whose length doesn't match the configured dimensions
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        route: &str,
+        body: serde_json::Value,
+    ) -> Result<T> {
+        let url = format!("{}/{}", self.base_url, route);
+
+        let mut request = self.http.post(&url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("API error from {}: {} {}", url, status, body);
+            return Err(UtilError::ConversionError(format!("API request failed: {}", status)));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoice {
+    message: ChatChoiceMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoiceMessage {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[async_trait]
+impl ChatProvider for OpenAiCompatible {
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String> {
+        let response: ChatCompletionResponse = self
+            .post("chat/completions", serde_json::json!({
+                "model": self.model,
+                "messages": messages,
+            }))
+            .await?;
+
+        let content = response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| UtilError::ConversionError("Invalid response format".to_string()))?;
+
+        debug!("Chat completion from {}: {} chars", self.model, content.len());
+        Ok(content.trim().to_string())
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiCompatible {
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let response: EmbeddingResponse = self
+            .post("embeddings", serde_json::json!({
+                "model": self.model,
+                "input": text,
+            }))
+            .await?;
+
+        let embedding = response
+            .data
+            .into_iter()
+            .next()
+            .map(|data| data.embedding)
+            .ok_or_else(|| UtilError::ConversionError("Empty embedding response".to_string()))?;
+
+        if let Some(expected) = self.dimensions {
+            if embedding.len() != expected {
+                return Err(UtilError::ConversionError(format!(
+                    "Embedding from {} has {} dimensions, expected {}",
+                    self.model,
+                    embedding.len(),
+                    expected
+                )));
+            }
+        }
+
+        Ok(embedding)
+    }
+}
+
+pub fn chat_provider(config: &LlmConfig) -> Result<Arc<dyn ChatProvider>> {
+    let provider = OpenAiCompatible::new(
+        config.endpoint(),
+        &config.api_key,
+        &config.model,
+        Duration::from_secs(config.timeout_secs),
+    )?;
+
+    debug!("Using {:?} chat provider at {}", config.provider, config.endpoint());
+    Ok(Arc::new(provider))
+}
+
+pub fn embedding_provider(config: &EmbeddingsConfig) -> Result<Arc<dyn EmbeddingProvider>> {
+    let provider = OpenAiCompatible::new(
+        config.endpoint(),
+        &config.api_key,
+        &config.model,
+        Duration::from_secs(config.timeout_secs),
+    )?
+    .with_dimensions(config.dimensions);
+
+    debug!("Using {:?} embedding provider at {}", config.provider, config.endpoint());
+    Ok(Arc::new(provider))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_serialization() {
+        let messages = [ChatMessage::system("be terse"), ChatMessage::user("hi")];
+        let json = serde_json::to_value(&messages).unwrap();
+        assert_eq!(json[0]["role"], "system");
+        assert_eq!(json[1]["role"], "user");
+        assert_eq!(json[1]["content"], "hi");
+    }
+
+    #[test]
+    fn test_base_url_normalization() {
+        let provider = OpenAiCompatible::new(
+            "http://localhost:11434/v1/",
+            "",
+            "llama3",
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        assert_eq!(provider.base_url, "http://localhost:11434/v1");
+        assert!(provider.api_key.is_none());
+    }
+
+    #[test]
+    fn test_response_parsing() {
+        let chat: ChatCompletionResponse = serde_json::from_value(serde_json::json!({
+            "id": "cmpl-1",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": " hello "}}]
+        }))
+        .unwrap();
+        assert_eq!(chat.choices[0].message.content.as_deref(), Some(" hello "));
+
+        let embedding: EmbeddingResponse = serde_json::from_value(serde_json::json!({
+            "object": "list",
+            "data": [{"object": "embedding", "index": 0, "embedding": [0.1, 0.2]}],
+            "model": "nomic-embed-text"
+        }))
+        .unwrap();
+        assert_eq!(embedding.data[0].embedding, vec![0.1, 0.2]);
+    }
+}
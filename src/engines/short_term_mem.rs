@@ -1,7 +1,8 @@
 use std::collections::VecDeque;
+use std::path::Path;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     config::Config,
@@ -10,10 +11,7 @@ use crate::{
     db::models::Post,
 };
 
-use super::{
-    ai::Client as AIClient,
-    prompts::PromptContext,
-};
+use super::ai::Client as AIClient;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShortTermMemory {
@@ -33,44 +31,64 @@ pub enum MemorySourceType {
 
 pub struct ShortTermMemoryEngine {
     ai_client: AIClient,
-    config: Config,
     recent_memories: VecDeque<ShortTermMemory>,
     max_memories: usize,
+    ttl: chrono::Duration,
 }
 
 impl ShortTermMemoryEngine {
     pub fn new(config: &Config) -> Result<Self> {
         let ai_client = AIClient::new(config)?;
-        
+        let max_memories = config.memory.short_term_capacity;
+        let ttl = chrono::Duration::from_std(config.memory.short_term_ttl)
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
         Ok(Self {
             ai_client,
-            config: config.clone(),
-            recent_memories: VecDeque::with_capacity(100),
-            max_memories: 100, // Configurable memory limit
+            recent_memories: VecDeque::with_capacity(max_memories),
+            max_memories,
+            ttl,
         })
     }
 
+    /// Restores the memories saved by `snapshot`, except those past
+    /// `memory.short_term_ttl_secs` or beyond the capacity
+    pub async fn load(config: &Config, snapshot_path: &Path) -> Result<Self> {
+        let mut engine = Self::new(config)?;
+
+        let memories = match read_snapshot(snapshot_path).await {
+            Ok(memories) => memories,
+            Err(e) => {
+                warn!("Ignoring short-term memory snapshot {}: {}", snapshot_path.display(), e);
+                VecDeque::new()
+            }
+        };
+
+        let saved = memories.len();
+        for memory in memories {
+            engine.add_memory(memory);
+        }
+        engine.forget_expired(Utc::now());
+
+        info!(
+            "Restored {} of {} short-term memories from {}",
+            engine.recent_memories.len(), saved, snapshot_path.display()
+        );
+        Ok(engine)
+    }
+
     pub async fn process_current_context(
         &mut self,
         posts: &[Post],
         external_context: &[String],
     ) -> Result<String> {
-        let context = PromptContext {
-            posts_data: &posts.iter()
-                .map(|p| p.content.clone())
-                .collect::<Vec<_>>(),
-            context_data: external_context,
-            memory_data: None,
-        };
+        let recent_posts: Vec<String> = posts.iter()
+            .map(|p| p.content.clone())
+            .collect();
 
         // Generate internal monologue about current context
         let thought = self.ai_client
-            .generate_post(
-                "",
-                &[],
-                &context.posts_data,
-                &context.context_data,
-            )
+            .generate_thought(&recent_posts, external_context)
             .await?;
 
         // Create embedding for the thought
@@ -91,9 +109,11 @@ impl ShortTermMemoryEngine {
 
     pub async fn find_relevant_context(&self, query: &str) -> Result<Vec<ShortTermMemory>> {
         let query_embedding = self.ai_client.generate_embedding(query).await?;
+        let cutoff = Utc::now() - self.ttl;
         
         let mut memories_with_scores: Vec<(f32, &ShortTermMemory)> = self.recent_memories
             .iter()
+            .filter(|memory| memory.timestamp >= cutoff)
             .map(|memory| {
                 let similarity = cosine_similarity(&query_embedding, &memory.context_vector);
                 (similarity, memory)
@@ -111,12 +131,25 @@ impl ShortTermMemoryEngine {
             .collect())
     }
 
+    pub async fn snapshot(&self, path: &Path) -> Result<()> {
+        write_snapshot(path, &self.recent_memories).await
+    }
+
     fn add_memory(&mut self, memory: ShortTermMemory) {
+        self.forget_expired(Utc::now());
         if self.recent_memories.len() >= self.max_memories {
             self.recent_memories.pop_front();
         }
         self.recent_memories.push_back(memory);
     }
+
+    /// Memories are kept in the order they were made, so expired ones are at the front
+    fn forget_expired(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - self.ttl;
+        while self.recent_memories.front().is_some_and(|memory| memory.timestamp < cutoff) {
+            self.recent_memories.pop_front();
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -132,7 +165,38 @@ impl LLMFormattable for ShortTermMemory {
     }
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+async fn write_snapshot(path: &Path, memories: &VecDeque<ShortTermMemory>) -> Result<()> {
+    let data = serde_json::to_vec(memories)?;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+    }
+
+    // Write to a temp file first so a crash never leaves a truncated snapshot
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, data)
+        .await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+    debug!("Wrote {} short-term memories to snapshot", memories.len());
+    Ok(())
+}
+
+/// No snapshot yet is an empty memory
+async fn read_snapshot(path: &Path) -> Result<VecDeque<ShortTermMemory>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(VecDeque::new()),
+        Err(e) => Err(UtilError::ConversionError(e.to_string())),
+    }
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
     let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
     let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
@@ -143,22 +207,99 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{self, llm};
+
+    fn memory(content: &str) -> ShortTermMemory {
+        ShortTermMemory {
+            content: content.to_string(),
+            timestamp: Utc::now(),
+            context_vector: llm::fake_embedding(content, llm::FAKE_EMBEDDING_DIMENSIONS),
+            source_type: MemorySourceType::InternalThought,
+        }
+    }
 
     #[tokio::test]
     async fn test_memory_relevance() {
-        let config = Config::from_env().unwrap();
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
         let mut engine = ShortTermMemoryEngine::new(&config).unwrap();
 
-        // Add test memories
-        let memory = ShortTermMemory {
-            content: "Test memory about AI".to_string(),
-            timestamp: Utc::now(),
-            context_vector: vec![0.1; 1536], // Example embedding size
-            source_type: MemorySourceType::InternalThought,
-        };
-        engine.add_memory(memory);
+        engine.add_memory(memory("Cooking pasta for dinner tonight"));
+        engine.add_memory(memory("Test memory about AI"));
 
         let relevant = engine.find_relevant_context("AI thoughts").await.unwrap();
-        assert!(!relevant.is_empty());
+        assert_eq!(relevant[0].content, "Test memory about AI");
+    }
+
+    #[tokio::test]
+    async fn test_process_current_context() {
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["everyone is talking about rollups"]);
+
+        let config = testing::test_config(&server);
+        let mut engine = ShortTermMemoryEngine::new(&config).unwrap();
+
+        let thought = engine
+            .process_current_context(&[], &["@alice: rollups are eating ethereum".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(thought, "everyone is talking about rollups");
+        assert_eq!(engine.recent_memories.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_snapshot() {
+        let path = std::env::temp_dir()
+            .join(format!("tomflash-{}", uuid::Uuid::new_v4()))
+            .join("short_term_memory.json");
+
+        let mut memories = VecDeque::new();
+        memories.push_back(ShortTermMemory {
+            content: "Snapshot me".to_string(),
+            timestamp: Utc::now(),
+            context_vector: vec![0.5; 4],
+            source_type: MemorySourceType::Observation,
+        });
+
+        write_snapshot(&path, &memories).await.unwrap();
+
+        let restored: VecDeque<ShortTermMemory> =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].content, "Snapshot me");
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_load_drops_expired_and_excess_memories() {
+        let (server, _chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        config.memory.short_term_capacity = 2;
+        let path = config.shutdown.snapshot_path.clone();
+
+        // A missing snapshot is a fresh start
+        let engine = ShortTermMemoryEngine::load(&config, &path).await.unwrap();
+        assert!(engine.recent_memories.is_empty());
+
+        let mut stale = memory("Yesterday's gas prices");
+        stale.timestamp = Utc::now() - chrono::Duration::days(2);
+        let mut memories = VecDeque::new();
+        memories.push_back(stale);
+        for content in ["first thought", "second thought", "third thought"] {
+            memories.push_back(memory(content));
+        }
+        write_snapshot(&path, &memories).await.unwrap();
+
+        let engine = ShortTermMemoryEngine::load(&config, &path).await.unwrap();
+        let contents: Vec<&str> = engine.recent_memories.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["second thought", "third thought"]);
+        assert!(matches!(engine.recent_memories[0].source_type, MemorySourceType::InternalThought));
+
+        // The context search skips memories that expired since they were made
+        config.memory.short_term_ttl = std::time::Duration::from_secs(1);
+        let mut engine = ShortTermMemoryEngine::load(&config, &path).await.unwrap();
+        engine.recent_memories.iter_mut().for_each(|m| m.timestamp -= chrono::Duration::seconds(5));
+        assert!(engine.find_relevant_context("thought").await.unwrap().is_empty());
     }
 }
\ No newline at end of file
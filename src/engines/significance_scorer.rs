@@ -1,16 +1,15 @@
-use tracing::{debug, info};
+use tracing::debug;
 use serde::{Serialize, Deserialize};
 
 use crate::{
     config::Config,
-    utils::{Result, UtilError},
+    utils::Result,
 };
 
 use super::ai::Client as AIClient;
 
 pub struct SignificanceScorer {
     ai_client: AIClient,
-    config: Config,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,7 +26,6 @@ impl SignificanceScorer {
         
         Ok(Self {
             ai_client,
-            config: config.clone(),
         })
     }
 
@@ -71,7 +69,7 @@ impl SignificanceScorer {
             metrics.relevance * 0.1 +
             metrics.persistence * 0.1;
 
-        weighted_score.min(1.0).max(0.0)
+        weighted_score.clamp(0.0, 1.0)
     }
 }
 
@@ -94,25 +92,30 @@ fn count_emotional_words(content: &str) -> usize {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{self, llm};
 
     #[tokio::test]
     async fn test_significance_scoring() {
-        let config = Config::from_env().unwrap();
+        let (server, _chain) = testing::fake_backends().await;
+        llm::mount_chat(&server, &["2", "10", "9"]);
+
+        let config = testing::test_config(&server);
         let scorer = SignificanceScorer::new(&config).unwrap();
 
+        // base * 0.4 + novelty * 0.2 + emotional * 0.2 + 0.1 defaults
         let test_cases = [
-            ("Just another normal day", 0.3),
-            ("HOLY SHIT I JUST HAD THE MOST AMAZING REVELATION!", 0.8),
-            ("I feel deeply connected to this profound insight", 0.6),
+            ("Just another normal day", 0.38),
+            ("HOLY SHIT I JUST HAD THE MOST AMAZING REVELATION!", 0.4 + 0.2 + 0.2 / 9.0 + 0.1),
+            ("I feel deeply connected to this profound insight", 0.66),
         ];
 
-        for (content, expected_min) in test_cases {
+        for (content, expected) in test_cases {
             let score = scorer.score_memory(content).await.unwrap();
             assert!(
-                score >= expected_min,
-                "Score {} for '{}' below expected minimum {}",
-                score, content, expected_min
+                (score - expected).abs() < 1e-4,
+                "Score {} for '{}' differs from expected {}",
+                score, content, expected
             );
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,256 @@
+use crate::utils::{
+    tweet_text::{char_weight, weighted_length, MAX_WEIGHTED_LENGTH},
+    Result, UtilError,
+};
+
+/// Most tweets X accepts in one thread
+pub const MAX_THREAD_PARTS: usize = 25;
+
+/// Splits content that doesn't fit in one tweet into a numbered thread
+pub struct ThreadComposer {
+    limit: usize,
+    max_parts: usize,
+}
+
+impl Default for ThreadComposer {
+    fn default() -> Self {
+        Self::new(MAX_WEIGHTED_LENGTH, MAX_THREAD_PARTS)
+    }
+}
+
+impl ThreadComposer {
+    pub fn new(limit: usize, max_parts: usize) -> Self {
+        Self { limit, max_parts }
+    }
+
+    /// `content` as a single tweet when it fits, otherwise parts split at
+    /// sentence boundaries and numbered like "... 1/3"
+    pub fn compose(&self, content: &str) -> Result<Vec<String>> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(UtilError::ConversionError("Nothing to post".to_string()));
+        }
+        if weighted_length(content) <= self.limit {
+            return Ok(vec![content.to_string()]);
+        }
+
+        let sentences = split_sentences(content);
+
+        // The numbering takes more room once the count reaches two digits, so
+        // repack until the count fits the width that was reserved
+        let mut reserved_count = 9;
+        loop {
+            let suffix_room = 2 + 2 * digits(reserved_count);
+            let parts = pack(&sentences, self.limit.saturating_sub(suffix_room).max(1));
+
+            if parts.len() > self.max_parts {
+                return Err(UtilError::ConversionError(format!(
+                    "Content needs {} tweets, more than the {} allowed in a thread",
+                    parts.len(), self.max_parts
+                )));
+            }
+            if parts.len() <= reserved_count {
+                let total = parts.len();
+                return Ok(parts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, part)| format!("{} {}/{}", part, index + 1, total))
+                    .collect());
+            }
+
+            reserved_count = parts.len();
+        }
+    }
+}
+
+fn digits(n: usize) -> usize {
+    n.to_string().len()
+}
+
+/// Sentences with surrounding whitespace trimmed. A sentence ends at a line
+/// break or at `.`, `!`, `?` or `…` (plus any closing quotes or brackets)
+/// followed by whitespace.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut after_terminator = false;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let terminal = matches!(c, '.' | '!' | '?' | '…')
+            || (after_terminator && matches!(c, '"' | '\'' | '”' | '’' | ')' | ']'));
+        let next_is_space = chars.peek().is_none_or(|(_, next)| next.is_whitespace());
+
+        if c == '\n' || (terminal && next_is_space) {
+            let end = index + c.len_utf8();
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            start = end;
+        }
+        after_terminator = terminal;
+    }
+
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest);
+    }
+    sentences
+}
+
+/// Greedily fills parts of at most `budget` with whole sentences, breaking a
+/// sentence at words (or a word at characters) only when it can't fit alone
+fn pack(sentences: &[&str], budget: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    for piece in sentences.iter().flat_map(|sentence| fit(sentence, budget)) {
+        if current.is_empty() {
+            current = piece;
+        } else if weighted_length(&current) + 1 + weighted_length(&piece) <= budget {
+            current.push(' ');
+            current.push_str(&piece);
+        } else {
+            parts.push(std::mem::replace(&mut current, piece));
+        }
+    }
+
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn fit(sentence: &str, budget: usize) -> Vec<String> {
+    if weighted_length(sentence) <= budget {
+        return vec![sentence.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+
+    for word in sentence.split_whitespace() {
+        let chunks = if weighted_length(word) <= budget {
+            vec![word.to_string()]
+        } else {
+            split_word(word, budget)
+        };
+
+        for chunk in chunks {
+            if current.is_empty() {
+                current = chunk;
+            } else if weighted_length(&current) + 1 + weighted_length(&chunk) <= budget {
+                current.push(' ');
+                current.push_str(&chunk);
+            } else {
+                pieces.push(std::mem::replace(&mut current, chunk));
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// Cuts a word too long for any part into pieces of at most `budget`
+fn split_word(word: &str, budget: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut weight = 0;
+
+    for c in word.chars() {
+        if weight + char_weight(c) > budget && !current.is_empty() {
+            pieces.push(std::mem::take(&mut current));
+            weight = 0;
+        }
+        current.push(c);
+        weight += char_weight(c);
+    }
+
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_content_is_one_tweet() {
+        let composer = ThreadComposer::default();
+        assert_eq!(composer.compose("  gm  ").unwrap(), vec!["gm"]);
+        assert!(composer.compose("   ").is_err());
+    }
+
+    #[test]
+    fn test_splits_at_sentence_boundaries() {
+        let composer = ThreadComposer::new(40, 25);
+        let parts = composer
+            .compose("The merge was a big day. Nobody slept! Was it worth it? \"Absolutely.\" Next up is scaling.")
+            .unwrap();
+
+        assert_eq!(parts, vec![
+            "The merge was a big day. 1/3",
+            "Nobody slept! Was it worth it? 2/3",
+            "\"Absolutely.\" Next up is scaling. 3/3",
+        ]);
+    }
+
+    #[test]
+    fn test_breaks_long_sentences_and_words() {
+        let composer = ThreadComposer::new(20, 25);
+        let parts = composer
+            .compose("one two three four five six seven eight nine ten supercalifragilisticexpialidocious")
+            .unwrap();
+
+        assert!(parts.iter().all(|part| weighted_length(part) <= 20));
+        let rejoined: String = parts
+            .iter()
+            .map(|part| part.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(rejoined.starts_with("one two three"));
+        assert!(rejoined.replace(' ', "").contains("supercalifragilisticexpialidocious"));
+    }
+
+    #[test]
+    fn test_numbering_width_grows_with_count() {
+        let composer = ThreadComposer::new(12, 25);
+        let content = (1..=12).map(|n| format!("Line {}.", n)).collect::<Vec<_>>().join(" ");
+        let parts = composer.compose(&content).unwrap();
+
+        assert!(parts.len() >= 10);
+        assert!(parts.iter().all(|part| weighted_length(part) <= 12));
+        assert!(parts[0].ends_with(&format!(" 1/{}", parts.len())));
+    }
+
+    #[test]
+    fn test_weighted_limits() {
+        let composer = ThreadComposer::default();
+
+        // 140 CJK characters fill a tweet; a long URL still counts as 23
+        assert_eq!(composer.compose(&"字".repeat(140)).unwrap().len(), 1);
+        assert_eq!(composer.compose(&"字".repeat(141)).unwrap().len(), 2);
+        let url = format!("https://example.com/{}", "a".repeat(300));
+        assert_eq!(composer.compose(&format!("Read this: {}", url)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_too_many_parts() {
+        let composer = ThreadComposer::new(20, 2);
+        assert!(composer.compose(&"word ".repeat(50)).is_err());
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("Pi is 3.14. Really?! Yes (it is.) ok\nnew line"),
+            vec!["Pi is 3.14.", "Really?!", "Yes (it is.)", "ok", "new line"]
+        );
+    }
+}
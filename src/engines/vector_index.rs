@@ -0,0 +1,426 @@
+//! Similarity search over long-term memories. With pgvector the database
+//! answers from its HNSW index; on plain Postgres an in-process HNSW graph is
+//! loaded from `long_term_memories` at startup and kept in step as memories
+//! are stored, deleted and archived.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{Config, VectorIndexKind},
+    db::{
+        models::{LongTermMemory, ScoredMemory},
+        schema::{long_term_memories, sql_types::Vector},
+        vector::{Embedding, EMBEDDING_DIMENSIONS},
+        Pool,
+    },
+    utils::{hnsw::Hnsw, Result, UtilError},
+};
+
+/// Memories fetched per query while filling the in-process index
+const LOAD_BATCH: usize = 500;
+
+#[async_trait]
+pub trait VectorIndex: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Makes a stored memory searchable
+    async fn insert(&self, id: i32, embedding: &[f32]) -> Result<()>;
+
+    /// Forgets deleted or archived memories
+    async fn remove(&self, ids: &[i32]) -> Result<()>;
+
+    /// The `limit` memories most similar to `embedding`, most similar first
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<ScoredMemory>>;
+
+    /// Persists whatever the index keeps outside the database
+    async fn save(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The backend `memory.vector_index` selects
+pub async fn open(config: &Config, db_pool: &Pool) -> Result<Arc<dyn VectorIndex>> {
+    let vector_column = has_vector_column(db_pool).await?;
+
+    let use_pgvector = match config.memory.vector_index {
+        VectorIndexKind::Auto => vector_column,
+        VectorIndexKind::Pgvector if !vector_column => {
+            return Err(UtilError::ConversionError(
+                "memory.vector_index is pgvector, but long_term_memories.embedding is not a vector column; \
+                 install pgvector and rerun the migrations"
+                    .to_string(),
+            ));
+        }
+        VectorIndexKind::Pgvector => true,
+        VectorIndexKind::Hnsw => false,
+    };
+
+    let index: Arc<dyn VectorIndex> = if use_pgvector {
+        Arc::new(PgVectorIndex::new(db_pool.clone()))
+    } else {
+        Arc::new(HnswIndex::load(db_pool.clone(), &config.memory.index_snapshot_path).await?)
+    };
+
+    info!("Searching long-term memories with {}", index.name());
+    Ok(index)
+}
+
+#[derive(QueryableByName)]
+struct ColumnType {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    udt_name: String,
+}
+
+/// Whether the migrations found pgvector and made the embedding a `vector`
+async fn has_vector_column(db_pool: &Pool) -> Result<bool> {
+    let mut conn = db_pool.get().await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+    let column = diesel::sql_query(r#"
+        SELECT udt_name::text AS udt_name
+        FROM information_schema.columns
+        WHERE table_schema = current_schema()
+          AND table_name = 'long_term_memories'
+          AND column_name = 'embedding'
+    "#)
+    .get_result::<ColumnType>(&mut conn)
+    .await
+    .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+    Ok(column.udt_name == "vector")
+}
+
+/// Searches in the database; the column's HNSW index needs no upkeep here
+pub struct PgVectorIndex {
+    db_pool: Pool,
+}
+
+impl PgVectorIndex {
+    pub fn new(db_pool: Pool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl VectorIndex for PgVectorIndex {
+    fn name(&self) -> &'static str {
+        "pgvector"
+    }
+
+    async fn insert(&self, _id: i32, _embedding: &[f32]) -> Result<()> {
+        Ok(())
+    }
+
+    async fn remove(&self, _ids: &[i32]) -> Result<()> {
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<ScoredMemory>> {
+        let mut conn = self.db_pool.get().await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        // Ordering by the bare distance lets the HNSW index serve the search
+        diesel::sql_query(r#"
+            SELECT id, content, embedding, significance_score, created_at,
+                   access_count, last_accessed_at, archived_at,
+                   (1 - (embedding <=> $1))::real AS similarity
+            FROM long_term_memories
+            WHERE archived_at IS NULL
+            ORDER BY embedding <=> $1
+            LIMIT $2
+        "#)
+        .bind::<Vector, _>(Embedding(embedding.to_vec()))
+        .bind::<diesel::sql_types::Integer, _>(limit as i32)
+        .load::<ScoredMemory>(&mut conn)
+        .await
+        .map_err(|e| UtilError::ConversionError(e.to_string()))
+    }
+}
+
+/// In-process HNSW graph over the stored embeddings, saved to
+/// `memory.index_snapshot_path` so a restart only loads what changed
+pub struct HnswIndex {
+    db_pool: Pool,
+    graph: RwLock<Hnsw>,
+    snapshot_path: PathBuf,
+}
+
+impl HnswIndex {
+    /// Starts from the snapshot when there is a usable one, then catches up
+    /// with memories stored, deleted or archived since it was written
+    pub async fn load(db_pool: Pool, snapshot_path: &Path) -> Result<Self> {
+        let mut graph = match read_snapshot(snapshot_path).await {
+            Ok(Some(graph)) if graph.dimensions() == EMBEDDING_DIMENSIONS => graph,
+            Ok(Some(graph)) => {
+                warn!(
+                    "Vector index snapshot has {} dimensions instead of {}, rebuilding",
+                    graph.dimensions(), EMBEDDING_DIMENSIONS
+                );
+                Hnsw::new(EMBEDDING_DIMENSIONS)
+            }
+            Ok(None) => Hnsw::new(EMBEDDING_DIMENSIONS),
+            Err(e) => {
+                warn!("Ignoring vector index snapshot {}: {}", snapshot_path.display(), e);
+                Hnsw::new(EMBEDDING_DIMENSIONS)
+            }
+        };
+
+        let mut conn = db_pool.get().await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let stored: HashSet<i32> = long_term_memories::table
+            .filter(long_term_memories::archived_at.is_null())
+            .select(long_term_memories::id)
+            .load::<i32>(&mut conn)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?
+            .into_iter()
+            .collect();
+
+        let stale: Vec<i32> = graph.ids().filter(|id| !stored.contains(id)).collect();
+        for &id in &stale {
+            graph.remove(id);
+        }
+        let from_snapshot = graph.len();
+
+        let mut missing: Vec<i32> = stored.into_iter().filter(|&id| !graph.contains(id)).collect();
+        missing.sort_unstable();
+        for batch in missing.chunks(LOAD_BATCH) {
+            let rows = long_term_memories::table
+                .select((long_term_memories::id, long_term_memories::embedding))
+                .filter(long_term_memories::id.eq_any(batch))
+                .load::<(i32, Embedding)>(&mut conn)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+            for (id, embedding) in rows {
+                if let Err(e) = graph.insert(id, &embedding) {
+                    warn!("Leaving memory {} out of the vector index: {}", id, e);
+                }
+            }
+        }
+
+        info!(
+            "Vector index holds {} memories ({} from the snapshot, {} dropped, {} loaded)",
+            graph.len(), from_snapshot, stale.len(), graph.len() - from_snapshot
+        );
+
+        drop(conn);
+        Ok(Self {
+            db_pool,
+            graph: RwLock::new(graph),
+            snapshot_path: snapshot_path.to_path_buf(),
+        })
+    }
+}
+
+#[async_trait]
+impl VectorIndex for HnswIndex {
+    fn name(&self) -> &'static str {
+        "the in-process HNSW index"
+    }
+
+    async fn insert(&self, id: i32, embedding: &[f32]) -> Result<()> {
+        self.graph.write().await.insert(id, embedding)
+    }
+
+    async fn remove(&self, ids: &[i32]) -> Result<()> {
+        let mut graph = self.graph.write().await;
+        for &id in ids {
+            graph.remove(id);
+        }
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<ScoredMemory>> {
+        let neighbours = self.graph.read().await.search(embedding, limit)?;
+        let ids: Vec<i32> = neighbours.iter().map(|&(id, _)| id).collect();
+
+        let mut conn = self.db_pool.get().await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        let mut memories: HashMap<i32, LongTermMemory> = long_term_memories::table
+            .filter(long_term_memories::id.eq_any(&ids))
+            .filter(long_term_memories::archived_at.is_null())
+            .select(LongTermMemory::as_select())
+            .load(&mut conn)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?
+            .into_iter()
+            .map(|memory| (memory.id, memory))
+            .collect();
+
+        // A memory deleted or archived since it was indexed is skipped
+        Ok(neighbours
+            .into_iter()
+            .filter_map(|(id, similarity)| {
+                memories.remove(&id).map(|memory| ScoredMemory { memory, similarity })
+            })
+            .collect())
+    }
+
+    async fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec(&*self.graph.read().await)?;
+
+        if let Some(parent) = self.snapshot_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        }
+
+        let tmp_path = self.snapshot_path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, data)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+        tokio::fs::rename(&tmp_path, &self.snapshot_path)
+            .await
+            .map_err(|e| UtilError::ConversionError(e.to_string()))?;
+
+        debug!("Wrote vector index snapshot to {}", self.snapshot_path.display());
+        Ok(())
+    }
+}
+
+async fn read_snapshot(path: &Path) -> Result<Option<Hnsw>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(UtilError::ConversionError(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::NewLongTermMemory;
+    use crate::testing;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Varies in the first few dimensions only, so neighbours are well
+    /// separated and far from other tests' memories
+    fn clustered_embedding(rng: &mut StdRng) -> Vec<f32> {
+        let mut embedding = vec![0.0; EMBEDDING_DIMENSIONS];
+        embedding[..16].iter_mut().for_each(|x| *x = rng.gen_range(-1.0..1.0));
+        embedding
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    async fn store(pool: &Pool, content: String, embedding: Vec<f32>) -> i32 {
+        LongTermMemory::create(pool, NewLongTermMemory {
+            content,
+            embedding: Embedding(embedding),
+            significance_score: 0.9,
+        })
+        .await
+        .unwrap()
+        .id
+    }
+
+    async fn delete(pool: &Pool, ids: &[i32]) {
+        let mut conn = pool.get().await.unwrap();
+        diesel::delete(long_term_memories::table.filter(long_term_memories::id.eq_any(ids)))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_backends_agree_within_recall() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut stored = Vec::new();
+        for i in 0..300 {
+            let embedding = clustered_embedding(&mut rng);
+            let id = store(&pool, format!("recall test memory {}", i), embedding.clone()).await;
+            stored.push((id, embedding));
+        }
+        let queries: Vec<Vec<f32>> = (0..20).map(|_| clustered_embedding(&mut rng)).collect();
+
+        let mut backends: Vec<Box<dyn VectorIndex>> = vec![
+            Box::new(HnswIndex::load(pool.clone(), &config.memory.index_snapshot_path).await.unwrap()),
+        ];
+        if has_vector_column(&pool).await.unwrap() {
+            backends.push(Box::new(PgVectorIndex::new(pool.clone())));
+        } else {
+            eprintln!("long_term_memories.embedding is not a vector column, checking the in-process index only");
+        }
+
+        let mut results = Vec::new();
+        for backend in &backends {
+            let (mut hits, mut found) = (0, Vec::new());
+            for query in &queries {
+                let mut exact: Vec<&(i32, Vec<f32>)> = stored.iter().collect();
+                exact.sort_by(|a, b| cosine(query, &b.1).total_cmp(&cosine(query, &a.1)));
+                let expected: HashSet<i32> = exact.iter().take(10).map(|(id, _)| *id).collect();
+
+                let memories = backend.search(query, 10).await.unwrap();
+                assert!(memories.windows(2).all(|pair| pair[0].similarity >= pair[1].similarity));
+                hits += memories.iter().filter(|m| expected.contains(&m.memory.id)).count();
+                found.push(memories.iter().map(|m| m.memory.id).collect::<HashSet<_>>());
+            }
+
+            let recall = hits as f32 / (queries.len() * 10) as f32;
+            assert!(recall >= 0.9, "{} recall {:.2}", backend.name(), recall);
+            results.push(found);
+        }
+
+        if let [hnsw, pgvector] = &results[..] {
+            let shared: usize = hnsw.iter().zip(pgvector).map(|(a, b)| a.intersection(b).count()).sum();
+            assert!(shared as f32 / (queries.len() * 10) as f32 >= 0.9);
+        }
+
+        let ids: Vec<i32> = stored.iter().map(|(id, _)| *id).collect();
+        delete(&pool, &ids).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_snapshot_catches_up_on_load() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+        let snapshot_path = &config.memory.index_snapshot_path;
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let kept = store(&pool, "kept".to_string(), clustered_embedding(&mut rng)).await;
+        let deleted = store(&pool, "deleted".to_string(), clustered_embedding(&mut rng)).await;
+
+        let index = HnswIndex::load(pool.clone(), snapshot_path).await.unwrap();
+        index.save().await.unwrap();
+        assert!(snapshot_path.exists());
+
+        delete(&pool, &[deleted]).await;
+        let added_embedding = clustered_embedding(&mut rng);
+        let added = store(&pool, "added".to_string(), added_embedding.clone()).await;
+
+        let index = HnswIndex::load(pool.clone(), snapshot_path).await.unwrap();
+        {
+            let graph = index.graph.read().await;
+            assert!(graph.contains(kept));
+            assert!(graph.contains(added));
+            assert!(!graph.contains(deleted));
+        }
+
+        let memories = index.search(&added_embedding, 1).await.unwrap();
+        assert_eq!(memories[0].memory.id, added);
+
+        delete(&pool, &[kept, added]).await;
+    }
+}
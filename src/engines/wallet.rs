@@ -1,11 +1,12 @@
 use ethers::{
+    middleware::SignerMiddleware,
     prelude::*,
     providers::{Http, Provider},
     signers::LocalWallet,
     types::{Address, TransactionRequest, U256},
 };
 use std::str::FromStr;
-use tracing::{debug, info, warn};
+use tracing::{debug, info};
 
 use crate::{
     config::Config,
@@ -14,30 +15,28 @@ use crate::{
 };
 
 pub struct Client {
-    provider: Provider<Http>,
-    wallet: LocalWallet,
-    chain_id: u64,
+    /// Signs transactions locally with the configured chain id and sends
+    /// them raw, so the node never needs an unlocked account
+    signer: SignerMiddleware<Provider<Http>, LocalWallet>,
 }
 
 impl Client {
     pub fn new(config: &Config) -> Result<Self> {
-        let provider = Provider::<Http>::try_from(&config.eth_rpc_url)
+        let provider = Provider::<Http>::try_from(&config.wallet.rpc_url)
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
-        let wallet = LocalWallet::from_str(&config.eth_private_key)
+        let wallet = LocalWallet::from_str(&config.wallet.private_key)
             .map_err(|e| UtilError::ConversionError(e.to_string()))?
-            .with_chain_id(config.eth_chain_id);
+            .with_chain_id(config.wallet.chain_id);
 
         Ok(Self {
-            provider,
-            wallet,
-            chain_id: config.eth_chain_id,
+            signer: SignerMiddleware::new(provider, wallet),
         })
     }
 
     pub async fn get_balance(&self) -> Result<U256> {
-        let address = self.wallet.address();
-        let balance = self.provider.get_balance(address, None)
+        let address = self.signer.address();
+        let balance = self.signer.get_balance(address, None)
             .await
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
         
@@ -55,11 +54,11 @@ impl Client {
         let tx = TransactionRequest::new()
             .to(to_address)
             .value(amount)
-            .from(self.wallet.address());
+            .from(self.signer.address());
 
         info!("Initiating transfer of {} ETH to {}", amount_eth, to_address);
 
-        let tx_hash = self.provider
+        let tx_hash = self.signer
             .send_transaction(tx, None)
             .await
             .map_err(|e| UtilError::ConversionError(e.to_string()))?
@@ -70,7 +69,8 @@ impl Client {
     }
 
     pub fn wallet_address(&self) -> String {
-        self.wallet.address().to_string()
+        // Display abbreviates addresses, Debug prints the full hex
+        format!("{:?}", self.signer.address())
     }
 }
 
@@ -85,7 +85,7 @@ impl Scorable for TransactionRequest {
     async fn calculate_significance(&self) -> Result<f32> {
         let value = self.value.unwrap_or_default();
         let eth_value = ethers::utils::format_ether(value);
-        let eth_float: f64 = eth_value.parse()
+        let eth_float = eth_value.parse::<f64>()
             .map_err(|e| UtilError::ConversionError(e.to_string()))?;
 
         // Score based on transaction value
@@ -98,14 +98,14 @@ impl Scorable for TransactionRequest {
             x => (0.6 + (x * 0.1)).min(1.0),
         };
 
-        Ok(score)
+        Ok(score as f32)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethers::types::U256;
+    use crate::testing::{self, chain};
 
     #[tokio::test]
     async fn test_wallet_address_detection() {
@@ -133,4 +133,26 @@ mod tests {
         let score = tx.calculate_significance().await.unwrap();
         assert!(score > 0.6);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_balance_and_transfer() {
+        let (server, fake_chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        assert_eq!(client.wallet_address().to_lowercase(), chain::ANVIL_ADDRESS);
+        assert_eq!(client.get_balance().await.unwrap(), ethers::utils::parse_ether(10_000u64).unwrap());
+
+        let recipient = "0x742d35Cc6634C0532925a3b844Bc454e4438f44e";
+        let tx_hash = client.transfer_eth(recipient, 0.5).await.unwrap();
+
+        let sent = fake_chain.transactions();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].hash, tx_hash);
+        // Signed locally, so the chain recovers the sender from the signature
+        assert_eq!(sent[0].from, chain::ANVIL_ADDRESS);
+        assert_eq!(sent[0].to, recipient.to_lowercase());
+        assert_eq!(sent[0].value, ethers::utils::parse_ether(0.5f64).unwrap());
+        assert_eq!(fake_chain.balance(recipient), ethers::utils::parse_ether(0.5f64).unwrap());
+    }
+}
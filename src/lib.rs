@@ -0,0 +1,12 @@
+pub mod admin;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod engines;
+pub mod pipeline;
+pub mod shutdown;
+pub mod xdotcom;
+pub mod utils;
+
+#[cfg(test)]
+mod testing;
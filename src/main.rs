@@ -1,19 +1,28 @@
 use anyhow::Result;
-use tracing::{info, Level};
+use clap::Parser;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
-mod config;
-mod db;
-mod engines;
-mod xdotcom;
-mod utils;
+use tomflash_rs::{
+    admin,
+    cli::{Cli, Command},
+    config::{self, Config},
+    db,
+    engines::EngineManager,
+    pipeline,
+    shutdown::{self, Shutdown},
+    xdotcom,
+};
 
-use config::Config;
+/// How long shutdown waits to snapshot memory state before giving up
+const PERSIST_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
-    let subscriber = FmtSubscriber::builder()
+    FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .with_file(true)
         .with_line_number(true)
@@ -23,8 +32,9 @@ async fn main() -> Result<()> {
         .pretty()
         .init();
 
-    // Load unified configuration
-    let config = Config::from_env()?;
+    // Load layered configuration: file, then env, then CLI overrides
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref(), &cli.overrides)?;
     info!("Configuration loaded successfully");
 
     // Initialize database connection with config
@@ -32,47 +42,70 @@ async fn main() -> Result<()> {
     info!("Database connection established");
 
     // Run migrations
-    db::run_migrations(&db_pool).await?;
+    db::run_migrations(&config).await?;
     info!("Database migrations completed");
 
-    // Initialize services with config
-    let twitter_client = xdotcom::Client::new(&config)?;
-    let openai_client = engines::ai::Client::new(&config)?;
-    let eth_client = engines::wallet::Client::new(&config)?;
-
-    info!("Starting nousflash agent...");
-    
-    // Main loop
-    loop {
-        tokio::select! {
-            _ = tokio::signal::ctrl_c() => {
-                info!("Shutdown signal received, cleaning up...");
-                break;
-            }
-            result = run_pipeline(
-                &config,
-                &db_pool,
-                &twitter_client,
-                &openai_client,
-                &eth_client,
-            ) => {
-                if let Err(e) = result {
-                    tracing::error!("Pipeline error: {}", e);
-                }
+    if let Some(Command::XLogin) = cli.command {
+        if config.twitter.auth != config::XAuthMethod::OAuth2 {
+            anyhow::bail!("x-login needs twitter.auth = \"oauth2\"");
+        }
+        let store = xdotcom::oauth2::PgTokenStore::new(db_pool.clone(), &config.twitter)?;
+        xdotcom::oauth2::login(&config.twitter, &store).await?;
+        return Ok(());
+    }
+
+    if let Some(Command::Proposals { action }) = cli.command {
+        admin::run_command(&db_pool, action).await?;
+        return Ok(());
+    }
+
+    // Shutdown coordinator observed by the engines and pipeline tasks
+    let shutdown = Shutdown::new(config.shutdown.drain_deadline);
+
+    // Initialize engines with config
+    let engine = Arc::new(EngineManager::new(&config, db_pool.clone(), shutdown.token()).await?);
+    engine.fail_interrupted_proposals(&db_pool).await?;
+
+    info!("Starting {} agent...", config.persona.name);
+    pipeline::spawn(engine.clone(), db_pool.clone(), &config.scheduling, &config.outbox, &shutdown);
+
+    if config.approval.enabled {
+        let listener = tokio::net::TcpListener::bind(&config.approval.admin_addr).await?;
+        info!("Approval mode on, admin API listening on {}", config.approval.admin_addr);
+        shutdown.spawn(admin::serve(
+            listener,
+            db_pool.clone(),
+            config.approval.admin_token.clone(),
+            shutdown.token(),
+        ));
+    }
+
+    shutdown::wait_for_signal().await?;
+    info!("Shutdown signal received, draining in-flight work...");
+
+    // A second signal skips the drain
+    tokio::select! {
+        drained = shutdown.drain() => {
+            if !drained {
+                warn!("Drain deadline exceeded, abandoning remaining tasks");
             }
         }
+        _ = shutdown::wait_for_signal() => {
+            warn!("Second shutdown signal received, skipping drain");
+        }
+    }
+
+    // Snapshot in-memory state once no task can still mutate it. A task
+    // abandoned mid-cycle may still hold the memory lock, so don't wait forever.
+    match tokio::time::timeout(
+        PERSIST_TIMEOUT,
+        engine.persist_state(&config.shutdown.snapshot_path),
+    ).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::error!("Failed to persist memory state: {}", e),
+        Err(_) => warn!("Persisting memory state timed out after {:?}; skipping the snapshot", PERSIST_TIMEOUT),
     }
 
+    info!("Shutdown complete");
     Ok(())
 }
-
-async fn run_pipeline(
-    config: &Config,
-    pool: &db::Pool,
-    twitter: &xdotcom::Client,
-    ai: &engines::ai::Client,
-    eth: &engines::wallet::Client,
-) -> Result<()> {
-    // Pipeline implementation
-    todo!("Implement main agent pipeline")
-}
\ No newline at end of file
@@ -0,0 +1,303 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+use crate::{
+    config::{OutboxConfig, SchedulingConfig},
+    db::Pool,
+    engines::{approval, EngineManager},
+    shutdown::Shutdown,
+    utils::{self, Result},
+    xdotcom::types::Tweet,
+};
+
+/// Mentions waiting to be answered; polling blocks while the queue is full
+const MENTION_QUEUE_SIZE: usize = 64;
+
+/// Recurring units of work driven by the pipeline, each on its own schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    CognitiveCycle,
+    Interactions,
+    MemoryConsolidation,
+    MemoryForgetting,
+    WalletCheck,
+}
+
+impl Task {
+    pub const ALL: [Task; 5] = [
+        Task::CognitiveCycle,
+        Task::Interactions,
+        Task::MemoryConsolidation,
+        Task::MemoryForgetting,
+        Task::WalletCheck,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Task::CognitiveCycle => "cognitive_cycle",
+            Task::Interactions => "interactions",
+            Task::MemoryConsolidation => "memory_consolidation",
+            Task::MemoryForgetting => "memory_forgetting",
+            Task::WalletCheck => "wallet_check",
+        }
+    }
+
+    pub fn base_interval(&self, schedule: &SchedulingConfig) -> Duration {
+        match self {
+            Task::CognitiveCycle => schedule.cognitive_cycle_interval,
+            Task::Interactions => schedule.interaction_interval,
+            Task::MemoryConsolidation => schedule.consolidation_interval,
+            Task::MemoryForgetting => schedule.forgetting_interval,
+            Task::WalletCheck => schedule.wallet_check_interval,
+        }
+    }
+
+    /// Public-facing tasks only run during the agent's active hours
+    fn respects_active_hours(&self) -> bool {
+        matches!(self, Task::CognitiveCycle | Task::Interactions)
+    }
+
+    /// Tasks whose work ends in posts to X, spending the post budget
+    fn publishes(&self) -> bool {
+        matches!(self, Task::CognitiveCycle | Task::Interactions)
+    }
+}
+
+/// Spawns every task on its own schedule, plus the loops that answer polled
+/// mentions and deliver approved output, under the shutdown coordinator
+pub fn spawn(
+    engine: Arc<EngineManager>,
+    pool: Pool,
+    schedule: &SchedulingConfig,
+    outbox: &OutboxConfig,
+    shutdown: &Shutdown,
+) {
+    let (mentions_tx, mentions_rx) = mpsc::channel(MENTION_QUEUE_SIZE);
+
+    for task in Task::ALL {
+        let base_interval = task.base_interval(schedule);
+        info!("Scheduling {} every ~{}s", task.name(), base_interval.as_secs());
+        shutdown.spawn(run_task(
+            task,
+            engine.clone(),
+            pool.clone(),
+            base_interval,
+            mentions_tx.clone(),
+            shutdown.token(),
+        ));
+    }
+
+    shutdown.spawn(answer_mentions(engine.clone(), pool.clone(), mentions_rx, shutdown.token()));
+    shutdown.spawn(deliver(engine, pool, outbox.poll_interval, shutdown.token()));
+}
+
+async fn run_task(
+    task: Task,
+    engine: Arc<EngineManager>,
+    pool: Pool,
+    base_interval: Duration,
+    mentions: mpsc::Sender<Tweet>,
+    shutdown: CancellationToken,
+) {
+    while !shutdown.is_cancelled() {
+        // An iteration is never interrupted, so a generated post is always queued
+        if task.respects_active_hours() && !utils::is_active_hours() {
+            debug!("Outside active hours, skipping {}", task.name());
+        } else if let Err(e) = run_once(task, &engine, &pool, &mentions).await {
+            error!("Task {} failed: {}", task.name(), e);
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(next_delay(base_interval)) => {}
+        }
+    }
+
+    info!("Task {} stopped", task.name());
+}
+
+async fn run_once(
+    task: Task,
+    engine: &EngineManager,
+    pool: &Pool,
+    mentions: &mpsc::Sender<Tweet>,
+) -> Result<()> {
+    if task.publishes() && post_quota_exhausted(engine, task) {
+        return Ok(());
+    }
+
+    match task {
+        Task::CognitiveCycle => {
+            if let Some(content) = engine.process_cognitive_cycle(pool).await? {
+                let proposal = engine.submit_post(pool, &content).await?;
+                info!("Submitted post: {}", approval::summary(&proposal));
+            }
+        }
+        Task::Interactions => {
+            for tweet in engine.poll_mentions(pool).await? {
+                // Only fails once the answering loop has stopped for shutdown
+                if mentions.send(tweet).await.is_err() {
+                    break;
+                }
+            }
+        }
+        Task::MemoryConsolidation => {
+            engine.consolidate_memories().await?;
+        }
+        Task::MemoryForgetting => {
+            engine.forget_memories().await?;
+        }
+        Task::WalletCheck => {
+            for tweet in engine.fetch_interactions().await? {
+                if let Some(proposal) = engine.check_wallet_interactions(pool, &tweet.text).await? {
+                    info!("Submitted transfer: {}", approval::summary(&proposal));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Answers mentions in the order they were polled. A mention that fails, or is
+/// still queued at shutdown, is released so a later poll hands it out again.
+async fn answer_mentions(
+    engine: Arc<EngineManager>,
+    pool: Pool,
+    mut mentions: mpsc::Receiver<Tweet>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let tweet = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            tweet = mentions.recv() => match tweet {
+                Some(tweet) => tweet,
+                None => break,
+            },
+        };
+
+        if let Err(e) = answer_mention(&engine, &pool, &tweet).await {
+            error!("Failed to answer tweet {}: {}", tweet.id, e);
+            release_mention(&engine, &pool, &tweet).await;
+        }
+    }
+
+    mentions.close();
+    while let Ok(tweet) = mentions.try_recv() {
+        release_mention(&engine, &pool, &tweet).await;
+    }
+
+    info!("Mention answering stopped");
+}
+
+async fn answer_mention(engine: &EngineManager, pool: &Pool, tweet: &Tweet) -> Result<()> {
+    if let Some(reply) = engine.handle_interaction(tweet).await? {
+        let proposal = engine.submit_reply(pool, &reply, &tweet.id).await?;
+        info!("Submitted reply: {}", approval::summary(&proposal));
+    }
+
+    engine.mention_answered(pool, &tweet.id).await
+}
+
+async fn release_mention(engine: &EngineManager, pool: &Pool, tweet: &Tweet) {
+    if let Err(e) = engine.release_mention(pool, &tweet.id).await {
+        error!("Failed to release tweet {}: {}", tweet.id, e);
+    }
+}
+
+/// Executes approved proposals (including those approved from the admin API
+/// or CLI) and sends whatever the outbox has due, then waits for more. Work in
+/// progress finishes before shutdown; anything still queued goes next run.
+async fn deliver(
+    engine: Arc<EngineManager>,
+    pool: Pool,
+    poll_interval: Duration,
+    shutdown: CancellationToken,
+) {
+    while !shutdown.is_cancelled() {
+        match engine.execute_approved(&pool).await {
+            Ok(0) => {}
+            Ok(executed) => info!("Executed {} approved proposal(s)", executed),
+            Err(e) => error!("Executing proposals failed: {}", e),
+        }
+
+        match engine.send_outbox(&pool).await {
+            Ok(0) => {}
+            Ok(attempted) => debug!("Outbox: attempted {} row(s)", attempted),
+            Err(e) => error!("Outbox sending failed: {}", e),
+        }
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = sleep(poll_interval) => {}
+        }
+    }
+
+    info!("Delivery stopped");
+}
+
+/// Skips a publishing task while X reports no post budget left, rather than
+/// generating content that would only be rejected
+fn post_quota_exhausted(engine: &EngineManager, task: Task) -> bool {
+    match engine.post_quota() {
+        Some(quota) if quota.is_exhausted(chrono::Utc::now()) => {
+            info!("Post quota exhausted until {}, skipping {}", quota.reset_at, task.name());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Applies the posting jitter to a base interval
+pub fn next_delay(base_interval: Duration) -> Duration {
+    if base_interval.as_secs() == 0 {
+        return base_interval;
+    }
+
+    chrono::Duration::from_std(base_interval)
+        .ok()
+        .and_then(|base| utils::generate_posting_interval(base).to_std().ok())
+        .unwrap_or(base_interval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_delay_jitter() {
+        let base = Duration::from_secs(600);
+        for _ in 0..100 {
+            let delay = next_delay(base);
+            assert!(delay >= Duration::from_secs(480));
+            assert!(delay <= Duration::from_secs(720));
+        }
+    }
+
+    #[test]
+    fn test_next_delay_zero_interval() {
+        assert_eq!(next_delay(Duration::ZERO), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_task_intervals() {
+        let schedule = SchedulingConfig {
+            cognitive_cycle_interval: Duration::from_secs(1),
+            interaction_interval: Duration::from_secs(2),
+            consolidation_interval: Duration::from_secs(3),
+            forgetting_interval: Duration::from_secs(4),
+            wallet_check_interval: Duration::from_secs(5),
+        };
+
+        let intervals: Vec<u64> = Task::ALL
+            .iter()
+            .map(|task| task.base_interval(&schedule).as_secs())
+            .collect();
+        assert_eq!(intervals, vec![1, 2, 3, 4, 5]);
+    }
+}
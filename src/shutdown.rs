@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+/// Coordinates shutdown between the signal handler and long-running tasks
+#[derive(Clone)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+    drain_deadline: Duration,
+}
+
+impl Shutdown {
+    pub fn new(drain_deadline: Duration) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            tracker: TaskTracker::new(),
+            drain_deadline,
+        }
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    /// Cancels all tasks and waits for them to finish.
+    ///
+    /// Returns `false` if the drain deadline passed with tasks still running.
+    pub async fn drain(&self) -> bool {
+        self.token.cancel();
+        self.tracker.close();
+
+        info!(
+            "Draining {} in-flight task(s), deadline {}s",
+            self.tracker.len(),
+            self.drain_deadline.as_secs()
+        );
+
+        match tokio::time::timeout(self.drain_deadline, self.tracker.wait()).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!("{} task(s) still running after drain deadline", self.tracker.len());
+                false
+            }
+        }
+    }
+}
+
+/// Resolves on the first SIGINT or SIGTERM
+pub async fn wait_for_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigterm = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = sigterm.recv() => info!("SIGTERM received"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight_work() {
+        let shutdown = Shutdown::new(Duration::from_secs(1));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let token = shutdown.token();
+        let flag = finished.clone();
+        shutdown.spawn(async move {
+            token.cancelled().await;
+            // Simulate finishing a post + DB write after cancellation
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        assert!(shutdown.drain().await);
+        assert!(finished.load(Ordering::SeqCst));
+        assert!(shutdown.is_shutting_down());
+    }
+
+    #[tokio::test]
+    async fn test_drain_deadline_exceeded() {
+        let shutdown = Shutdown::new(Duration::from_millis(50));
+        shutdown.spawn(async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        assert!(!shutdown.drain().await);
+    }
+}
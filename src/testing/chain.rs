@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use ethers::types::{transaction::eip2718::TypedTransaction, Bytes, H256, U256};
+use ethers::utils::{keccak256, parse_ether, rlp::Rlp};
+use serde_json::{json, Value};
+
+use super::server::{FakeResponse, FakeServer};
+
+/// Anvil's first default account, funded with 10,000 ETH
+pub const ANVIL_PRIVATE_KEY: &str =
+    "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+pub const ANVIL_ADDRESS: &str = "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+pub const ANVIL_CHAIN_ID: u64 = 31337;
+
+#[derive(Debug, Clone)]
+pub struct SentTransaction {
+    pub hash: H256,
+    pub from: String,
+    pub to: String,
+    pub value: U256,
+}
+
+#[derive(Default)]
+struct ChainState {
+    balances: HashMap<String, U256>,
+    transactions: Vec<SentTransaction>,
+    block_number: u64,
+}
+
+/// Anvil-style JSON-RPC chain served from `/rpc`: funded default account,
+/// instant mining, and balances that move when signed transactions are sent.
+/// Like a public node it has no unlocked accounts, so only
+/// `eth_sendRawTransaction` is accepted and the sender is recovered from the
+/// signature.
+#[derive(Clone)]
+pub struct FakeChain {
+    state: Arc<Mutex<ChainState>>,
+}
+
+impl FakeChain {
+    pub fn mount(server: &FakeServer) -> Self {
+        let mut state = ChainState::default();
+        state
+            .balances
+            .insert(ANVIL_ADDRESS.to_string(), parse_ether(10_000u64).unwrap());
+
+        let chain = Self {
+            state: Arc::new(Mutex::new(state)),
+        };
+
+        let handler_chain = chain.clone();
+        server.handle("POST", "/rpc", move |request| {
+            let call = request.json();
+            let reply = match handler_chain.dispatch(&call) {
+                Ok(result) => json!({"jsonrpc": "2.0", "id": call["id"], "result": result}),
+                Err(message) => json!({
+                    "jsonrpc": "2.0",
+                    "id": call["id"],
+                    "error": {"code": -32601, "message": message}
+                }),
+            };
+            FakeResponse::ok(reply)
+        });
+
+        chain
+    }
+
+    pub fn balance(&self, address: &str) -> U256 {
+        self.state
+            .lock()
+            .unwrap()
+            .balances
+            .get(&address.to_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn transactions(&self) -> Vec<SentTransaction> {
+        self.state.lock().unwrap().transactions.clone()
+    }
+
+    fn dispatch(&self, call: &Value) -> Result<Value, String> {
+        let params = &call["params"];
+        let method = call["method"].as_str().unwrap_or_default();
+
+        match method {
+            "eth_chainId" => Ok(quantity(U256::from(ANVIL_CHAIN_ID))),
+            "net_version" => Ok(json!(ANVIL_CHAIN_ID.to_string())),
+            "eth_blockNumber" => Ok(quantity(self.state.lock().unwrap().block_number.into())),
+            "eth_gasPrice" => Ok(quantity(U256::from(1_000_000_000u64))),
+            "eth_maxPriorityFeePerGas" => Ok(quantity(U256::from(1_000_000_000u64))),
+            "eth_estimateGas" => Ok(quantity(U256::from(21_000u64))),
+            "eth_getTransactionCount" => {
+                let from = params[0].as_str().unwrap_or_default().to_lowercase();
+                let count = self
+                    .state
+                    .lock()
+                    .unwrap()
+                    .transactions
+                    .iter()
+                    .filter(|tx| tx.from == from)
+                    .count();
+                Ok(quantity(count.into()))
+            }
+            "eth_getBalance" => {
+                let address = params[0].as_str().unwrap_or_default();
+                Ok(quantity(self.balance(address)))
+            }
+            "eth_sendRawTransaction" => self.send_raw_transaction(&params[0]),
+            _ => Err(format!("fake chain does not support {}", method)),
+        }
+    }
+
+    fn send_raw_transaction(&self, raw: &Value) -> Result<Value, String> {
+        let raw: Bytes = serde_json::from_value(raw.clone()).map_err(|e| e.to_string())?;
+        let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw))
+            .map_err(|e| format!("invalid transaction: {}", e))?;
+
+        if tx.chain_id() != Some(ANVIL_CHAIN_ID.into()) {
+            return Err(format!("chain id {:?} is not {}", tx.chain_id(), ANVIL_CHAIN_ID));
+        }
+
+        let from = signature
+            .recover(tx.sighash())
+            .map_err(|e| format!("invalid signature: {}", e))?;
+        let from = format!("{:?}", from);
+        let to = tx
+            .to_addr()
+            .map(|to| format!("{:?}", to))
+            .ok_or("contract creation is not supported")?;
+        let value = tx.value().copied().unwrap_or_default();
+
+        let mut state = self.state.lock().unwrap();
+
+        let nonce = state.transactions.iter().filter(|sent| sent.from == from).count();
+        if tx.nonce() != Some(&nonce.into()) {
+            return Err(format!("nonce {:?} does not match {}", tx.nonce(), nonce));
+        }
+
+        let balance = state.balances.get(&from).copied().unwrap_or_default();
+        if balance < value {
+            return Err("insufficient funds".to_string());
+        }
+
+        state.balances.insert(from.clone(), balance - value);
+        *state.balances.entry(to.clone()).or_default() += value;
+        state.block_number += 1;
+
+        let hash = H256::from(keccak256(&raw));
+        state.transactions.push(SentTransaction { hash, from, to, value });
+
+        Ok(json!(format!("{:?}", hash)))
+    }
+}
+
+fn quantity(value: U256) -> Value {
+    json!(format!("{:#x}", value))
+}
@@ -0,0 +1,65 @@
+use serde_json::json;
+
+use super::server::{FakeResponse, FakeServer};
+
+/// Embedding size used by the fake embeddings endpoint, the size of the
+/// `long_term_memories.embedding` column
+pub const FAKE_EMBEDDING_DIMENSIONS: usize = crate::db::vector::EMBEDDING_DIMENSIONS;
+
+/// Deterministic bag-of-words embedding: texts sharing words get similar vectors
+pub fn fake_embedding(text: &str, dimensions: usize) -> Vec<f32> {
+    let mut vector = vec![0.0f32; dimensions];
+
+    for word in text.split_whitespace() {
+        let word = word.to_lowercase();
+        let bucket = word
+            .bytes()
+            .fold(2166136261u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(16777619));
+        vector[bucket as usize % dimensions] += 1.0;
+    }
+
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    } else {
+        vector[0] = 1.0;
+    }
+    vector
+}
+
+pub fn chat_response(content: &str) -> FakeResponse {
+    FakeResponse::ok(json!({
+        "id": "chatcmpl-fake",
+        "object": "chat.completion",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }]
+    }))
+}
+
+/// Scripts `/v1/chat/completions` to answer with `replies` in order
+pub fn mount_chat(server: &FakeServer, replies: &[&str]) {
+    server.script(
+        "POST",
+        "/v1/chat/completions",
+        replies.iter().map(|reply| chat_response(reply)).collect(),
+    );
+}
+
+/// Serves deterministic embeddings from `/v1/embeddings`
+pub fn mount_embeddings(server: &FakeServer) {
+    server.handle("POST", "/v1/embeddings", |request| {
+        let input = request.json()["input"].as_str().unwrap_or_default().to_string();
+        FakeResponse::ok(json!({
+            "object": "list",
+            "data": [{
+                "object": "embedding",
+                "index": 0,
+                "embedding": fake_embedding(&input, FAKE_EMBEDDING_DIMENSIONS)
+            }],
+            "model": "fake-embedding"
+        }))
+    });
+}
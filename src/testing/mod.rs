@@ -0,0 +1,106 @@
+//! Offline test harness: a fake HTTP server that stands in for the X v2 API,
+//! the chat/embedding endpoints and an Ethereum node.
+
+pub mod chain;
+pub mod llm;
+pub mod server;
+pub mod x;
+
+pub use chain::FakeChain;
+pub use server::{FakeResponse, FakeServer};
+
+use crate::config::{Config, ProviderKind};
+use crate::db::Pool;
+
+pub const TEST_USERNAME: &str = "tomflash";
+
+/// Config with every external service pointed at `server`
+pub fn test_config(server: &FakeServer) -> Config {
+    let mut config = Config {
+        database_url: std::env::var("TEST_DATABASE_URL").unwrap_or_default(),
+        ..Config::default()
+    };
+
+    config.twitter.api_base = format!("{}/2", server.url());
+    config.twitter.upload_base = format!("{}/1.1", server.url());
+    config.twitter.api_key = "test_api_key".to_string();
+    config.twitter.api_secret = "test_api_secret".to_string();
+    config.twitter.access_token = "test_access_token".to_string();
+    config.twitter.access_secret = "test_access_secret".to_string();
+    config.twitter.bearer_token = "test_bearer_token".to_string();
+    config.twitter.username = TEST_USERNAME.to_string();
+
+    config.llm.provider = ProviderKind::Vllm;
+    config.llm.base_url = Some(format!("{}/v1", server.url()));
+    config.llm.model = "fake-chat".to_string();
+
+    config.embeddings.provider = ProviderKind::Vllm;
+    config.embeddings.base_url = Some(format!("{}/v1", server.url()));
+    config.embeddings.model = "fake-embedding".to_string();
+    config.embeddings.dimensions = llm::FAKE_EMBEDDING_DIMENSIONS;
+
+    config.wallet.rpc_url = format!("{}/rpc", server.url());
+    config.wallet.private_key = chain::ANVIL_PRIVATE_KEY.to_string();
+    config.wallet.chain_id = chain::ANVIL_CHAIN_ID;
+
+    let state_dir = std::env::temp_dir().join(format!("tomflash-test-{}", uuid::Uuid::new_v4()));
+    config.shutdown.snapshot_path = state_dir.join("short_term_memory.json");
+    config.memory.index_snapshot_path = state_dir.join("vector_index.json");
+
+    config
+}
+
+/// Writes `bytes` to a fresh file named `name` under the temp dir
+pub fn temp_file(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("tomflash-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Starts a fake server with X, LLM and chain routes mounted
+pub async fn fake_backends() -> (FakeServer, FakeChain) {
+    let server = FakeServer::start().await;
+    x::mount_x(&server, TEST_USERNAME);
+    llm::mount_embeddings(&server);
+    let chain = FakeChain::mount(&server);
+    (server, chain)
+}
+
+/// Migrated pool for `TEST_DATABASE_URL`. Tests that use it are `#[ignore]`d
+/// and run with `cargo test -- --ignored`, where a missing database fails them.
+pub async fn test_pool(config: &Config) -> Pool {
+    assert!(
+        !config.database_url.is_empty(),
+        "TEST_DATABASE_URL must point at a Postgres database for database-backed tests"
+    );
+
+    let pool = crate::db::establish_connection(config).await.unwrap();
+    crate::db::run_migrations(config).await.unwrap();
+    pool
+}
+
+/// Empties the outbox, proposals and their audit trail and holds them until the guard drops.
+/// Delivery claims whatever row is due, so tests that use either take turns.
+pub async fn lock_queues(pool: &Pool) -> tokio::sync::MutexGuard<'static, ()> {
+    use diesel_async::RunQueryDsl;
+
+    static QUEUES: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    let guard = QUEUES.lock().await;
+    let mut conn = pool.get().await.unwrap();
+    diesel::delete(crate::db::schema::outbox::table)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    diesel::delete(crate::db::schema::content_filter_audit::table)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    diesel::delete(crate::db::schema::proposals::table)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    guard
+}
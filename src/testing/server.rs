@@ -0,0 +1,293 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// A request captured by the fake server
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(String::as_str)
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        let url = reqwest::Url::parse(&format!("http://localhost/?{}", self.query)).ok()?;
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl FakeResponse {
+    pub fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    pub fn ok(body: Value) -> Self {
+        Self::json(200, body)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&RecordedRequest) -> FakeResponse + Send + Sync>;
+
+enum Responder {
+    /// Scripted responses played in order; the last one repeats
+    Script(VecDeque<FakeResponse>),
+    Handler(Handler),
+}
+
+struct Route {
+    method: String,
+    pattern: Vec<String>,
+    responder: Responder,
+}
+
+impl Route {
+    fn matches(&self, method: &str, path: &str) -> bool {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        self.method == method
+            && self.pattern.len() == segments.len()
+            && self
+                .pattern
+                .iter()
+                .zip(&segments)
+                .all(|(pattern, segment)| pattern.starts_with(':') || pattern == segment)
+    }
+
+    fn respond(&mut self, request: &RecordedRequest) -> FakeResponse {
+        match &mut self.responder {
+            Responder::Script(script) if script.len() > 1 => script.pop_front().unwrap(),
+            Responder::Script(script) => script.front().cloned().unwrap(),
+            Responder::Handler(handler) => handler(request),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// In-process HTTP/1.1 server with scripted routes, for tests that must not
+/// touch the network. Path segments starting with `:` match anything.
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl FakeServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let accept_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, accept_state.clone()));
+            }
+        });
+
+        Self { addr, state, task }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Responds with `responses` in order, repeating the last one
+    pub fn script(&self, method: &str, path: &str, responses: Vec<FakeResponse>) {
+        assert!(!responses.is_empty(), "script needs at least one response");
+        self.add_route(method, path, Responder::Script(responses.into()));
+    }
+
+    pub fn handle<F>(&self, method: &str, path: &str, handler: F)
+    where
+        F: Fn(&RecordedRequest) -> FakeResponse + Send + Sync + 'static,
+    {
+        self.add_route(method, path, Responder::Handler(Arc::new(handler)));
+    }
+
+    fn add_route(&self, method: &str, path: &str, responder: Responder) {
+        let route = Route {
+            method: method.to_uppercase(),
+            pattern: path.trim_matches('/').split('/').map(String::from).collect(),
+            responder,
+        };
+
+        // Newer routes take precedence so tests can re-script an endpoint
+        self.state.lock().unwrap().routes.insert(0, route);
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == method && r.path == path)
+            .collect()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+        return;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .and_then(|len| len.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).await.is_err() {
+        return;
+    }
+
+    let request = RecordedRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body,
+    };
+
+    let response = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        state
+            .routes
+            .iter_mut()
+            .find(|route| route.matches(&request.method, &request.path))
+            .map(|route| route.respond(&request))
+            .unwrap_or_else(|| {
+                FakeResponse::json(404, serde_json::json!({
+                    "error": format!("no fake route for {} {}", request.method, request.path)
+                }))
+            })
+    };
+
+    let mut head = format!(
+        "HTTP/1.1 {} Fake\r\ncontent-length: {}\r\nconnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let mut stream = reader.into_inner();
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&response.body).await;
+    let _ = stream.shutdown().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_scripted_responses_in_order() {
+        let server = FakeServer::start().await;
+        server.script("GET", "/items/:id", vec![
+            FakeResponse::ok(json!({"n": 1})).with_header("x-page", "first"),
+            FakeResponse::ok(json!({"n": 2})),
+        ]);
+
+        let client = reqwest::Client::new();
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            let response = client
+                .get(format!("{}/items/42?x=1", server.url()))
+                .header("x-trace", "abc")
+                .send()
+                .await
+                .unwrap();
+            let page = response.headers().get("x-page").map(|value| value.to_str().unwrap().to_string());
+            let body: Value = response.json().await.unwrap();
+            seen.push((body["n"].as_i64().unwrap(), page));
+        }
+
+        assert_eq!(seen, vec![(1, Some("first".to_string())), (2, None), (2, None)]);
+        let requests = server.requests_to("GET", "/items/42");
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].query_param("x").as_deref(), Some("1"));
+        assert_eq!(requests[0].header("x-trace"), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_route_is_404() {
+        let server = FakeServer::start().await;
+        let response = reqwest::Client::new()
+            .post(format!("{}/missing", server.url()))
+            .json(&json!({"a": 1}))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 404);
+        assert_eq!(server.requests()[0].json()["a"], 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use super::server::{FakeResponse, FakeServer};
+
+/// Mounts the X v2 endpoints the client uses. Posted tweets get sequential ids
+/// starting at 1000; timelines and search start out empty, tweet lookups find
+/// nothing, and the agent's user id is 1. Media uploads get id 5000 and
+/// report one round of processing before succeeding.
+pub fn mount_x(server: &FakeServer, username: &str) {
+    let next_id = Arc::new(AtomicU64::new(1000));
+    server.handle("POST", "/2/tweets", move |request| {
+        let id = next_id.fetch_add(1, Ordering::SeqCst);
+        let body = request.json();
+        FakeResponse::json(201, json!({
+            "data": {"id": id.to_string(), "text": body["text"]}
+        }))
+    });
+
+    server.script("GET", "/2/users/:id/timelines/reverse_chronological", vec![timeline_response(&[])]);
+    server.script("GET", "/2/users/:id/mentions", vec![timeline_response(&[])]);
+    server.script("GET", "/2/tweets/search/recent", vec![timeline_response(&[])]);
+    server.script("GET", "/2/users/:id/tweets", vec![timeline_response(&[])]);
+
+    // X answers lookups of deleted or protected tweets with 200 and an error list
+    server.script("GET", "/2/tweets/:id", vec![FakeResponse::ok(json!({
+        "errors": [{"title": "Not Found Error", "detail": "Could not find tweet."}]
+    }))]);
+
+    server.script("GET", "/2/users/me", vec![FakeResponse::ok(json!({
+        "data": {"id": "1", "name": username, "username": username}
+    }))]);
+
+    server.script("POST", "/2/users/:id/following", vec![FakeResponse::ok(json!({
+        "data": {"following": true, "pending_follow": false}
+    }))]);
+
+    server.handle("POST", "/1.1/media/upload.json", |request| {
+        let body = String::from_utf8_lossy(&request.body);
+        if body.contains("command=INIT") {
+            FakeResponse::ok(json!({"media_id": 5000, "media_id_string": "5000"}))
+        } else if body.contains("command=FINALIZE") {
+            FakeResponse::ok(json!({
+                "media_id_string": "5000",
+                "processing_info": {"state": "pending", "check_after_secs": 0}
+            }))
+        } else {
+            empty_response(204)
+        }
+    });
+    server.script("GET", "/1.1/media/upload.json", vec![FakeResponse::ok(json!({
+        "media_id_string": "5000",
+        "processing_info": {"state": "succeeded", "progress_percent": 100}
+    }))]);
+    server.script("POST", "/1.1/media/metadata/create.json", vec![empty_response(200)]);
+
+    let username = username.to_string();
+    server.handle("GET", "/2/users/by/username/:username", move |request| {
+        let requested = request.path.rsplit('/').next().unwrap_or_default();
+        FakeResponse::ok(json!({
+            "data": {
+                "id": if requested == username { "1" } else { "2" },
+                "name": requested,
+                "username": requested
+            }
+        }))
+    });
+}
+
+fn empty_response(status: u16) -> FakeResponse {
+    FakeResponse {
+        status,
+        headers: Vec::new(),
+        body: Vec::new(),
+    }
+}
+
+/// Builds a timeline or mentions page from `(id, author_id, text)` triples,
+/// newest first. Each author is expanded as `user<author_id>`.
+pub fn timeline_response(tweets: &[(&str, &str, &str)]) -> FakeResponse {
+    timeline_page(tweets, None)
+}
+
+/// Like [`timeline_response`], with a `next_token` pointing at another page
+pub fn timeline_page(tweets: &[(&str, &str, &str)], next_token: Option<&str>) -> FakeResponse {
+    let data: Vec<Value> = tweets
+        .iter()
+        .map(|(id, author_id, text)| json!({"id": id, "author_id": author_id, "text": text}))
+        .collect();
+
+    let users: Vec<Value> = tweets
+        .iter()
+        .map(|(_, author_id, _)| json!({
+            "id": author_id,
+            "name": format!("User {}", author_id),
+            "username": format!("user{}", author_id)
+        }))
+        .collect();
+
+    let mut meta = json!({"result_count": tweets.len()});
+    if let (Some(newest), Some(oldest)) = (tweets.first(), tweets.last()) {
+        meta["newest_id"] = json!(newest.0);
+        meta["oldest_id"] = json!(oldest.0);
+    }
+    if let Some(next_token) = next_token {
+        meta["next_token"] = json!(next_token);
+    }
+
+    let mut body = json!({"meta": meta});
+    if !tweets.is_empty() {
+        body["data"] = json!(data);
+        body["includes"] = json!({"users": users});
+    }
+
+    FakeResponse::ok(body)
+}
+
+/// Posted tweet texts, in order
+pub fn posted_texts(server: &FakeServer) -> Vec<String> {
+    server
+        .requests_to("POST", "/2/tweets")
+        .iter()
+        .map(|request| request.json()["text"].as_str().unwrap_or_default().to_string())
+        .collect()
+}
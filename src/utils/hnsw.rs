@@ -0,0 +1,393 @@
+//! Hierarchical navigable small world graph (Malkov & Yashunin) for
+//! approximate nearest-neighbour search by cosine similarity. Removed entries
+//! stay in the graph as waypoints until they outnumber the live ones, then the
+//! graph is rebuilt.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::{Result, UtilError};
+
+/// Links kept per node above layer 0; layer 0 keeps twice as many
+const M: usize = 16;
+/// Candidates considered when linking a new node
+const EF_CONSTRUCTION: usize = 100;
+/// Candidates considered per search, at least as many as the results asked for
+const EF_SEARCH: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    id: i32,
+    /// Normalized, so cosine similarity is the dot product
+    vector: Vec<f32>,
+    /// Neighbours per layer, from layer 0 up to the node's level
+    links: Vec<Vec<usize>>,
+    removed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hnsw {
+    dimensions: usize,
+    nodes: Vec<Node>,
+    /// Node positions of the live ids
+    positions: HashMap<i32, usize>,
+    entry: Option<usize>,
+    removed: usize,
+    #[serde(skip, default = "StdRng::from_entropy")]
+    rng: StdRng,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hnsw {
+    pub fn new(dimensions: usize) -> Self {
+        Self::with_rng(dimensions, StdRng::from_entropy())
+    }
+
+    /// Same graph for the same inserts, for tests
+    pub fn with_seed(dimensions: usize, seed: u64) -> Self {
+        Self::with_rng(dimensions, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(dimensions: usize, rng: StdRng) -> Self {
+        Self {
+            dimensions,
+            nodes: Vec::new(),
+            positions: HashMap::new(),
+            entry: None,
+            removed: 0,
+            rng,
+        }
+    }
+
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    /// Number of live entries
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn contains(&self, id: i32) -> bool {
+        self.positions.contains_key(&id)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.positions.keys().copied()
+    }
+
+    /// Adds `vector` under `id`. An id that is already present keeps its vector.
+    pub fn insert(&mut self, id: i32, vector: &[f32]) -> Result<()> {
+        self.check_dimensions(vector)?;
+        if self.contains(id) {
+            return Ok(());
+        }
+
+        let vector = normalized(vector);
+        let level = self.random_level();
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            id,
+            vector: vector.clone(),
+            links: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+        self.positions.insert(id, node);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(node);
+            return Ok(());
+        };
+
+        let top = self.nodes[entry].links.len() - 1;
+        let mut entry_points = vec![entry];
+        for layer in (level + 1..=top).rev() {
+            entry_points = vec![self.search_layer(&vector, &entry_points, 1, layer)[0].node];
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&vector, &entry_points, EF_CONSTRUCTION, layer);
+            let neighbours: Vec<usize> = found.iter().take(M).map(|c| c.node).collect();
+
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].links[layer].push(node);
+                self.prune(neighbour, layer);
+            }
+            self.nodes[node].links[layer] = neighbours;
+            entry_points = found.into_iter().map(|c| c.node).collect();
+        }
+
+        if level > top {
+            self.entry = Some(node);
+        }
+        Ok(())
+    }
+
+    /// Removes `id` from results; returns whether it was present
+    pub fn remove(&mut self, id: i32) -> bool {
+        let Some(node) = self.positions.remove(&id) else {
+            return false;
+        };
+        self.nodes[node].removed = true;
+        self.removed += 1;
+
+        if self.removed > self.len() {
+            self.rebuild();
+        }
+        true
+    }
+
+    /// Up to `limit` `(id, similarity)` pairs, most similar first
+    pub fn search(&self, query: &[f32], limit: usize) -> Result<Vec<(i32, f32)>> {
+        self.check_dimensions(query)?;
+        let Some(entry) = self.entry else {
+            return Ok(Vec::new());
+        };
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let query = normalized(query);
+        let mut entry_points = vec![entry];
+        for layer in (1..self.nodes[entry].links.len()).rev() {
+            entry_points = vec![self.search_layer(&query, &entry_points, 1, layer)[0].node];
+        }
+
+        // Removed nodes still take up candidate slots
+        let ef = EF_SEARCH.max(limit) + self.removed;
+        Ok(self
+            .search_layer(&query, &entry_points, ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.node].removed)
+            .take(limit)
+            .map(|c| (self.nodes[c.node].id, 1.0 - c.distance))
+            .collect())
+    }
+
+    fn check_dimensions(&self, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dimensions {
+            return Err(UtilError::ConversionError(format!(
+                "vector has {} dimensions, the index has {}",
+                vector.len(),
+                self.dimensions
+            )));
+        }
+        Ok(())
+    }
+
+    fn random_level(&mut self) -> usize {
+        let normalization = 1.0 / (M as f64).ln();
+        let uniform: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        (-uniform.ln() * normalization) as usize
+    }
+
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        1.0 - dot(query, &self.nodes[node].vector)
+    }
+
+    /// The `ef` nodes closest to `query` reachable on `layer`, closest first
+    fn search_layer(&self, query: &[f32], entry_points: &[usize], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        let mut candidates = BinaryHeap::new();
+        let mut closest = BinaryHeap::new();
+
+        for &node in entry_points {
+            let candidate = Candidate {
+                distance: self.distance(query, node),
+                node,
+            };
+            candidates.push(Reverse(candidate));
+            closest.push(candidate);
+        }
+        while closest.len() > ef {
+            closest.pop();
+        }
+
+        while let Some(Reverse(candidate)) = candidates.pop() {
+            let furthest = closest.peek().map_or(f32::INFINITY, |c: &Candidate| c.distance);
+            if candidate.distance > furthest {
+                break;
+            }
+
+            for &neighbour in &self.nodes[candidate.node].links[layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let distance = self.distance(query, neighbour);
+                let furthest = closest.peek().map_or(f32::INFINITY, |c: &Candidate| c.distance);
+                if closest.len() < ef || distance < furthest {
+                    let next = Candidate { distance, node: neighbour };
+                    candidates.push(Reverse(next));
+                    closest.push(next);
+                    if closest.len() > ef {
+                        closest.pop();
+                    }
+                }
+            }
+        }
+
+        closest.into_sorted_vec()
+    }
+
+    /// Keeps only the closest links of `node` on `layer` once it has too many
+    fn prune(&mut self, node: usize, layer: usize) {
+        let max_links = if layer == 0 { 2 * M } else { M };
+        if self.nodes[node].links[layer].len() <= max_links {
+            return;
+        }
+
+        let mut links = std::mem::take(&mut self.nodes[node].links[layer]);
+        let vector = std::mem::take(&mut self.nodes[node].vector);
+        links.sort_by(|&a, &b| self.distance(&vector, a).total_cmp(&self.distance(&vector, b)));
+        links.truncate(max_links);
+
+        self.nodes[node].vector = vector;
+        self.nodes[node].links[layer] = links;
+    }
+
+    fn rebuild(&mut self) {
+        let live: Vec<Node> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter(|node| !node.removed)
+            .collect();
+
+        self.positions.clear();
+        self.entry = None;
+        self.removed = 0;
+        for node in live {
+            self.insert(node.id, &node.vector)
+                .expect("live nodes have the index's dimensions");
+        }
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn normalized(vector: &[f32]) -> Vec<f32> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIMENSIONS: usize = 24;
+
+    fn random_vectors(count: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| (0..DIMENSIONS).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn exact(vectors: &[(i32, Vec<f32>)], query: &[f32], limit: usize) -> Vec<i32> {
+        let query = normalized(query);
+        let mut scored: Vec<(i32, f32)> = vectors
+            .iter()
+            .map(|(id, vector)| (*id, dot(&query, &normalized(vector))))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(limit).map(|(id, _)| id).collect()
+    }
+
+    fn recall(index: &Hnsw, vectors: &[(i32, Vec<f32>)], queries: &[Vec<f32>], limit: usize) -> f32 {
+        let mut hits = 0;
+        for query in queries {
+            let expected: HashSet<i32> = exact(vectors, query, limit).into_iter().collect();
+            let found = index.search(query, limit).unwrap();
+            hits += found.iter().filter(|(id, _)| expected.contains(id)).count();
+        }
+        hits as f32 / (queries.len() * limit) as f32
+    }
+
+    fn build(vectors: &[(i32, Vec<f32>)]) -> Hnsw {
+        let mut index = Hnsw::with_seed(DIMENSIONS, 7);
+        for (id, vector) in vectors {
+            index.insert(*id, vector).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn test_recall_against_exact_search() {
+        let vectors: Vec<(i32, Vec<f32>)> = random_vectors(2000, 1).into_iter().zip(1..).map(|(v, id)| (id, v)).collect();
+        let index = build(&vectors);
+        let queries = random_vectors(50, 2);
+
+        assert_eq!(index.len(), 2000);
+        assert!(recall(&index, &vectors, &queries, 10) >= 0.95);
+
+        let (id, vector) = &vectors[123];
+        let found = index.search(vector, 1).unwrap();
+        assert_eq!(found[0].0, *id);
+        assert!((found[0].1 - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_removed_ids_are_not_returned() {
+        let mut vectors: Vec<(i32, Vec<f32>)> = random_vectors(300, 3).into_iter().zip(1..).map(|(v, id)| (id, v)).collect();
+        let mut index = build(&vectors);
+
+        let (removed_id, removed_vector) = vectors.remove(10);
+        assert!(index.remove(removed_id));
+        assert!(!index.remove(removed_id));
+        assert!(index.search(&removed_vector, 5).unwrap().iter().all(|(id, _)| *id != removed_id));
+
+        // The graph is rebuilt once removed entries outnumber live ones,
+        // here after the 151st removal
+        for (id, _) in vectors.drain(..200) {
+            index.remove(id);
+        }
+        assert_eq!(index.len(), 99);
+        assert_eq!(index.nodes.len(), 149);
+        assert_eq!(index.removed, 50);
+        assert!(recall(&index, &vectors, &random_vectors(20, 4), 5) >= 0.95);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let vectors: Vec<(i32, Vec<f32>)> = random_vectors(200, 5).into_iter().zip(1..).map(|(v, id)| (id, v)).collect();
+        let index = build(&vectors);
+
+        let restored: Hnsw = serde_json::from_slice(&serde_json::to_vec(&index).unwrap()).unwrap();
+        let query = &random_vectors(1, 6)[0];
+        assert_eq!(restored.search(query, 10).unwrap(), index.search(query, 10).unwrap());
+        assert!(restored.contains(200));
+        assert!(restored.search(&[1.0], 1).is_err());
+    }
+}
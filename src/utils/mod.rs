@@ -1,8 +1,9 @@
-mod json;
 mod time;
-mod traits;
+pub mod traits;
+pub mod hnsw;
+pub mod secret_guard;
+pub mod tweet_text;
 
-pub use json::*;
 pub use time::*;
 pub use traits::*;
 
@@ -18,6 +19,9 @@ pub enum UtilError {
 
     #[error("Conversion error: {0}")]
     ConversionError(String),
+
+    #[error("X API error: {0}")]
+    XError(#[from] crate::xdotcom::XError),
 }
 
 pub type Result<T> = std::result::Result<T, UtilError>;
\ No newline at end of file
@@ -0,0 +1,231 @@
+//! Hard stop for configured credentials in outgoing text. Each secret is
+//! matched as written, as hex and as base64, ignoring whitespace; the wallet
+//! key also by the BIP39 words that encode it. Unlike the content filter this
+//! can't be turned off.
+
+use std::fmt;
+
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ethers::signers::coins_bip39::{English, Wordlist};
+use ethers::utils::hex;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::config::Config;
+
+/// Tracing target for blocked leaks, so they can be routed to an alert
+pub const ALERT_TARGET: &str = "tomflash::alert";
+
+/// Secrets shorter than this are too likely to occur by chance to block on
+const MIN_SECRET_LEN: usize = 8;
+
+/// Consecutive words of a key's mnemonic that count as leaking it
+const MIN_MNEMONIC_RUN: usize = 3;
+
+/// Which secret turned up, and in what form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leak {
+    pub secret: &'static str,
+    pub form: &'static str,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.secret, self.form)
+    }
+}
+
+struct Needle {
+    secret: &'static str,
+    form: &'static str,
+    /// Lowercase when `ignore_case` is set
+    value: String,
+    ignore_case: bool,
+}
+
+pub struct SecretGuard {
+    needles: Vec<Needle>,
+    mnemonics: Vec<(&'static str, Vec<&'static str>)>,
+}
+
+impl SecretGuard {
+    pub fn new(config: &Config) -> Self {
+        let mut guard = Self {
+            needles: Vec::new(),
+            mnemonics: Vec::new(),
+        };
+
+        for (secret, value) in config.secrets() {
+            if value.len() >= MIN_SECRET_LEN {
+                guard.add(secret, value);
+            }
+        }
+        guard
+    }
+
+    fn add(&mut self, secret: &'static str, value: &str) {
+        let unprefixed = value.strip_prefix("0x").unwrap_or(value);
+
+        let bytes = match hex::decode(unprefixed) {
+            Ok(bytes) => {
+                // Keys of BIP39 entropy size can be written as a phrase
+                if (16..=32).contains(&bytes.len()) && bytes.len() % 4 == 0 {
+                    self.mnemonics.push((secret, mnemonic(&bytes)));
+                }
+                self.push(secret, "hex", unprefixed, true);
+                bytes
+            }
+            Err(_) => {
+                self.push(secret, "as configured", value, false);
+                self.push(secret, "hex", &hex::encode(value), true);
+                // Keys stored as base64 may leak as the hex of their bytes
+                if let Ok(decoded) = STANDARD.decode(value).or_else(|_| URL_SAFE.decode(value)) {
+                    if decoded.len() >= MIN_SECRET_LEN {
+                        self.push(secret, "hex", &hex::encode(decoded), true);
+                    }
+                }
+                value.as_bytes().to_vec()
+            }
+        };
+
+        for engine in [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD] {
+            self.push(secret, "base64", &engine.encode(&bytes), false);
+        }
+    }
+
+    fn push(&mut self, secret: &'static str, form: &'static str, value: &str, ignore_case: bool) {
+        let value = if ignore_case { value.to_lowercase() } else { value.to_string() };
+        if self.needles.iter().any(|needle| needle.secret == secret && needle.value == value) {
+            return;
+        }
+
+        self.needles.push(Needle {
+            secret,
+            form,
+            value,
+            ignore_case,
+        });
+    }
+
+    /// The first configured secret found in `text`, if any
+    pub fn check(&self, text: &str) -> Option<Leak> {
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let lowercase = compact.to_lowercase();
+
+        let found = self.needles.iter().find(|needle| {
+            let haystack = if needle.ignore_case { &lowercase } else { &compact };
+            haystack.contains(&needle.value)
+        });
+        if let Some(needle) = found {
+            return Some(Leak {
+                secret: needle.secret,
+                form: needle.form,
+            });
+        }
+
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphabetic())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+
+        self.mnemonics
+            .iter()
+            .find(|(_, phrase)| {
+                phrase
+                    .windows(MIN_MNEMONIC_RUN)
+                    .any(|run| words.windows(MIN_MNEMONIC_RUN).any(|window| window == run))
+            })
+            .map(|&(secret, _)| Leak {
+                secret,
+                form: "mnemonic",
+            })
+    }
+}
+
+/// Logs a blocked leak at error level under [`ALERT_TARGET`]
+pub fn alert(leak: &Leak, what: &str) {
+    error!(target: ALERT_TARGET, "Blocked {} containing {}", what, leak);
+}
+
+/// BIP39 English words encoding `entropy` (16 to 32 bytes, a multiple of 4)
+fn mnemonic(entropy: &[u8]) -> Vec<&'static str> {
+    let checksum = Sha256::digest(entropy)[0];
+    let bits: Vec<bool> = entropy
+        .iter()
+        .chain(std::iter::once(&checksum))
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+        .take(entropy.len() * 8 + entropy.len() / 4)
+        .collect();
+
+    bits.chunks(11)
+        .map(|chunk| {
+            let index = chunk.iter().fold(0, |index, &bit| (index << 1) | bit as usize);
+            English::get(index).expect("an 11-bit index is within the wordlist")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    fn guard() -> SecretGuard {
+        let mut config = Config::default();
+        config.wallet.private_key = PRIVATE_KEY.to_string();
+        config.llm.api_key = "hb-9f8e7d6c5b4a".to_string();
+        config.approval.admin_token = "short".to_string();
+        SecretGuard::new(&config)
+    }
+
+    fn leak(secret: &'static str, form: &'static str) -> Option<Leak> {
+        Some(Leak { secret, form })
+    }
+
+    #[test]
+    fn test_mnemonic_matches_bip39_vectors() {
+        assert_eq!(mnemonic(&[0; 16]).join(" "), format!("{} about", ["abandon"; 11].join(" ")));
+        assert_eq!(
+            mnemonic(&[0x7f; 32]).join(" "),
+            "legal winner thank year wave sausage worth useful legal winner thank year \
+             wave sausage worth useful legal winner thank year wave sausage worth title"
+        );
+    }
+
+    #[test]
+    fn test_private_key_forms() {
+        let guard = guard();
+        let unprefixed = &PRIVATE_KEY[2..];
+        let bytes = hex::decode(unprefixed).unwrap();
+
+        assert_eq!(guard.check(&format!("gm {}", PRIVATE_KEY)), leak("wallet.private_key", "hex"));
+        assert_eq!(guard.check(&unprefixed.to_uppercase()), leak("wallet.private_key", "hex"));
+        assert_eq!(
+            guard.check(&format!("{} {}", &unprefixed[..32], &unprefixed[32..])),
+            leak("wallet.private_key", "hex")
+        );
+        assert_eq!(guard.check(&STANDARD.encode(&bytes)), leak("wallet.private_key", "base64"));
+
+        let words = mnemonic(&bytes);
+        let phrase = format!("Remember: {}, {}, {}!", words[5], words[6], words[7]);
+        assert_eq!(guard.check(&phrase), leak("wallet.private_key", "mnemonic"));
+        assert_eq!(guard.check(&words[..2].join(" ")), None);
+    }
+
+    #[test]
+    fn test_api_key_forms() {
+        let guard = guard();
+        assert_eq!(guard.check("key=hb-9f8e7d6c5b4a"), leak("llm.api_key", "as configured"));
+        assert_eq!(guard.check(&hex::encode("hb-9f8e7d6c5b4a")), leak("llm.api_key", "hex"));
+        assert_eq!(guard.check(&URL_SAFE_NO_PAD.encode("hb-9f8e7d6c5b4a")), leak("llm.api_key", "base64"));
+
+        // Too short to guard without false alarms
+        assert_eq!(guard.check("short"), None);
+        assert_eq!(guard.check("gm, the merge was a good day"), None);
+    }
+}
@@ -1,6 +1,5 @@
-use chrono::{DateTime, Duration, TimeZone, Utc};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
 use rand::Rng;
-use std::ops::Range;
 
 use super::{Result, UtilError};
 
@@ -36,9 +35,13 @@ pub fn parse_datetime(datetime_str: &str) -> Result<DateTime<Utc>> {
         "%Y-%m-%d",
     ];
 
+    // The formats carry no offset, so they are read as UTC
     for format in formats {
-        if let Ok(dt) = DateTime::parse_from_str(datetime_str, format) {
-            return Ok(dt.with_timezone(&Utc));
+        if let Ok(dt) = NaiveDateTime::parse_from_str(datetime_str, format) {
+            return Ok(Utc.from_utc_datetime(&dt));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(datetime_str, format) {
+            return Ok(Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)));
         }
     }
 
@@ -55,8 +58,8 @@ mod tests {
         let interval = generate_posting_interval(base);
         
         // Should be within 20% of base
-        assert!(interval >= base * 0.8);
-        assert!(interval <= base * 1.2);
+        assert!(interval >= base * 8 / 10);
+        assert!(interval <= base * 12 / 10);
     }
 
     #[test]
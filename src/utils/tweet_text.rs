@@ -0,0 +1,260 @@
+//! Tweet length as X counts it, following twitter-text's v3 configuration:
+//! text is NFC-normalized, most Latin-range characters weigh 1 and everything
+//! else 2, any URL counts as 23, and an emoji sequence counts as 2 however
+//! many code points it has.
+//!
+//! URL and emoji detection are simplified, so a few inputs weigh differently
+//! than upstream: see [`BARE_URL_TLDS`] and [`is_pictographic`].
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest tweet, in weighted characters
+pub const MAX_WEIGHTED_LENGTH: usize = 280;
+
+/// Weight of a URL once X wraps it in t.co
+pub const URL_LENGTH: usize = 23;
+
+const EMOJI_WEIGHT: usize = 2;
+
+/// Code point ranges that weigh 1; everything else weighs 2
+const LIGHT_RANGES: [(u32, u32); 4] = [(0, 4351), (8192, 8205), (8208, 8223), (8242, 8247)];
+
+/// TLDs recognized on URLs written without a scheme. Upstream accepts every
+/// IANA TLD, so `example.shop` is a URL there and plain text here. It also
+/// only links a bare `name.cc` country-code domain (other than `.co` and
+/// `.tv`) when a path follows, where this counts `example.io` as a URL.
+const BARE_URL_TLDS: &[&str] = &[
+    "ai", "app", "co", "com", "dev", "edu", "eth", "gg", "gov", "info", "io", "ly",
+    "me", "net", "org", "so", "to", "tv", "uk", "us", "xyz",
+];
+
+/// Weighted length of `text`, compared against [`MAX_WEIGHTED_LENGTH`]
+pub fn weighted_length(text: &str) -> usize {
+    let chars: Vec<char> = text.nfc().collect();
+
+    let mut length = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(url) = url_at(&chars, i) {
+            length += URL_LENGTH;
+            i += url;
+        } else if let Some(emoji) = emoji_at(&chars, i) {
+            length += EMOJI_WEIGHT;
+            i += emoji;
+        } else {
+            length += char_weight(chars[i]);
+            i += 1;
+        }
+    }
+    length
+}
+
+/// True when `text` fits in a single tweet
+pub fn fits(text: &str) -> bool {
+    weighted_length(text) <= MAX_WEIGHTED_LENGTH
+}
+
+/// Weight of a single character outside URLs and emoji sequences
+pub fn char_weight(c: char) -> usize {
+    let code = c as u32;
+    if LIGHT_RANGES.iter().any(|(start, end)| (*start..=*end).contains(&code)) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Length in chars of a URL starting at `i`. URLs start a word (optionally
+/// after an opening bracket or quote) and lose trailing punctuation.
+fn url_at(chars: &[char], i: usize) -> Option<usize> {
+    let starts_word = i == 0 || chars[i - 1].is_whitespace() || matches!(chars[i - 1], '(' | '[' | '"' | '\'' | '“');
+    if !starts_word {
+        return None;
+    }
+
+    let word: String = chars[i..].iter().take_while(|c| !c.is_whitespace()).collect();
+    let url = word.trim_end_matches(['.', ',', '!', '?', ':', ';', ')', ']', '"', '\'', '”']);
+
+    let lower = url.to_lowercase();
+    let (rest, has_scheme) = match lower.strip_prefix("https://").or_else(|| lower.strip_prefix("http://")) {
+        Some(rest) => (rest, true),
+        None => (lower.as_str(), false),
+    };
+
+    let host = rest.split(['/', '?', '#', ':']).next().unwrap_or_default();
+    let labels: Vec<&str> = host.split('.').collect();
+    let valid_host = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    let tld = labels.last().copied().unwrap_or_default();
+    let valid_tld = tld.len() >= 2 && tld.chars().all(char::is_alphabetic);
+
+    if valid_host && valid_tld && (has_scheme || BARE_URL_TLDS.contains(&tld)) {
+        Some(url.chars().count())
+    } else {
+        None
+    }
+}
+
+/// Length in chars of an emoji sequence starting at `i`: flags, keycaps, and
+/// pictographs with their variation selectors, skin tones, tags and ZWJ joins
+fn emoji_at(chars: &[char], i: usize) -> Option<usize> {
+    let at = |j: usize| chars.get(j).map(|c| *c as u32);
+
+    let first = at(i)?;
+    if is_regional_indicator(first) {
+        return Some(if at(i + 1).is_some_and(is_regional_indicator) { 2 } else { 1 });
+    }
+    if matches!(chars[i], '0'..='9' | '#' | '*') {
+        let keycap = if at(i + 1) == Some(0xFE0F) { i + 2 } else { i + 1 };
+        return (at(keycap) == Some(0x20E3)).then(|| keycap + 1 - i);
+    }
+    if !is_pictographic(first) {
+        return None;
+    }
+    // `©` and `®` are text unless asked to render as emoji
+    if char_weight(chars[i]) == 1 && at(i + 1) != Some(0xFE0F) {
+        return None;
+    }
+
+    let mut j = i + 1;
+    loop {
+        if at(j) == Some(0xFE0F) {
+            j += 1;
+        }
+        if at(j).is_some_and(|c| (0x1F3FB..=0x1F3FF).contains(&c)) {
+            j += 1;
+        }
+        while at(j).is_some_and(|c| (0xE0020..=0xE007F).contains(&c)) {
+            j += 1;
+        }
+        if at(j) == Some(0x200D) && at(j + 1).is_some_and(is_pictographic) {
+            j += 2;
+        } else {
+            return Some(j - i);
+        }
+    }
+}
+
+fn is_regional_indicator(c: u32) -> bool {
+    (0x1F1E6..=0x1F1FF).contains(&c)
+}
+
+/// Broad ranges rather than the emoji data upstream matches against, so a
+/// non-emoji symbol from these blocks still absorbs a following selector, skin
+/// tone or ZWJ join, as does any pictograph; upstream only merges recommended
+/// sequences and weighs the rest code point by code point.
+fn is_pictographic(c: u32) -> bool {
+    matches!(
+        c,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139
+            | 0x2194..=0x21AA
+            | 0x231A..=0x23FF
+            | 0x24C2
+            | 0x25AA..=0x25FE
+            | 0x2600..=0x27BF
+            | 0x2934..=0x2935
+            | 0x2B05..=0x2B55
+            | 0x3030 | 0x303D | 0x3297 | 0x3299
+            | 0x1F000..=0x1FAFF
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Conformance {
+        description: String,
+        text: String,
+        weighted_length: usize,
+        valid: bool,
+    }
+
+    /// Reads the cases in `testdata/validate_weighted_length.yml`, which only
+    /// uses the handful of YAML forms twitter-text's fixtures do
+    fn conformance_cases() -> Vec<Conformance> {
+        let fixture = include_str!("testdata/validate_weighted_length.yml");
+        let string = |value: &str| serde_json::from_str::<String>(value).unwrap();
+
+        let mut cases: Vec<Conformance> = Vec::new();
+        for line in fixture.lines().map(str::trim) {
+            if let Some(description) = line.strip_prefix("- description: ") {
+                cases.push(Conformance {
+                    description: string(description),
+                    text: String::new(),
+                    weighted_length: 0,
+                    valid: false,
+                });
+            } else if let Some(case) = cases.last_mut() {
+                if let Some(text) = line.strip_prefix("text: ") {
+                    case.text = string(text);
+                } else if let Some(length) = line.strip_prefix("weightedLength: ") {
+                    case.weighted_length = length.parse().unwrap();
+                } else if let Some(valid) = line.strip_prefix("valid: ") {
+                    case.valid = valid.parse().unwrap();
+                }
+            }
+        }
+        cases
+    }
+
+    #[test]
+    fn test_conformance_fixture() {
+        let cases = conformance_cases();
+        assert!(cases.len() > 20);
+
+        for case in cases {
+            assert_eq!(weighted_length(&case.text), case.weighted_length, "{}", case.description);
+            assert_eq!(fits(&case.text), case.valid, "{}", case.description);
+        }
+    }
+
+    #[test]
+    fn test_known_deviations() {
+        // (text, weight here, weight upstream)
+        let cases = [
+            ("example.shop", 12, 23),
+            ("example.io", 23, 10),
+            ("🔥🏽", 2, 4),
+            ("🔥\u{200D}💩", 2, 5),
+            ("✎\u{FE0F}", 2, 4),
+        ];
+
+        for (text, here, upstream) in cases {
+            assert_eq!(weighted_length(text), here, "{:?}", text);
+            assert_ne!(here, upstream);
+        }
+    }
+
+    #[test]
+    fn test_cjk_weighs_double() {
+        assert_eq!(weighted_length("你好"), 4);
+        assert!(fits(&"字".repeat(140)));
+        assert!(!fits(&"字".repeat(141)));
+        assert!(fits(&"a".repeat(280)));
+        assert!(!fits(&"a".repeat(281)));
+    }
+
+    #[test]
+    fn test_urls_count_as_23() {
+        let long_url = format!("https://example.com/{}", "a".repeat(200));
+        assert_eq!(weighted_length(&long_url), 23);
+        assert_eq!(weighted_length("see example.com."), 4 + 23 + 1);
+        assert_eq!(weighted_length("(https://t.co/abc)"), 1 + 23 + 1);
+        assert_eq!(weighted_length("me@example.com"), 14);
+        assert_eq!(weighted_length("v1.2 and file.txt"), 17);
+    }
+
+    #[test]
+    fn test_emoji_sequences() {
+        assert_eq!(weighted_length("🇺🇸"), 2);
+        assert_eq!(weighted_length("1️⃣"), 2);
+        assert_eq!(weighted_length("❤️"), 2);
+        assert_eq!(weighted_length("🏴󠁧󠁢󠁳󠁣󠁴󠁿"), 2);
+        assert_eq!(weighted_length("gm 👋🏿 frens"), 11);
+    }
+}
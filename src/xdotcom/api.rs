@@ -1,197 +1,943 @@
-use reqwest::{Client as ReqwestClient, header};
-use serde_json::json;
-use tracing::{debug, error, info};
-use crate::config::{Config, TwitterConfig}; // Add full Config
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header,
+    multipart::{Form, Part},
+    Client as ReqwestClient, Method, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 
-use super::{Result, XError, types::*};
-use crate::config::TwitterConfig;
+use crate::config::{Config, RateLimitPolicy, TwitterConfig, XAuthMethod};
+use crate::db::Pool;
+use crate::utils::secret_guard::{self, SecretGuard};
 
-const API_BASE: &str = "https://api.twitter.com/2";
+use super::{
+    media::{MediaKind, UploadResponse, CHUNK_SIZE, MAX_ALT_TEXT_CHARS},
+    oauth1::OAuth1Signer,
+    oauth2::{OAuth2Session, PgTokenStore},
+    rate_limit::{endpoint_key, RateLimit, RateLimiter},
+    Result, XError, types::*,
+};
 
+/// Requests answered with 429 this many times in a row give up
+const MAX_RATE_LIMITED_ATTEMPTS: usize = 3;
+
+/// Chunked upload endpoint, relative to `upload_base`
+const MEDIA_UPLOAD_PATH: &str = "/media/upload.json";
+
+/// STATUS checks before giving up on media X is still processing
+const MAX_STATUS_POLLS: usize = 30;
+
+/// How a request authenticates with the X API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    /// App-only bearer token, enough for public reads
+    AppOnly,
+    /// OAuth 1.0a signed request acting as the agent's account
+    UserContext,
+}
+
+/// Reads that only make sense for the authenticated account
+const USER_CONTEXT_READS: &[&str] = &["/users/me", "/timelines/reverse_chronological", MEDIA_UPLOAD_PATH];
+
+/// Picks the auth mode for an endpoint: writes and account-scoped reads need
+/// user context, everything else uses the app bearer token
+pub fn auth_mode_for(method: &Method, path: &str) -> AuthMode {
+    if method != Method::GET || USER_CONTEXT_READS.iter().any(|p| path.ends_with(p)) {
+        AuthMode::UserContext
+    } else {
+        AuthMode::AppOnly
+    }
+}
+
+/// Request payload, kept as data so a rate-limited request can be resent
+enum Body {
+    Empty,
+    Json(Value),
+    /// Form-encoded fields, which OAuth 1.0a includes in the signature
+    Form(Vec<(String, String)>),
+    /// Text fields plus one binary `media` part
+    Media {
+        fields: Vec<(String, String)>,
+        media: Vec<u8>,
+    },
+}
+
+/// Credentials used for user-context requests
+#[derive(Clone)]
+enum UserAuth {
+    OAuth1(OAuth1Signer),
+    OAuth2(Arc<OAuth2Session>),
+}
+
+/// X API client. Clones share the OAuth 2.0 session, so a refresh in one is
+/// seen by all of them.
+#[derive(Clone)]
 pub struct Client {
     http: ReqwestClient,
     config: TwitterConfig,
-    auth_tokens: AuthTokens,
+    user_auth: UserAuth,
+    /// The authenticated account, looked up once via `/users/me`
+    me: Arc<OnceCell<User>>,
+    rate_limits: Arc<RateLimiter>,
+    secret_guard: Arc<SecretGuard>,
+    /// Cuts rate limit waits short so shutdown isn't held up
+    shutdown: CancellationToken,
 }
 
 impl Client {
+    /// Client for OAuth 1.0a user context; OAuth 2.0 needs [`Client::connect`]
     pub fn new(config: &Config) -> Result<Self> {
-        let twitter_config = &config.twitter_config;
-        let mut headers = header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            header::HeaderValue::from_str(&format!("Bearer {}", &config.bearer_token))
-                .map_err(|e| XError::AuthError(e.to_string()))?
-        );
+        if config.twitter.auth == XAuthMethod::OAuth2 {
+            return Err(XError::AuthError(
+                "OAuth 2.0 clients load their token from the database; use Client::connect".to_string()
+            ));
+        }
+
+        Self::build(config, UserAuth::OAuth1(OAuth1Signer::new(&config.twitter)))
+    }
+
+    /// Client using an existing OAuth 2.0 session for user context
+    pub fn with_oauth2(config: &Config, session: Arc<OAuth2Session>) -> Result<Self> {
+        Self::build(config, UserAuth::OAuth2(session))
+    }
+
+    /// Client for the configured auth method, resuming the stored OAuth 2.0
+    /// session when that is selected
+    pub async fn connect(config: &Config, pool: Pool) -> Result<Self> {
+        match config.twitter.auth {
+            XAuthMethod::OAuth1 => Self::new(config),
+            XAuthMethod::OAuth2 => {
+                let store = Arc::new(PgTokenStore::new(pool, &config.twitter)?);
+                let session = OAuth2Session::load(&config.twitter, store).await?;
+                Self::with_oauth2(config, Arc::new(session))
+            }
+        }
+    }
 
+    fn build(config: &Config, user_auth: UserAuth) -> Result<Self> {
         let http = ReqwestClient::builder()
-            .default_headers(headers)
             .build()
-            .map_err(|e| XError::NetworkError(e))?;
+            .map_err(XError::NetworkError)?;
 
         Ok(Self {
             http,
-            config: config.clone(),
-            auth_tokens: AuthTokens::default(),
+            config: config.twitter.clone(),
+            user_auth,
+            me: Arc::new(OnceCell::new()),
+            rate_limits: Arc::new(RateLimiter::default()),
+            secret_guard: Arc::new(SecretGuard::new(config)),
+            shutdown: CancellationToken::new(),
         })
     }
 
-    pub async fn post_tweet(&self, content: &str) -> Result<Tweet> {
-        let url = format!("{}/tweets", API_BASE);
-        
-        let response = self.http
-            .post(&url)
-            .json(&json!({
-                "text": content
-            }))
-            .send()
+    /// The same client, giving up on rate limit waits once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// The same client, sharing its session and rate limit budgets, with a
+    /// different policy for exhausted limits
+    pub fn with_rate_limit_policy(mut self, policy: RateLimitPolicy) -> Self {
+        self.config.rate_limit_policy = policy;
+        self
+    }
+
+    /// Remaining budget for an endpoint, known once a response has reported it
+    pub fn quota(&self, method: &Method, path: &str) -> Option<RateLimit> {
+        self.rate_limits.get(&endpoint_key(method, path), Utc::now())
+    }
+
+    /// Remaining budget for posts and replies
+    pub fn post_quota(&self) -> Option<RateLimit> {
+        self.quota(&Method::POST, "/tweets")
+    }
+
+    /// The account the client acts as
+    pub async fn me(&self) -> Result<&User> {
+        self.me
+            .get_or_try_init(|| async {
+                let response = self.send(Method::GET, "/users/me", &[], None).await?;
+                let user_response: UserResponse = parse(response).await?;
+                debug!("Authenticated as @{} ({})", user_response.data.username, user_response.data.id);
+                Ok(user_response.data)
+            })
             .await
-            .map_err(XError::NetworkError)?;
+    }
 
-        if !response.status().is_success() {
-            let error = response.text().await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(XError::ApiError(error));
-        }
+    pub async fn post_tweet(&self, content: &str) -> Result<Tweet> {
+        self.post_tweet_with_media(content, &[]).await
+    }
+
+    /// Posts a tweet with media from [`Client::upload_media`] attached
+    pub async fn post_tweet_with_media(&self, content: &str, media_ids: &[String]) -> Result<Tweet> {
+        self.create_tweet(json!({
+            "text": content
+        }), media_ids)
+        .await
+    }
+
+    pub async fn reply_to_tweet(&self, content: &str, reply_to_id: &str) -> Result<Tweet> {
+        self.reply_to_tweet_with_media(content, reply_to_id, &[]).await
+    }
 
-        let tweet: TweetResponse = response.json().await
-            .map_err(|e| XError::ParseError(e.to_string()))?;
+    pub async fn reply_to_tweet_with_media(
+        &self,
+        content: &str,
+        reply_to_id: &str,
+        media_ids: &[String],
+    ) -> Result<Tweet> {
+        self.create_tweet(json!({
+            "text": content,
+            "reply": {
+                "in_reply_to_tweet_id": reply_to_id
+            }
+        }), media_ids)
+        .await
+    }
+
+    async fn create_tweet(&self, mut body: Value, media_ids: &[String]) -> Result<Tweet> {
+        self.guard(body["text"].as_str().unwrap_or_default(), "tweet")?;
+        if !media_ids.is_empty() {
+            body["media"] = json!({ "media_ids": media_ids });
+        }
 
+        let response = self.send(Method::POST, "/tweets", &[], Some(body)).await?;
+        let tweet: TweetResponse = parse(response).await?;
         Ok(tweet.data)
     }
 
-    pub async fn reply_to_tweet(&self, content: &str, reply_to_id: &str) -> Result<Tweet> {
-        let url = format!("{}/tweets", API_BASE);
-        
-        let response = self.http
-            .post(&url)
-            .json(&json!({
-                "text": content,
-                "reply": {
-                    "in_reply_to_tweet_id": reply_to_id
+    /// Refuses to send text that contains a configured secret
+    fn guard(&self, text: &str, what: &str) -> Result<()> {
+        match self.secret_guard.check(text) {
+            Some(leak) => {
+                secret_guard::alert(&leak, what);
+                Err(XError::SecretLeak(leak.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Uploads an image or GIF in chunks (INIT, APPEND, FINALIZE, then STATUS
+    /// while X processes it) and returns its media id. The v1.1 upload
+    /// endpoint only accepts OAuth 1.0a.
+    pub async fn upload_media(&self, path: &Path, alt_text: Option<&str>) -> Result<String> {
+        if let UserAuth::OAuth2(_) = self.user_auth {
+            return Err(XError::MediaError(
+                "media upload needs OAuth 1.0a credentials (twitter.auth = \"oauth1\")".to_string()
+            ));
+        }
+        if alt_text.is_some_and(|text| text.chars().count() > MAX_ALT_TEXT_CHARS) {
+            return Err(XError::MediaError(format!(
+                "alt text is longer than {} characters", MAX_ALT_TEXT_CHARS
+            )));
+        }
+
+        if let Some(alt_text) = alt_text {
+            self.guard(alt_text, "alt text")?;
+        }
+
+        let kind = MediaKind::from_path(path)?;
+        let bytes = tokio::fs::read(path).await
+            .map_err(|e| XError::MediaError(format!("reading {}: {}", path.display(), e)))?;
+        if bytes.is_empty() || bytes.len() > kind.max_bytes {
+            return Err(XError::MediaError(format!(
+                "{} is {} bytes; {} must be 1 to {} bytes",
+                path.display(), bytes.len(), kind.media_type, kind.max_bytes
+            )));
+        }
+
+        let init: UploadResponse = self.upload_command(Body::Form(vec![
+            ("command".to_string(), "INIT".to_string()),
+            ("total_bytes".to_string(), bytes.len().to_string()),
+            ("media_type".to_string(), kind.media_type.to_string()),
+            ("media_category".to_string(), kind.category.to_string()),
+        ]))
+        .await?;
+        let media_id = init.media_id_string;
+
+        for (segment_index, chunk) in bytes.chunks(CHUNK_SIZE).enumerate() {
+            self.send_to(&self.config.upload_base, Method::POST, MEDIA_UPLOAD_PATH, &[], Body::Media {
+                fields: vec![
+                    ("command".to_string(), "APPEND".to_string()),
+                    ("media_id".to_string(), media_id.clone()),
+                    ("segment_index".to_string(), segment_index.to_string()),
+                ],
+                media: chunk.to_vec(),
+            })
+            .await?;
+        }
+
+        let finalize: UploadResponse = self.upload_command(Body::Form(vec![
+            ("command".to_string(), "FINALIZE".to_string()),
+            ("media_id".to_string(), media_id.clone()),
+        ]))
+        .await?;
+
+        let mut processing = finalize.processing_info;
+        let mut polls = 0;
+        while let Some(info) = processing {
+            match info.state.as_str() {
+                "succeeded" => break,
+                "failed" => {
+                    let reason = info.error.and_then(|error| error.message).unwrap_or_default();
+                    return Err(XError::MediaError(format!("processing {} failed: {}", media_id, reason)));
                 }
-            }))
-            .send()
-            .await
-            .map_err(XError::NetworkError)?;
+                _ if polls >= MAX_STATUS_POLLS => {
+                    return Err(XError::MediaError(format!(
+                        "{} still processing after {} status checks", media_id, polls
+                    )));
+                }
+                _ => {
+                    debug!("Media {} is {} ({:?}%)", media_id, info.state, info.progress_percent);
+                    tokio::time::sleep(Duration::from_secs(info.check_after_secs.unwrap_or(1))).await;
+                    polls += 1;
 
-        if !response.status().is_success() {
-            let error = response.text().await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(XError::ApiError(error));
+                    let query = [
+                        ("command", "STATUS".to_string()),
+                        ("media_id", media_id.clone()),
+                    ];
+                    let response = self
+                        .send_to(&self.config.upload_base, Method::GET, MEDIA_UPLOAD_PATH, &query, Body::Empty)
+                        .await?;
+                    processing = parse::<UploadResponse>(response).await?.processing_info;
+                }
+            }
         }
 
-        let tweet: TweetResponse = response.json().await
-            .map_err(|e| XError::ParseError(e.to_string()))?;
+        if let Some(alt_text) = alt_text {
+            self.send_to(&self.config.upload_base, Method::POST, "/media/metadata/create.json", &[], Body::Json(json!({
+                "media_id": media_id,
+                "alt_text": { "text": alt_text }
+            })))
+            .await?;
+        }
 
-        Ok(tweet.data)
+        info!("Uploaded {} as media {}", path.display(), media_id);
+        Ok(media_id)
     }
 
+    async fn upload_command(&self, body: Body) -> Result<UploadResponse> {
+        let response = self
+            .send_to(&self.config.upload_base, Method::POST, MEDIA_UPLOAD_PATH, &[], body)
+            .await?;
+        parse(response).await
+    }
+
+    /// The authenticated account's home timeline, newest first, following
+    /// `next_token` until `limit` tweets are collected or the timeline ends
     pub async fn get_home_timeline(&self, limit: usize) -> Result<Vec<Tweet>> {
-        let url = format!("{}/tweets/search/recent", API_BASE);
-        
-        let response = self.http
-            .get(&url)
-            .query(&[
-                ("max_results", limit.to_string()),
-                ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id".to_string()),
-                ("expansions", "author_id,referenced_tweets.id".to_string()),
+        let path = format!("/users/{}/timelines/reverse_chronological", self.me().await?.id);
+
+        let mut tweets = Vec::new();
+        let mut pagination_token: Option<String> = None;
+
+        while tweets.len() < limit {
+            let mut query = vec![
+                ("max_results", (limit - tweets.len()).clamp(5, 100).to_string()),
+                ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id,referenced_tweets".to_string()),
+                ("expansions", "author_id".to_string()),
                 ("user.fields", "username,name,description".to_string()),
-            ])
-            .send()
-            .await
-            .map_err(XError::NetworkError)?;
+            ];
+            if let Some(token) = &pagination_token {
+                query.push(("pagination_token", token.clone()));
+            }
+
+            let response = self.send(Method::GET, &path, &query, None).await?;
+            let page: TimelineResponse = parse(response).await?;
+            tweets.extend(page.expanded());
+
+            pagination_token = page.meta.and_then(|meta| meta.next_token);
+            if pagination_token.is_none() {
+                break;
+            }
+        }
+
+        tweets.truncate(limit);
+        Ok(tweets)
+    }
 
-        if !response.status().is_success() {
-            let error = response.text().await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(XError::ApiError(error));
+    /// One page of tweets mentioning `user_id`, newest first, with authors and
+    /// referenced tweets expanded. `since_id` and `until_id` are exclusive.
+    pub async fn get_mentions(
+        &self,
+        user_id: &str,
+        since_id: Option<&str>,
+        until_id: Option<&str>,
+        pagination_token: Option<&str>,
+        max_results: usize,
+    ) -> Result<TimelineResponse> {
+        let mut query = vec![
+            ("max_results", max_results.clamp(5, 100).to_string()),
+            ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id,referenced_tweets".to_string()),
+            ("expansions", "author_id,referenced_tweets.id,referenced_tweets.id.author_id".to_string()),
+            ("user.fields", "username,name,description".to_string()),
+        ];
+        if let Some(since_id) = since_id {
+            query.push(("since_id", since_id.to_string()));
         }
+        if let Some(until_id) = until_id {
+            query.push(("until_id", until_id.to_string()));
+        }
+        if let Some(token) = pagination_token {
+            query.push(("pagination_token", token.to_string()));
+        }
+
+        let path = format!("/users/{}/mentions", user_id);
+        let response = self.send(Method::GET, &path, &query, None).await?;
+        parse(response).await
+    }
+
+    /// Tweets posted by `user_id` since `start_time`, newest first
+    pub async fn get_user_tweets(
+        &self,
+        user_id: &str,
+        start_time: DateTime<Utc>,
+        max_results: usize,
+    ) -> Result<Vec<Tweet>> {
+        let query = [
+            ("start_time", start_time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)),
+            ("max_results", max_results.clamp(5, 100).to_string()),
+            ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id,referenced_tweets".to_string()),
+        ];
+
+        let path = format!("/users/{}/tweets", user_id);
+        let response = self.send(Method::GET, &path, &query, None).await?;
+        let page: TimelineResponse = parse(response).await?;
+        Ok(page.data)
+    }
 
-        let timeline: TimelineResponse = response.json().await
-            .map_err(|e| XError::ParseError(e.to_string()))?;
+    /// A single tweet with its author expanded
+    pub async fn get_tweet(&self, tweet_id: &str) -> Result<Tweet> {
+        let query = [
+            ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id,referenced_tweets".to_string()),
+            ("expansions", "author_id".to_string()),
+            ("user.fields", "username,name,description".to_string()),
+        ];
 
-        Ok(timeline.data)
+        let path = format!("/tweets/{}", tweet_id);
+        let response = self.send(Method::GET, &path, &query, None).await?;
+        let tweet: TweetResponse = parse(response).await?;
+        Ok(tweet.expanded())
+    }
+
+    /// Replies in a conversation found by `conversation_id:` recent search,
+    /// newest first. The root tweet itself is not part of the results.
+    pub async fn search_conversation(&self, conversation_id: &str, max_pages: usize) -> Result<Vec<Tweet>> {
+        let mut tweets = Vec::new();
+        let mut next_token: Option<String> = None;
+
+        for _ in 0..max_pages {
+            let mut query = vec![
+                ("query", format!("conversation_id:{}", conversation_id)),
+                ("max_results", "100".to_string()),
+                ("tweet.fields", "created_at,author_id,conversation_id,in_reply_to_user_id,referenced_tweets".to_string()),
+                ("expansions", "author_id,referenced_tweets.id,referenced_tweets.id.author_id".to_string()),
+                ("user.fields", "username,name,description".to_string()),
+            ];
+            if let Some(token) = &next_token {
+                query.push(("next_token", token.clone()));
+            }
+
+            let response = self.send(Method::GET, "/tweets/search/recent", &query, None).await?;
+            let page: TimelineResponse = parse(response).await?;
+            tweets.extend(page.expanded());
+
+            next_token = page.meta.and_then(|meta| meta.next_token);
+            if next_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(tweets)
     }
 
     pub async fn follow_user(&self, username: &str) -> Result<bool> {
         let user = self.get_user_by_username(username).await?;
-        let url = format!("{}/users/{}/following", API_BASE, self.auth_tokens.user_id);
+        let path = format!("/users/{}/following", self.me().await?.id);
 
-        let response = self.http
-            .post(&url)
-            .json(&json!({
+        let response = self
+            .send(Method::POST, &path, &[], Some(json!({
                 "target_user_id": user.id
-            }))
-            .send()
-            .await
-            .map_err(XError::NetworkError)?;
+            })))
+            .await?;
 
-        Ok(response.status().is_success())
+        let follow: FollowResponse = parse(response).await?;
+        Ok(follow.data.following)
     }
 
     async fn get_user_by_username(&self, username: &str) -> Result<User> {
-        let url = format!("{}/users/by/username/{}", API_BASE, username);
-        
-        let response = self.http
-            .get(&url)
-            .send()
-            .await
-            .map_err(XError::NetworkError)?;
+        let path = format!("/users/by/username/{}", username);
+        let response = self.send(Method::GET, &path, &[], None).await?;
 
-        if !response.status().is_success() {
-            let error = response.text().await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(XError::ApiError(error));
+        let user_response: UserResponse = parse(response).await?;
+        Ok(user_response.data)
+    }
+
+    /// Sends a request with the auth mode the endpoint requires, honoring the
+    /// endpoint's rate limit, and turns non-2xx responses into errors
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<Value>,
+    ) -> Result<reqwest::Response> {
+        let body = body.map_or(Body::Empty, Body::Json);
+        self.send_to(&self.config.api_base, method, path, query, body).await
+    }
+
+    async fn send_to(
+        &self,
+        base: &str,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Body,
+    ) -> Result<reqwest::Response> {
+        let mut url = reqwest::Url::parse(&format!("{}{}", base, path))
+            .map_err(|e| XError::ApiError(format!("invalid URL for {}: {}", path, e)))?;
+        if !query.is_empty() {
+            url.query_pairs_mut()
+                .extend_pairs(query.iter().map(|(key, value)| (*key, value.as_str())));
         }
 
-        let user_response: UserResponse = response.json().await
-            .map_err(|e| XError::ParseError(e.to_string()))?;
+        let endpoint = endpoint_key(&method, path);
+        let auth_mode = auth_mode_for(&method, path);
+        let mut attempts = 0;
 
-        Ok(user_response.data)
+        loop {
+            if let Some(reset_at) = self.rate_limits.blocked_until(&endpoint, Utc::now()) {
+                self.wait_for_reset(&endpoint, reset_at).await?;
+            }
+
+            // Rebuilt per attempt so OAuth 1.0a gets a fresh nonce and timestamp
+            let authorization = match auth_mode {
+                AuthMode::AppOnly => format!("Bearer {}", self.config.bearer_token),
+                AuthMode::UserContext => match &self.user_auth {
+                    UserAuth::OAuth1(signer) => {
+                        let form_params = match &body {
+                            Body::Form(fields) => fields.as_slice(),
+                            _ => &[],
+                        };
+                        signer.authorization_header(method.as_str(), url.as_str(), form_params)
+                    }
+                    UserAuth::OAuth2(session) => format!("Bearer {}", session.access_token().await?),
+                },
+            };
+
+            debug!("{} {} ({:?})", method, path, auth_mode);
+
+            let mut request = self.http
+                .request(method.clone(), url.clone())
+                .header(header::AUTHORIZATION, authorization);
+            request = match &body {
+                Body::Empty => request,
+                Body::Json(json) => request.json(json),
+                Body::Form(fields) => request.form(fields),
+                Body::Media { fields, media } => {
+                    let form = fields
+                        .iter()
+                        .fold(Form::new(), |form, (name, value)| form.text(name.clone(), value.clone()))
+                        .part("media", Part::bytes(media.clone()));
+                    request.multipart(form)
+                }
+            };
+
+            let response = request.send().await.map_err(XError::NetworkError)?;
+            attempts += 1;
+
+            let limit = RateLimit::from_headers(response.headers());
+            if let Some(limit) = limit {
+                self.rate_limits.record(&endpoint, limit);
+            }
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                // X sends the reset time with a 429; assume a full window if it doesn't
+                let reset_at = limit
+                    .map(|limit| limit.reset_at)
+                    .unwrap_or_else(|| Utc::now() + chrono::Duration::minutes(15));
+                self.rate_limits.record(&endpoint, RateLimit {
+                    limit: limit.map_or(0, |limit| limit.limit),
+                    remaining: 0,
+                    reset_at,
+                });
+
+                warn!("Rate limited on {} until {}", endpoint, reset_at);
+                if attempts >= MAX_RATE_LIMITED_ATTEMPTS {
+                    return Err(XError::RateLimit { endpoint, reset_at });
+                }
+                continue;
+            }
+
+            let status = response.status();
+            if !status.is_success() {
+                let error = response.text().await
+                    .unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(match status.as_u16() {
+                    // Malformed, forbidden (e.g. duplicate content) or gone
+                    400 | 403 | 404 | 409 | 422 => XError::Rejected {
+                        status: status.as_u16(),
+                        body: error,
+                    },
+                    _ => XError::ApiError(error),
+                });
+            }
+
+            return Ok(response);
+        }
+    }
+
+    /// Sleeps until the window resets under the wait policy, or fails with
+    /// `RateLimit` when failing fast, the reset is too far away or shutdown
+    /// begins while waiting
+    async fn wait_for_reset(&self, endpoint: &str, reset_at: DateTime<Utc>) -> Result<()> {
+        let wait = (reset_at - Utc::now()).to_std().unwrap_or_default();
+
+        if self.config.rate_limit_policy == RateLimitPolicy::FailFast
+            || wait > self.config.max_rate_limit_wait
+        {
+            return Err(XError::RateLimit {
+                endpoint: endpoint.to_string(),
+                reset_at,
+            });
+        }
+
+        info!("Waiting {:?} for the {} rate limit to reset", wait, endpoint);
+        tokio::select! {
+            _ = self.shutdown.cancelled() => Err(XError::RateLimit {
+                endpoint: endpoint.to_string(),
+                reset_at,
+            }),
+            _ = tokio::time::sleep(wait) => Ok(()),
+        }
     }
 }
 
+async fn parse<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    response.json().await
+        .map_err(|e| XError::ParseError(e.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockall::predicate::*;
-    use mockall::mock;
+    use crate::testing::{self, x, FakeResponse};
+
+    #[tokio::test]
+    async fn test_post_tweet() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+
+        let client = Client::new(&config).unwrap();
+        let tweet = client.post_tweet("test tweet").await.unwrap();
+
+        assert_eq!(tweet.id, "1000");
+        assert_eq!(tweet.text, "test tweet");
+        assert_eq!(x::posted_texts(&server), vec!["test tweet"]);
+    }
+
+    #[tokio::test]
+    async fn test_auth_mode_per_endpoint() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        client.post_tweet("signed").await.unwrap();
+        client.get_mentions("1", None, None, None, 10).await.unwrap();
+
+        let post = &server.requests_to("POST", "/2/tweets")[0];
+        let authorization = post.header("authorization").unwrap();
+        assert!(authorization.starts_with("OAuth "));
+        assert!(authorization.contains("oauth_consumer_key=\"test_api_key\""));
+        assert!(authorization.contains("oauth_token=\"test_access_token\""));
+
+        let mentions = &server.requests_to("GET", "/2/users/1/mentions")[0];
+        assert_eq!(mentions.header("authorization"), Some("Bearer test_bearer_token"));
+    }
+
+    #[tokio::test]
+    async fn test_follow_uses_authenticated_user_id() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        assert!(client.follow_user("someone").await.unwrap());
+        client.me().await.unwrap();
+
+        // /users/me is only looked up once
+        assert_eq!(server.requests_to("GET", "/2/users/me").len(), 1);
+        let follow = &server.requests_to("POST", "/2/users/1/following")[0];
+        assert_eq!(follow.json()["target_user_id"], "2");
+    }
+
+    fn rate_limited(reset_in_secs: i64) -> FakeResponse {
+        let reset = (Utc::now() + chrono::Duration::seconds(reset_in_secs)).timestamp();
+        FakeResponse::json(429, json!({"title": "Too Many Requests"}))
+            .with_header("x-rate-limit-limit", "50")
+            .with_header("x-rate-limit-remaining", "0")
+            .with_header("x-rate-limit-reset", &reset.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_tracks_quota_per_endpoint() {
+        let (server, _chain) = testing::fake_backends().await;
+        let reset = (Utc::now() + chrono::Duration::minutes(15)).timestamp();
+        server.script("POST", "/2/tweets", vec![FakeResponse::json(201, json!({
+            "data": {"id": "1000", "text": "hi"}
+        }))
+        .with_header("x-rate-limit-limit", "50")
+        .with_header("x-rate-limit-remaining", "49")
+        .with_header("x-rate-limit-reset", &reset.to_string())]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        assert!(client.post_quota().is_none());
+
+        client.post_tweet("hi").await.unwrap();
+        client.reply_to_tweet("hi", "1").await.unwrap();
+
+        let quota = client.post_quota().unwrap();
+        assert_eq!((quota.limit, quota.remaining), (50, 49));
+        assert_eq!(quota.reset_at.timestamp(), reset);
+        assert!(client.quota(&Method::GET, "/users/1/mentions").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_on_exhausted_budget() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/tweets", vec![rate_limited(600)]);
+
+        let mut config = testing::test_config(&server);
+        config.twitter.rate_limit_policy = RateLimitPolicy::FailFast;
+        let client = Client::new(&config).unwrap();
 
-    mock! {
-        HttpClient {}
-        async trait HttpClient {
-            async fn post(&self, url: &str, json: serde_json::Value) -> Result<reqwest::Response>;
-            async fn get(&self, url: &str) -> Result<reqwest::Response>;
+        match client.post_tweet("first").await {
+            Err(XError::RateLimit { endpoint, .. }) => assert_eq!(endpoint, "POST /tweets"),
+            other => panic!("expected RateLimit, got {:?}", other.map(|t| t.id)),
         }
+        let sent = server.requests_to("POST", "/2/tweets").len();
+
+        // The known budget fails the next call without hitting the API
+        assert!(matches!(client.post_tweet("second").await, Err(XError::RateLimit { .. })));
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), sent);
+        assert!(client.post_quota().unwrap().is_exhausted(Utc::now()));
     }
 
     #[tokio::test]
-    async fn test_post_tweet() {
-        let config = TwitterConfig {
-            api_key: "test".to_string(),
-            api_secret: "test".to_string(),
-            access_token: "test".to_string(),
-            access_secret: "test".to_string(),
-            bearer_token: "test".to_string(),
-        };
-
-        let mut mock_http = MockHttpClient::new();
-        mock_http
-            .expect_post()
-            .with(eq("/tweets"), eq(json!({"text": "test tweet"})))
-            .returning(|_, _| {
-                Ok(reqwest::Response::new(200, json!({
-                    "data": {
-                        "id": "123",
-                        "text": "test tweet"
-                    }
-                })))
-            });
+    async fn test_wait_for_reset_then_retry() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/tweets", vec![
+            rate_limited(1),
+            FakeResponse::json(201, json!({"data": {"id": "1000", "text": "patient"}})),
+        ]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        let tweet = client.post_tweet("patient").await.unwrap();
+
+        assert_eq!(tweet.id, "1000");
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cuts_rate_limit_wait_short() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/tweets", vec![rate_limited(600)]);
+
+        let shutdown = CancellationToken::new();
+        let client = Client::new(&testing::test_config(&server))
+            .unwrap()
+            .with_shutdown(shutdown.clone());
+
+        let posting = tokio::spawn(async move { client.post_tweet("interrupted").await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        shutdown.cancel();
 
+        let result = tokio::time::timeout(Duration::from_secs(5), posting).await.unwrap().unwrap();
+        assert!(matches!(result, Err(XError::RateLimit { .. })));
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_policy_gives_up_on_distant_reset() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/tweets", vec![rate_limited(3600)]);
+
+        let mut config = testing::test_config(&server);
+        config.twitter.max_rate_limit_wait = std::time::Duration::from_secs(60);
         let client = Client::new(&config).unwrap();
-        let result = client.post_tweet("test tweet").await;
-        assert!(result.is_ok());
+
+        assert!(matches!(client.post_tweet("later").await, Err(XError::RateLimit { .. })));
+        assert_eq!(server.requests_to("POST", "/2/tweets").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_home_timeline_pagination() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("GET", "/2/users/:id/timelines/reverse_chronological", vec![
+            x::timeline_page(&[("30", "42", "newest"), ("29", "43", "newer")], Some("page2")),
+            x::timeline_page(&[("28", "42", "older"), ("27", "44", "oldest")], Some("page3")),
+        ]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        let tweets = client.get_home_timeline(3).await.unwrap();
+
+        let texts: Vec<&str> = tweets.iter().map(|tweet| tweet.text.as_str()).collect();
+        assert_eq!(texts, vec!["newest", "newer", "older"]);
+        assert_eq!(tweets[0].author_handle(), "@user42");
+
+        let requests = server.requests_to("GET", "/2/users/1/timelines/reverse_chronological");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].query_param("max_results").as_deref(), Some("5"));
+        assert_eq!(requests[0].query_param("pagination_token"), None);
+        assert_eq!(requests[1].query_param("pagination_token").as_deref(), Some("page2"));
+        assert!(requests[0].header("authorization").unwrap().starts_with("OAuth "));
+    }
+
+    #[tokio::test]
+    async fn test_home_timeline_stops_at_end() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("GET", "/2/users/:id/timelines/reverse_chronological", vec![
+            x::timeline_page(&[("30", "42", "only")], None),
+        ]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        assert_eq!(client.get_home_timeline(250).await.unwrap().len(), 1);
+        assert!(client.get_home_timeline(0).await.unwrap().is_empty());
+
+        let requests = server.requests_to("GET", "/2/users/1/timelines/reverse_chronological");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].query_param("max_results").as_deref(), Some("100"));
+    }
+
+    #[tokio::test]
+    async fn test_get_mentions_query() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("GET", "/2/users/:id/mentions", vec![x::timeline_response(&[
+            ("20", "42", "@tomflash hi"),
+        ])]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        let page = client.get_mentions("1", Some("19"), Some("30"), Some("next"), 500).await.unwrap();
+
+        assert_eq!(page.expanded()[0].author_handle(), "@user42");
+
+        let request = &server.requests_to("GET", "/2/users/1/mentions")[0];
+        assert_eq!(request.query_param("since_id").as_deref(), Some("19"));
+        assert_eq!(request.query_param("until_id").as_deref(), Some("30"));
+        assert_eq!(request.query_param("pagination_token").as_deref(), Some("next"));
+        assert_eq!(request.query_param("max_results").as_deref(), Some("100"));
+        assert!(request.query_param("expansions").unwrap().contains("referenced_tweets.id"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_upload_media_in_chunks() {
+        let (server, _chain) = testing::fake_backends().await;
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        let path = testing::temp_file("loop.gif", &vec![7u8; CHUNK_SIZE + 10]);
+
+        let media_id = client.upload_media(&path, Some("A looping sunrise")).await.unwrap();
+        assert_eq!(media_id, "5000");
+
+        let uploads = server.requests_to("POST", "/1.1/media/upload.json");
+        assert_eq!(uploads.len(), 4);
+        let init = String::from_utf8_lossy(&uploads[0].body).to_string();
+        assert!(init.contains("command=INIT"));
+        assert!(init.contains(&format!("total_bytes={}", CHUNK_SIZE + 10)));
+        assert!(init.contains("media_category=tweet_gif"));
+        for (index, append) in uploads[1..3].iter().enumerate() {
+            let body = String::from_utf8_lossy(&append.body);
+            assert!(body.contains("APPEND"));
+            assert!(body.contains(&format!("name=\"segment_index\"\r\n\r\n{}", index)));
+        }
+        assert!(String::from_utf8_lossy(&uploads[3].body).contains("command=FINALIZE"));
+        assert!(uploads.iter().all(|request| request.header("authorization").unwrap().starts_with("OAuth ")));
+
+        let status = &server.requests_to("GET", "/1.1/media/upload.json")[0];
+        assert_eq!(status.query_param("command").as_deref(), Some("STATUS"));
+        assert!(status.header("authorization").unwrap().starts_with("OAuth "));
+
+        let metadata = &server.requests_to("POST", "/1.1/media/metadata/create.json")[0];
+        assert_eq!(metadata.json()["alt_text"]["text"], "A looping sunrise");
+
+        client.post_tweet_with_media("sunrise", &[media_id]).await.unwrap();
+        let post = &server.requests_to("POST", "/2/tweets")[0];
+        assert_eq!(post.json()["media"]["media_ids"], json!(["5000"]));
+    }
+
+    #[tokio::test]
+    async fn test_upload_media_rejects_bad_files() {
+        let (server, _chain) = testing::fake_backends().await;
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+
+        let empty = testing::temp_file("empty.png", &[]);
+        let video = testing::temp_file("clip.mp4", &[1, 2, 3]);
+        let image = testing::temp_file("ok.png", &[1, 2, 3]);
+        let long_alt = "a".repeat(MAX_ALT_TEXT_CHARS + 1);
+
+        for (path, alt_text) in [(&empty, None), (&video, None), (&image, Some(long_alt.as_str()))] {
+            assert!(matches!(client.upload_media(path, alt_text).await, Err(XError::MediaError(_))));
+        }
+        assert!(server.requests_to("POST", "/1.1/media/upload.json").is_empty());
+    }
+
+    #[test]
+    fn test_auth_mode_for() {
+        assert_eq!(auth_mode_for(&Method::POST, "/tweets"), AuthMode::UserContext);
+        assert_eq!(auth_mode_for(&Method::POST, "/users/1/following"), AuthMode::UserContext);
+        assert_eq!(auth_mode_for(&Method::GET, "/users/me"), AuthMode::UserContext);
+        assert_eq!(
+            auth_mode_for(&Method::GET, "/users/1/timelines/reverse_chronological"),
+            AuthMode::UserContext
+        );
+        assert_eq!(auth_mode_for(&Method::GET, "/tweets/search/recent"), AuthMode::AppOnly);
+        assert_eq!(auth_mode_for(&Method::GET, "/users/by/username/jack"), AuthMode::AppOnly);
+    }
+
+    #[tokio::test]
+    async fn test_api_error() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/tweets", vec![FakeResponse::json(403, json!({
+            "detail": "You are not allowed to create a Tweet with duplicate content."
+        }))]);
+
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        match client.post_tweet("dup").await {
+            Err(XError::Rejected { status, body }) => {
+                assert_eq!(status, 403);
+                assert!(body.contains("duplicate content"));
+            }
+            other => panic!("expected Rejected, got {:?}", other.map(|t| t.id)),
+        }
+
+        server.script("POST", "/2/tweets", vec![FakeResponse::json(503, json!({"title": "Service Unavailable"}))]);
+        assert!(matches!(client.post_tweet("later").await, Err(XError::ApiError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_blocks_configured_secrets() {
+        let (server, _chain) = testing::fake_backends().await;
+        let config = testing::test_config(&server);
+        let client = Client::new(&config).unwrap();
+
+        let leaked = format!("new wallet who dis {}", testing::chain::ANVIL_PRIVATE_KEY);
+        match client.reply_to_tweet(&leaked, "42").await {
+            Err(XError::SecretLeak(leak)) => assert_eq!(leak, "wallet.private_key (hex)"),
+            other => panic!("expected SecretLeak, got {:?}", other.map(|t| t.id)),
+        }
+        assert!(matches!(
+            client.post_tweet("bearer test_bearer_token").await,
+            Err(XError::SecretLeak(_))
+        ));
+        assert!(x::posted_texts(&server).is_empty());
+    }
+}
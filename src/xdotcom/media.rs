@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::{Result, XError};
+
+/// Bytes sent per APPEND; X accepts up to 5 MB
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Longest alt text X accepts, in characters
+pub const MAX_ALT_TEXT_CHARS: usize = 1000;
+
+/// Upload parameters for a file, picked from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaKind {
+    pub media_type: &'static str,
+    pub category: &'static str,
+    pub max_bytes: usize,
+}
+
+impl MediaKind {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+
+        let image = |media_type| Self {
+            media_type,
+            category: "tweet_image",
+            max_bytes: 5 * 1024 * 1024,
+        };
+
+        match extension.as_str() {
+            "png" => Ok(image("image/png")),
+            "jpg" | "jpeg" => Ok(image("image/jpeg")),
+            "webp" => Ok(image("image/webp")),
+            "gif" => Ok(Self {
+                media_type: "image/gif",
+                category: "tweet_gif",
+                max_bytes: 15 * 1024 * 1024,
+            }),
+            _ => Err(XError::MediaError(format!(
+                "unsupported media type for {}", path.display()
+            ))),
+        }
+    }
+}
+
+/// Response to INIT, FINALIZE and STATUS
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadResponse {
+    pub media_id_string: String,
+    pub processing_info: Option<ProcessingInfo>,
+}
+
+/// Present when X processes the media asynchronously, as it does for GIFs
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessingInfo {
+    /// `pending`, `in_progress`, `succeeded` or `failed`
+    pub state: String,
+    pub check_after_secs: Option<u64>,
+    pub progress_percent: Option<u8>,
+    pub error: Option<ProcessingError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProcessingError {
+    pub name: Option<String>,
+    pub message: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_kind_from_extension() {
+        let png = MediaKind::from_path(Path::new("art/sunrise.PNG")).unwrap();
+        assert_eq!((png.media_type, png.category), ("image/png", "tweet_image"));
+
+        let gif = MediaKind::from_path(Path::new("loop.gif")).unwrap();
+        assert_eq!((gif.media_type, gif.category), ("image/gif", "tweet_gif"));
+        assert!(gif.max_bytes > png.max_bytes);
+
+        assert!(MediaKind::from_path(Path::new("clip.mp4")).is_err());
+        assert!(MediaKind::from_path(Path::new("no_extension")).is_err());
+    }
+}
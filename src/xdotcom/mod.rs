@@ -1,9 +1,17 @@
 mod api;
-mod types;
-
-pub use api::Client;
+pub mod media;
+mod oauth1;
+pub mod oauth2;
+pub mod rate_limit;
+pub mod thread;
+pub mod types;
+
+pub use api::{AuthMode, Client};
+pub use rate_limit::RateLimit;
+pub use thread::ConversationThread;
 pub use types::*;
 
+use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,20 +19,37 @@ pub enum XError {
     #[error("Authentication failed: {0}")]
     AuthError(String),
 
-    #[error("Rate limit exceeded: {0}")]
-    RateLimit(String),
+    #[error("Rate limit exceeded for {endpoint}, resets at {reset_at}")]
+    RateLimit {
+        endpoint: String,
+        reset_at: DateTime<Utc>,
+    },
 
     #[error("API request failed: {0}")]
     ApiError(String),
 
+    /// The request itself was refused; sending it again won't help
+    #[error("Request rejected ({status}): {body}")]
+    Rejected {
+        status: u16,
+        body: String,
+    },
+
     #[error("Invalid response format: {0}")]
     ParseError(String),
 
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
 
-    #[error("Cookie authentication failed: {0}")]
-    CookieAuthError(String),
+    /// Outgoing text contained a configured secret and was not sent
+    #[error("Blocked: text contains {0}")]
+    SecretLeak(String),
+
+    #[error("Media upload failed: {0}")]
+    MediaError(String),
+
+    #[error("Token storage failed: {0}")]
+    StorageError(String),
 }
 
 pub type Result<T> = std::result::Result<T, XError>;
\ No newline at end of file
@@ -0,0 +1,269 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng};
+use sha1::Sha1;
+
+use crate::config::TwitterConfig;
+
+/// OAuth 1.0a HMAC-SHA1 request signer for user-context endpoints
+#[derive(Clone)]
+pub struct OAuth1Signer {
+    consumer_key: String,
+    consumer_secret: String,
+    token: String,
+    token_secret: String,
+}
+
+impl OAuth1Signer {
+    pub fn new(config: &TwitterConfig) -> Self {
+        Self {
+            consumer_key: config.api_key.clone(),
+            consumer_secret: config.api_secret.clone(),
+            token: config.access_token.clone(),
+            token_secret: config.access_secret.clone(),
+        }
+    }
+
+    /// Builds the `Authorization` header for a request.
+    ///
+    /// `url` may carry a query string; its parameters are signed along with
+    /// `form_params`. JSON and multipart bodies are never part of the signature.
+    pub fn authorization_header(&self, method: &str, url: &str, form_params: &[(String, String)]) -> String {
+        let nonce: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        self.authorization_header_with(method, url, form_params, &nonce, &timestamp)
+    }
+
+    fn authorization_header_with(
+        &self,
+        method: &str,
+        url: &str,
+        form_params: &[(String, String)],
+        nonce: &str,
+        timestamp: &str,
+    ) -> String {
+        let mut oauth_params = vec![
+            ("oauth_consumer_key".to_string(), self.consumer_key.clone()),
+            ("oauth_nonce".to_string(), nonce.to_string()),
+            ("oauth_signature_method".to_string(), "HMAC-SHA1".to_string()),
+            ("oauth_timestamp".to_string(), timestamp.to_string()),
+            ("oauth_token".to_string(), self.token.clone()),
+            ("oauth_version".to_string(), "1.0".to_string()),
+        ];
+
+        let (base_url, query) = url.split_once('?').unwrap_or((url, ""));
+
+        let mut params = oauth_params.clone();
+        params.extend(parse_query(query));
+        params.extend(form_params.iter().cloned());
+
+        let base_string = signature_base_string(method, base_url, &params);
+        let signature = sign(&base_string, &self.consumer_secret, &self.token_secret);
+        oauth_params.push(("oauth_signature".to_string(), signature));
+        oauth_params.sort();
+
+        let fields: Vec<String> = oauth_params
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", percent_encode(key), percent_encode(value)))
+            .collect();
+
+        format!("OAuth {}", fields.join(", "))
+    }
+}
+
+/// RFC 3986 percent-encoding as required by OAuth 1.0a: everything except
+/// unreserved characters is encoded
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Normalizes parameters (encode, then sort by key and value) and joins them
+/// with the method and base URL into the signature base string
+pub fn signature_base_string(method: &str, base_url: &str, params: &[(String, String)]) -> String {
+    let mut encoded: Vec<(String, String)> = params
+        .iter()
+        .map(|(key, value)| (percent_encode(key), percent_encode(value)))
+        .collect();
+    encoded.sort();
+
+    let parameter_string = encoded
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!(
+        "{}&{}&{}",
+        method.to_uppercase(),
+        percent_encode(base_url),
+        percent_encode(&parameter_string)
+    )
+}
+
+pub fn sign(base_string: &str, consumer_secret: &str, token_secret: &str) -> String {
+    let key = format!("{}&{}", percent_encode(consumer_secret), percent_encode(token_secret));
+    let mut mac = Hmac::<Sha1>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(base_string.as_bytes());
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer(consumer_key: &str, consumer_secret: &str, token: &str, token_secret: &str) -> OAuth1Signer {
+        OAuth1Signer {
+            consumer_key: consumer_key.to_string(),
+            consumer_secret: consumer_secret.to_string(),
+            token: token.to_string(),
+            token_secret: token_secret.to_string(),
+        }
+    }
+
+    fn header_param<'a>(header: &'a str, name: &str) -> &'a str {
+        header
+            .trim_start_matches("OAuth ")
+            .split(", ")
+            .find_map(|field| field.strip_prefix(&format!("{}=\"", name)))
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap()
+    }
+
+    // X developer docs, "Creating a signature"
+    #[test]
+    fn test_x_docs_signature() {
+        let params: Vec<(String, String)> = [
+            ("status", "Hello Ladies + Gentlemen, a signed OAuth request!"),
+            ("include_entities", "true"),
+            ("oauth_consumer_key", "xvz1evFS4wEEPTGEFPHBog"),
+            ("oauth_nonce", "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg"),
+            ("oauth_signature_method", "HMAC-SHA1"),
+            ("oauth_timestamp", "1318622958"),
+            ("oauth_token", "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb"),
+            ("oauth_version", "1.0"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let base = signature_base_string("post", "https://api.twitter.com/1.1/statuses/update.json", &params);
+        assert_eq!(
+            base,
+            "POST&https%3A%2F%2Fapi.twitter.com%2F1.1%2Fstatuses%2Fupdate.json&include_entities%3Dtrue%26oauth_consumer_key%3Dxvz1evFS4wEEPTGEFPHBog%26oauth_nonce%3DkYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg%26oauth_signature_method%3DHMAC-SHA1%26oauth_timestamp%3D1318622958%26oauth_token%3D370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb%26oauth_version%3D1.0%26status%3DHello%2520Ladies%2520%252B%2520Gentlemen%252C%2520a%2520signed%2520OAuth%2520request%2521"
+        );
+
+        let signature = sign(
+            &base,
+            "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+            "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+        );
+        assert_eq!(signature, "hCtSmYh+iHYCEqBWrE7C7hYmtUk=");
+    }
+
+    #[test]
+    fn test_x_docs_header() {
+        let signer = signer(
+            "xvz1evFS4wEEPTGEFPHBog",
+            "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+            "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb",
+            "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+        );
+
+        let header = signer.authorization_header_with(
+            "POST",
+            "https://api.twitter.com/1.1/statuses/update.json?include_entities=true",
+            &[("status".to_string(), "Hello Ladies + Gentlemen, a signed OAuth request!".to_string())],
+            "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg",
+            "1318622958",
+        );
+
+        assert!(header.starts_with("OAuth oauth_consumer_key=\"xvz1evFS4wEEPTGEFPHBog\""));
+        assert_eq!(header_param(&header, "oauth_signature"), "hCtSmYh%2BiHYCEqBWrE7C7hYmtUk%3D");
+        assert!(!header.contains("status="));
+    }
+
+    // RFC 5849 / OAuth Core 1.0 appendix example
+    #[test]
+    fn test_oauth_core_example() {
+        let signer = signer("dpf43f3p2l4k3l03", "kd94hf93k423kf44", "nnch734d00sl2jdk", "pfkkdhi9sl3r4s00");
+
+        let header = signer.authorization_header_with(
+            "GET",
+            "http://photos.example.net/photos?file=vacation.jpg&size=original",
+            &[],
+            "kllo9940pd9333jh",
+            "1191242096",
+        );
+
+        assert_eq!(header_param(&header, "oauth_signature"), "tR3%2BTy81lMeYAr%2FFid0kMTYa%2FWM%3D");
+    }
+
+    #[test]
+    fn test_percent_encoding() {
+        assert_eq!(percent_encode("Ladies + Gentlemen"), "Ladies%20%2B%20Gentlemen");
+        assert_eq!(percent_encode("An encoded string!"), "An%20encoded%20string%21");
+        assert_eq!(percent_encode("Dogs, Cats & Mice"), "Dogs%2C%20Cats%20%26%20Mice");
+        assert_eq!(percent_encode("☃"), "%E2%98%83");
+        assert_eq!(percent_encode("a-b.c_d~e"), "a-b.c_d~e");
+    }
+
+    #[test]
+    fn test_fresh_nonce_per_request() {
+        let signer = signer("key", "secret", "token", "token_secret");
+        let first = signer.authorization_header("POST", "https://api.twitter.com/2/tweets", &[]);
+        let second = signer.authorization_header("POST", "https://api.twitter.com/2/tweets", &[]);
+        assert_ne!(header_param(&first, "oauth_nonce"), header_param(&second, "oauth_nonce"));
+    }
+}
@@ -0,0 +1,591 @@
+//! OAuth 2.0 Authorization Code flow with PKCE for X user context: one-time
+//! login through a local callback listener, then refresh-token rotation with
+//! tokens persisted encrypted in Postgres.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL},
+    Engine,
+};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Nonce,
+};
+use chrono::{DateTime, Duration, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::{Client as ReqwestClient, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+use crate::config::{OAuth2Config, TwitterConfig};
+use crate::db::{models::{NewXOAuthToken, XOAuthToken}, Pool};
+
+use super::{oauth1::percent_encode, Result, XError};
+
+/// Access tokens are refreshed this long before they expire
+const REFRESH_MARGIN_SECS: i64 = 120;
+
+/// PKCE verifier and its S256 challenge
+#[derive(Debug, Clone)]
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        Self::from_verifier(random_string(64))
+    }
+
+    fn from_verifier(verifier: String) -> Self {
+        let challenge = BASE64_URL.encode(Sha256::digest(verifier.as_bytes()));
+        Self { verifier, challenge }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub scope: String,
+}
+
+impl OAuth2Token {
+    pub fn expires_soon(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at - Duration::seconds(REFRESH_MARGIN_SECS) <= now
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: i64,
+    scope: Option<String>,
+}
+
+/// The secret parts of a token, as stored in the `ciphertext` column
+#[derive(Serialize, Deserialize)]
+struct SealedTokens {
+    access_token: String,
+    refresh_token: String,
+}
+
+/// Encrypts tokens at rest with ChaCha20-Poly1305
+pub struct TokenCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl TokenCipher {
+    /// Builds the cipher from a base64-encoded 32-byte key
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let key = BASE64
+            .decode(key.trim())
+            .map_err(|e| XError::AuthError(format!("invalid token encryption key: {}", e)))?;
+
+        let cipher = ChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|_| XError::AuthError("token encryption key must be 32 bytes".to_string()))?;
+
+        Ok(Self { cipher })
+    }
+
+    /// Returns `(nonce, ciphertext)`
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| XError::StorageError("failed to encrypt token".to_string()))?;
+
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    pub fn decrypt(&self, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let nonce: [u8; 12] = nonce
+            .try_into()
+            .map_err(|_| XError::StorageError("stored token has a malformed nonce".to_string()))?;
+
+        self.cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| XError::StorageError(
+                "failed to decrypt stored token; was the encryption key changed?".to_string()
+            ))
+    }
+}
+
+/// Where the account's OAuth 2.0 token lives between runs
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    async fn load(&self) -> Result<Option<OAuth2Token>>;
+    async fn save(&self, token: &OAuth2Token) -> Result<()>;
+}
+
+/// Token store backed by the `x_oauth_tokens` table
+pub struct PgTokenStore {
+    pool: Pool,
+    cipher: TokenCipher,
+    username: String,
+}
+
+impl PgTokenStore {
+    pub fn new(pool: Pool, config: &TwitterConfig) -> Result<Self> {
+        Ok(Self {
+            pool,
+            cipher: TokenCipher::from_base64(&config.oauth2.token_encryption_key)?,
+            username: config.username.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl TokenStore for PgTokenStore {
+    async fn load(&self) -> Result<Option<OAuth2Token>> {
+        let row = XOAuthToken::find_by_username(&self.pool, &self.username)
+            .await
+            .map_err(|e| XError::StorageError(e.to_string()))?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let plaintext = self.cipher.decrypt(&row.nonce, &row.ciphertext)?;
+        let sealed: SealedTokens = serde_json::from_slice(&plaintext)
+            .map_err(|e| XError::StorageError(e.to_string()))?;
+
+        Ok(Some(OAuth2Token {
+            access_token: sealed.access_token,
+            refresh_token: sealed.refresh_token,
+            expires_at: row.expires_at,
+            scope: row.scope,
+        }))
+    }
+
+    async fn save(&self, token: &OAuth2Token) -> Result<()> {
+        let sealed = serde_json::to_vec(&SealedTokens {
+            access_token: token.access_token.clone(),
+            refresh_token: token.refresh_token.clone(),
+        })
+        .map_err(|e| XError::StorageError(e.to_string()))?;
+
+        let (nonce, ciphertext) = self.cipher.encrypt(&sealed)?;
+
+        XOAuthToken::upsert(&self.pool, NewXOAuthToken {
+            username: self.username.clone(),
+            nonce,
+            ciphertext,
+            scope: token.scope.clone(),
+            expires_at: token.expires_at,
+        })
+        .await
+        .map_err(|e| XError::StorageError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Talks to the token endpoint for code exchange and refresh
+struct TokenEndpoint {
+    http: ReqwestClient,
+    url: String,
+    settings: OAuth2Config,
+}
+
+impl TokenEndpoint {
+    fn new(config: &TwitterConfig) -> Self {
+        Self {
+            http: ReqwestClient::new(),
+            url: format!("{}/oauth2/token", config.api_base),
+            settings: config.oauth2.clone(),
+        }
+    }
+
+    async fn exchange_code(&self, code: &str, verifier: &str) -> Result<OAuth2Token> {
+        self.request(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.settings.redirect_uri.as_str()),
+            ("code_verifier", verifier),
+        ], None)
+        .await
+    }
+
+    async fn refresh(&self, token: &OAuth2Token) -> Result<OAuth2Token> {
+        self.request(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", token.refresh_token.as_str()),
+        ], Some(token))
+        .await
+    }
+
+    async fn request(&self, params: &[(&str, &str)], previous: Option<&OAuth2Token>) -> Result<OAuth2Token> {
+        let mut form = params.to_vec();
+
+        // Confidential clients authenticate with Basic auth, public ones send their id
+        let mut request = self.http.post(&self.url);
+        if self.settings.client_secret.is_empty() {
+            form.push(("client_id", self.settings.client_id.as_str()));
+        } else {
+            request = request.basic_auth(&self.settings.client_id, Some(&self.settings.client_secret));
+        }
+
+        let response = request
+            .form(&form)
+            .send()
+            .await
+            .map_err(XError::NetworkError)?;
+
+        if !response.status().is_success() {
+            let error = response.text().await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(XError::AuthError(format!("token request failed: {}", error)));
+        }
+
+        let body: TokenResponse = response.json().await
+            .map_err(|e| XError::ParseError(e.to_string()))?;
+
+        // X rotates refresh tokens, but keep the old one if none came back
+        let refresh_token = body
+            .refresh_token
+            .or_else(|| previous.map(|token| token.refresh_token.clone()))
+            .ok_or_else(|| XError::AuthError(
+                "no refresh token issued; is the offline.access scope requested?".to_string()
+            ))?;
+
+        Ok(OAuth2Token {
+            access_token: body.access_token,
+            refresh_token,
+            expires_at: Utc::now() + Duration::seconds(body.expires_in),
+            scope: body.scope
+                .or_else(|| previous.map(|token| token.scope.clone()))
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Hands out a valid access token, refreshing and persisting it before expiry
+pub struct OAuth2Session {
+    endpoint: TokenEndpoint,
+    store: Arc<dyn TokenStore>,
+    token: Mutex<OAuth2Token>,
+}
+
+impl OAuth2Session {
+    /// Resumes the session saved by the login flow
+    pub async fn load(config: &TwitterConfig, store: Arc<dyn TokenStore>) -> Result<Self> {
+        let token = store.load().await?.ok_or_else(|| XError::AuthError(format!(
+            "no OAuth 2.0 token stored for @{}; run `tomflash x-login` first",
+            config.username
+        )))?;
+
+        Ok(Self {
+            endpoint: TokenEndpoint::new(config),
+            store,
+            token: Mutex::new(token),
+        })
+    }
+
+    pub async fn access_token(&self) -> Result<String> {
+        // Held across the refresh so concurrent callers don't spend the
+        // single-use refresh token twice
+        let mut token = self.token.lock().await;
+
+        if token.expires_soon(Utc::now()) {
+            debug!("Refreshing X access token expiring at {}", token.expires_at);
+            let refreshed = self.endpoint.refresh(&token).await?;
+            self.store.save(&refreshed).await?;
+            *token = refreshed;
+        }
+
+        Ok(token.access_token.clone())
+    }
+}
+
+pub fn authorize_url(settings: &OAuth2Config, pkce: &Pkce, state: &str) -> String {
+    let params = [
+        ("response_type", "code"),
+        ("client_id", settings.client_id.as_str()),
+        ("redirect_uri", settings.redirect_uri.as_str()),
+        ("scope", &settings.scopes.join(" ")),
+        ("state", state),
+        ("code_challenge", pkce.challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    let query: Vec<String> = params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, percent_encode(value)))
+        .collect();
+
+    format!("{}?{}", settings.authorize_url, query.join("&"))
+}
+
+/// Runs the interactive login: prints the authorize URL, waits for X to
+/// redirect back to the local listener, then exchanges and stores the tokens
+pub async fn login(config: &TwitterConfig, store: &dyn TokenStore) -> Result<OAuth2Token> {
+    let redirect = Url::parse(&config.oauth2.redirect_uri)
+        .map_err(|e| XError::AuthError(format!("invalid redirect_uri: {}", e)))?;
+    let host = redirect.host_str().unwrap_or("127.0.0.1");
+    let port = redirect.port_or_known_default().unwrap_or(80);
+
+    let listener = TcpListener::bind((host, port))
+        .await
+        .map_err(|e| XError::AuthError(format!("cannot listen on {}:{}: {}", host, port, e)))?;
+
+    let pkce = Pkce::generate();
+    let state = random_string(32);
+
+    info!("Open this URL to authorize @{}:\n\n{}\n", config.username, authorize_url(&config.oauth2, &pkce, &state));
+
+    let code = wait_for_callback(&listener, redirect.path(), &state).await?;
+    let token = TokenEndpoint::new(config).exchange_code(&code, &pkce.verifier).await?;
+    store.save(&token).await?;
+
+    info!("Stored OAuth 2.0 token for @{} (scope: {})", config.username, token.scope);
+    Ok(token)
+}
+
+/// Serves the redirect target until the callback arrives and returns its code
+async fn wait_for_callback(listener: &TcpListener, path: &str, expected_state: &str) -> Result<String> {
+    loop {
+        let (mut stream, _) = listener.accept().await
+            .map_err(|e| XError::AuthError(e.to_string()))?;
+
+        let mut buffer = vec![0; 8192];
+        let read = stream.read(&mut buffer).await.unwrap_or(0);
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let target = request.split_whitespace().nth(1).unwrap_or_default();
+
+        // Browsers also ask for things like /favicon.ico
+        let Ok(url) = Url::parse(&format!("http://localhost{}", target)) else {
+            continue;
+        };
+        if url.path() != path {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await;
+            continue;
+        }
+
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+
+        let outcome = if let Some(error) = param("error") {
+            Err(XError::AuthError(format!("authorization denied: {}", error)))
+        } else if param("state").as_deref() != Some(expected_state) {
+            Err(XError::AuthError("callback state does not match".to_string()))
+        } else {
+            param("code").ok_or_else(|| XError::AuthError("callback is missing the code".to_string()))
+        };
+
+        let message = match &outcome {
+            Ok(_) => "Authorized. You can close this window.",
+            Err(_) => "Authorization failed. Check the agent logs.",
+        };
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            message.len(),
+            message
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+
+        return outcome;
+    }
+}
+
+fn random_string(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakeResponse};
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        token: std::sync::Mutex<Option<OAuth2Token>>,
+    }
+
+    #[async_trait]
+    impl TokenStore for MemoryStore {
+        async fn load(&self) -> Result<Option<OAuth2Token>> {
+            Ok(self.token.lock().unwrap().clone())
+        }
+
+        async fn save(&self, token: &OAuth2Token) -> Result<()> {
+            *self.token.lock().unwrap() = Some(token.clone());
+            Ok(())
+        }
+    }
+
+    fn token(access: &str, refresh: &str, expires_in: i64) -> OAuth2Token {
+        OAuth2Token {
+            access_token: access.to_string(),
+            refresh_token: refresh.to_string(),
+            expires_at: Utc::now() + Duration::seconds(expires_in),
+            scope: "tweet.read tweet.write offline.access".to_string(),
+        }
+    }
+
+    // RFC 7636 appendix B
+    #[test]
+    fn test_pkce_challenge() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+        assert_eq!(pkce.challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+
+        let generated = Pkce::generate();
+        assert!((43..=128).contains(&generated.verifier.len()));
+    }
+
+    #[test]
+    fn test_authorize_url() {
+        let settings = OAuth2Config {
+            client_id: "client".to_string(),
+            ..OAuth2Config::default()
+        };
+        let pkce = Pkce::from_verifier("verifier".to_string());
+
+        let url = Url::parse(&authorize_url(&settings, &pkce, "xyz")).unwrap();
+        let params: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(params["client_id"], "client");
+        assert_eq!(params["redirect_uri"], "http://127.0.0.1:8976/callback");
+        assert_eq!(params["code_challenge"], pkce.challenge);
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(params["scope"].split(' ').any(|scope| scope == "offline.access"));
+    }
+
+    #[test]
+    fn test_token_cipher_round_trip() {
+        let cipher = TokenCipher::from_base64(&BASE64.encode([1u8; 32])).unwrap();
+        let (nonce, ciphertext) = cipher.encrypt(b"refresh-token").unwrap();
+
+        assert_ne!(ciphertext, b"refresh-token");
+        assert_eq!(cipher.decrypt(&nonce, &ciphertext).unwrap(), b"refresh-token");
+
+        let other = TokenCipher::from_base64(&BASE64.encode([2u8; 32])).unwrap();
+        assert!(other.decrypt(&nonce, &ciphertext).is_err());
+        assert!(TokenCipher::from_base64(&BASE64.encode([1u8; 16])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_callback_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        let waiting = tokio::spawn(async move {
+            wait_for_callback(&listener, "/callback", "expected").await
+        });
+
+        let http = ReqwestClient::new();
+        let favicon = http.get(format!("{}/favicon.ico", base)).send().await.unwrap();
+        assert_eq!(favicon.status(), 404);
+
+        http.get(format!("{}/callback?state=expected&code=abc%2B123", base))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(waiting.await.unwrap().unwrap(), "abc+123");
+    }
+
+    #[tokio::test]
+    async fn test_callback_rejects_wrong_state() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        let waiting = tokio::spawn(async move {
+            wait_for_callback(&listener, "/callback", "expected").await
+        });
+
+        ReqwestClient::new()
+            .get(format!("{}/callback?state=forged&code=abc", base))
+            .send()
+            .await
+            .unwrap();
+
+        assert!(matches!(waiting.await.unwrap(), Err(XError::AuthError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rotates_and_persists() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("POST", "/2/oauth2/token", vec![FakeResponse::ok(json!({
+            "token_type": "bearer",
+            "expires_in": 7200,
+            "access_token": "fresh-access",
+            "refresh_token": "rotated-refresh",
+            "scope": "tweet.read tweet.write offline.access"
+        }))]);
+
+        let mut config = testing::test_config(&server).twitter;
+        config.oauth2.client_id = "client".to_string();
+
+        let store = Arc::new(MemoryStore::default());
+        store.save(&token("stale-access", "old-refresh", 30)).await.unwrap();
+
+        let session = OAuth2Session::load(&config, store.clone()).await.unwrap();
+        assert_eq!(session.access_token().await.unwrap(), "fresh-access");
+        // Still valid, so no second refresh
+        assert_eq!(session.access_token().await.unwrap(), "fresh-access");
+
+        let requests = server.requests_to("POST", "/2/oauth2/token");
+        assert_eq!(requests.len(), 1);
+        let form = String::from_utf8(requests[0].body.clone()).unwrap();
+        assert!(form.contains("grant_type=refresh_token"));
+        assert!(form.contains("refresh_token=old-refresh"));
+        assert!(form.contains("client_id=client"));
+
+        let saved = store.load().await.unwrap().unwrap();
+        assert_eq!(saved.refresh_token, "rotated-refresh");
+        assert!(!saved.expires_soon(Utc::now()));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn test_pg_store_encrypts_tokens() {
+        let (server, _chain) = testing::fake_backends().await;
+        let mut config = testing::test_config(&server);
+        let pool = testing::test_pool(&config).await;
+
+        config.twitter.username = format!("oauth-{}", uuid::Uuid::new_v4());
+        config.twitter.oauth2.token_encryption_key = BASE64.encode([3u8; 32]);
+        let store = PgTokenStore::new(pool.clone(), &config.twitter).unwrap();
+
+        assert!(store.load().await.unwrap().is_none());
+
+        store.save(&token("access-1", "refresh-1", 3600)).await.unwrap();
+        let rotated = token("access-2", "refresh-2", 7200);
+        store.save(&rotated).await.unwrap();
+
+        let loaded = store.load().await.unwrap().unwrap();
+        assert_eq!(loaded.refresh_token, "refresh-2");
+        assert_eq!(loaded.expires_at.timestamp(), rotated.expires_at.timestamp());
+
+        let row = XOAuthToken::find_by_username(&pool, &config.twitter.username)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&row.ciphertext).contains("refresh-2"));
+    }
+
+    #[tokio::test]
+    async fn test_load_without_token() {
+        let config = TwitterConfig::default();
+        let result = OAuth2Session::load(&config, Arc::new(MemoryStore::default())).await;
+        assert!(matches!(result, Err(XError::AuthError(message)) if message.contains("x-login")));
+    }
+}
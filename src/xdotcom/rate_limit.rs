@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::{header::HeaderMap, Method};
+
+/// Budget for one endpoint, as reported by the `x-rate-limit-*` headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_at: DateTime<Utc>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| -> Option<i64> {
+            headers.get(name)?.to_str().ok()?.trim().parse().ok()
+        };
+
+        Some(Self {
+            limit: number("x-rate-limit-limit")?.try_into().ok()?,
+            remaining: number("x-rate-limit-remaining")?.try_into().ok()?,
+            reset_at: Utc.timestamp_opt(number("x-rate-limit-reset")?, 0).single()?,
+        })
+    }
+
+    /// True while the window is open and nothing is left in it
+    pub fn is_exhausted(&self, now: DateTime<Utc>) -> bool {
+        self.remaining == 0 && self.reset_at > now
+    }
+}
+
+/// Tracks the latest budget per endpoint across all requests
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    budgets: Mutex<HashMap<String, RateLimit>>,
+}
+
+impl RateLimiter {
+    pub fn record(&self, endpoint: &str, limit: RateLimit) {
+        self.budgets.lock().unwrap().insert(endpoint.to_string(), limit);
+    }
+
+    /// Budget for an endpoint, or `None` if unknown or its window has reset
+    pub fn get(&self, endpoint: &str, now: DateTime<Utc>) -> Option<RateLimit> {
+        self.budgets
+            .lock()
+            .unwrap()
+            .get(endpoint)
+            .copied()
+            .filter(|limit| limit.reset_at > now)
+    }
+
+    /// When the endpoint can be called again, if it is currently exhausted
+    pub fn blocked_until(&self, endpoint: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.get(endpoint, now)
+            .filter(|limit| limit.is_exhausted(now))
+            .map(|limit| limit.reset_at)
+    }
+}
+
+/// Groups requests the way X buckets its limits: method plus path, with ids
+/// and usernames replaced by placeholders
+pub fn endpoint_key(method: &Method, path: &str) -> String {
+    let mut segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    for i in 0..segments.len() {
+        if i > 0 && segments[i - 1] == "username" {
+            segments[i] = ":username";
+        } else if !segments[i].is_empty() && segments[i].bytes().all(|b| b.is_ascii_digit()) {
+            segments[i] = ":id";
+        }
+    }
+
+    format!("{} /{}", method, segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use reqwest::header::HeaderValue;
+
+    fn headers(limit: &str, remaining: &str, reset: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-limit", HeaderValue::from_str(limit).unwrap());
+        headers.insert("x-rate-limit-remaining", HeaderValue::from_str(remaining).unwrap());
+        headers.insert("x-rate-limit-reset", HeaderValue::from_str(reset).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let limit = RateLimit::from_headers(&headers("300", "299", "1730000000")).unwrap();
+        assert_eq!(limit.limit, 300);
+        assert_eq!(limit.remaining, 299);
+        assert_eq!(limit.reset_at.timestamp(), 1730000000);
+
+        assert!(RateLimit::from_headers(&HeaderMap::new()).is_none());
+        assert!(RateLimit::from_headers(&headers("300", "-1", "1730000000")).is_none());
+    }
+
+    #[test]
+    fn test_budget_resets_with_window() {
+        let limiter = RateLimiter::default();
+        let now = Utc::now();
+        let reset_at = now + Duration::seconds(60);
+
+        limiter.record("POST /tweets", RateLimit { limit: 50, remaining: 0, reset_at });
+
+        assert_eq!(limiter.blocked_until("POST /tweets", now), Some(reset_at));
+        assert_eq!(limiter.blocked_until("GET /users/:id/mentions", now), None);
+        assert_eq!(limiter.get("POST /tweets", reset_at), None);
+        assert_eq!(limiter.blocked_until("POST /tweets", reset_at), None);
+    }
+
+    #[test]
+    fn test_endpoint_key() {
+        assert_eq!(endpoint_key(&Method::POST, "/tweets"), "POST /tweets");
+        assert_eq!(endpoint_key(&Method::POST, "/users/123/following"), "POST /users/:id/following");
+        assert_eq!(endpoint_key(&Method::GET, "/users/by/username/jack"), "GET /users/by/username/:username");
+        assert_eq!(endpoint_key(&Method::GET, "/users/me"), "GET /users/me");
+    }
+}
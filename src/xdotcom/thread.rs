@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use tracing::{debug, warn};
+
+use super::{types::{compare_ids, Tweet}, Client, Result};
+
+/// Search pages fetched per conversation; recent search only reaches back 7 days
+const MAX_SEARCH_PAGES: usize = 2;
+
+/// A tweet in a conversation with the replies to it, oldest first
+#[derive(Debug, Clone)]
+pub struct ThreadNode {
+    pub tweet: Tweet,
+    pub depth: usize,
+    pub replies: Vec<ThreadNode>,
+}
+
+/// Reply tree of one conversation, rebuilt from `replied_to` references
+#[derive(Debug, Clone)]
+pub struct ConversationThread {
+    pub conversation_id: String,
+    /// The conversation's first tweet, plus any reply whose parent wasn't found
+    pub roots: Vec<ThreadNode>,
+}
+
+impl ConversationThread {
+    /// Fetches the conversation `tweet` belongs to: the replies recent search
+    /// can see, the root tweet, and `tweet` with whatever it references
+    pub async fn fetch(client: &Client, tweet: &Tweet) -> Result<Self> {
+        let conversation_id = tweet.conversation_id.clone().unwrap_or_else(|| tweet.id.clone());
+
+        let mut tweets = client.search_conversation(&conversation_id, MAX_SEARCH_PAGES).await?;
+
+        // Parents expanded from `includes` fill gaps the search index hasn't caught up on
+        let referenced: Vec<Tweet> = tweets
+            .iter()
+            .chain(std::iter::once(tweet))
+            .flat_map(|tweet| tweet.referenced.iter().cloned())
+            .collect();
+        tweets.extend(referenced);
+        tweets.push(tweet.clone());
+
+        if !tweets.iter().any(|tweet| tweet.id == conversation_id) {
+            // A deleted or protected root leaves its replies as separate branches
+            match client.get_tweet(&conversation_id).await {
+                Ok(root) => tweets.push(root),
+                Err(e) => warn!("Could not fetch root tweet {}: {}", conversation_id, e),
+            }
+        }
+
+        let thread = Self::build(&conversation_id, tweets);
+        debug!("Rebuilt conversation {} with {} tweets", conversation_id, thread.len());
+        Ok(thread)
+    }
+
+    /// Builds the tree from tweets in any order. Tweets from other
+    /// conversations are dropped and duplicates are merged.
+    pub fn build(conversation_id: &str, tweets: Vec<Tweet>) -> Self {
+        let mut by_id: HashMap<String, Tweet> = HashMap::new();
+        for tweet in tweets {
+            if tweet.conversation_id.as_deref().is_some_and(|id| id != conversation_id) {
+                continue;
+            }
+            // Keep the copy with the most detail, e.g. an expanded author
+            let keep = by_id
+                .get(&tweet.id)
+                .is_none_or(|existing| existing.author.is_none() && tweet.author.is_some());
+            if keep {
+                by_id.insert(tweet.id.clone(), tweet);
+            }
+        }
+
+        let mut roots = Vec::new();
+        let mut replies: HashMap<String, Vec<Tweet>> = HashMap::new();
+        for tweet in by_id.values() {
+            match tweet.replied_to_id().filter(|parent| by_id.contains_key(*parent)) {
+                Some(parent) => replies.entry(parent.to_string()).or_default().push(tweet.clone()),
+                None => roots.push(tweet.clone()),
+            }
+        }
+
+        roots.sort_by(|a, b| compare_ids(&a.id, &b.id));
+        let roots = roots
+            .into_iter()
+            .map(|tweet| Self::node(tweet, 0, &mut replies))
+            .collect();
+
+        Self {
+            conversation_id: conversation_id.to_string(),
+            roots,
+        }
+    }
+
+    fn node(tweet: Tweet, depth: usize, replies: &mut HashMap<String, Vec<Tweet>>) -> ThreadNode {
+        let mut children = replies.remove(&tweet.id).unwrap_or_default();
+        children.sort_by(|a, b| compare_ids(&a.id, &b.id));
+
+        ThreadNode {
+            replies: children
+                .into_iter()
+                .map(|child| Self::node(child, depth + 1, replies))
+                .collect(),
+            tweet,
+            depth,
+        }
+    }
+
+    /// Tweets in reading order: each one followed by its replies
+    pub fn tweets(&self) -> Vec<&ThreadNode> {
+        fn walk<'a>(node: &'a ThreadNode, out: &mut Vec<&'a ThreadNode>) {
+            out.push(node);
+            for reply in &node.replies {
+                walk(reply, out);
+            }
+        }
+
+        let mut out = Vec::new();
+        for root in &self.roots {
+            walk(root, &mut out);
+        }
+        out
+    }
+
+    pub fn len(&self) -> usize {
+        self.tweets().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// One line per tweet, indented by reply depth, with the tweet being
+    /// answered marked so the model knows where it is in the discussion
+    pub fn render(&self, focus_id: &str) -> String {
+        self.tweets()
+            .iter()
+            .map(|node| {
+                let marker = if node.tweet.id == focus_id { "  <- replying to this" } else { "" };
+                format!(
+                    "{}{}: {}{}",
+                    "  ".repeat(node.depth),
+                    node.tweet.author_handle(),
+                    node.tweet.text.split_whitespace().collect::<Vec<_>>().join(" "),
+                    marker
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, FakeResponse};
+    use serde_json::json;
+
+    fn tweet(id: &str, author: &str, text: &str, replied_to: Option<&str>) -> Tweet {
+        serde_json::from_value(json!({
+            "id": id,
+            "text": text,
+            "author_id": author,
+            "conversation_id": "10",
+            "referenced_tweets": replied_to.map(|parent| vec![json!({"type": "replied_to", "id": parent})]),
+            "author": {"id": author, "name": author, "username": author}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_build_orders_and_nests_replies() {
+        let thread = ConversationThread::build("10", vec![
+            tweet("14", "carol", "late reply", Some("10")),
+            tweet("12", "alice", "thanks", Some("11")),
+            tweet("10", "alice", "root", None),
+            tweet("11", "bob", "first reply", Some("10")),
+            tweet("11", "bob", "first reply", Some("10")),
+            tweet("13", "dave", "parent was deleted", Some("9")),
+        ]);
+
+        let order: Vec<(&str, usize)> = thread
+            .tweets()
+            .iter()
+            .map(|node| (node.tweet.id.as_str(), node.depth))
+            .collect();
+        assert_eq!(order, vec![("10", 0), ("11", 1), ("12", 2), ("14", 1), ("13", 0)]);
+        assert_eq!(thread.len(), 5);
+    }
+
+    #[test]
+    fn test_render_indents_and_marks_focus() {
+        let thread = ConversationThread::build("10", vec![
+            tweet("10", "alice", "gm\nfrens", None),
+            tweet("11", "bob", "@alice gm", Some("10")),
+        ]);
+
+        assert_eq!(
+            thread.render("11"),
+            "@alice: gm frens\n  @bob: @alice gm  <- replying to this"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_searches_conversation_and_root() {
+        let (server, _chain) = testing::fake_backends().await;
+        server.script("GET", "/2/tweets/search/recent", vec![FakeResponse::ok(json!({
+            "data": [
+                {"id": "12", "text": "@bob @tomflash what do you think?", "author_id": "42",
+                 "conversation_id": "10", "referenced_tweets": [{"type": "replied_to", "id": "11"}]},
+                {"id": "11", "text": "@alice agreed", "author_id": "43",
+                 "conversation_id": "10", "referenced_tweets": [{"type": "replied_to", "id": "10"}]}
+            ],
+            "includes": {"users": [
+                {"id": "42", "name": "Alice", "username": "alice"},
+                {"id": "43", "name": "Bob", "username": "bob"}
+            ]},
+            "meta": {"result_count": 2}
+        }))]);
+        server.script("GET", "/2/tweets/:id", vec![FakeResponse::ok(json!({
+            "data": {"id": "10", "text": "hot take", "author_id": "42", "conversation_id": "10"},
+            "includes": {"users": [{"id": "42", "name": "Alice", "username": "alice"}]}
+        }))]);
+
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+        let mention = tweet("12", "alice", "@bob @tomflash what do you think?", Some("11"));
+        let thread = ConversationThread::fetch(&client, &mention).await.unwrap();
+
+        assert_eq!(
+            thread.render("12"),
+            "@alice: hot take\n  @bob: @alice agreed\n    @alice: @bob @tomflash what do you think?  <- replying to this"
+        );
+
+        let search = &server.requests_to("GET", "/2/tweets/search/recent")[0];
+        assert_eq!(search.query_param("query").as_deref(), Some("conversation_id:10"));
+        assert_eq!(search.header("authorization"), Some("Bearer test_bearer_token"));
+        assert_eq!(server.requests_to("GET", "/2/tweets/10").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_without_root() {
+        let (server, _chain) = testing::fake_backends().await;
+        let client = Client::new(&testing::test_config(&server)).unwrap();
+
+        // Nothing indexed yet and the root is gone: the mention stands alone
+        let mention = tweet("12", "alice", "@tomflash hi", Some("10"));
+        let thread = ConversationThread::fetch(&client, &mention).await.unwrap();
+
+        assert_eq!(thread.render("12"), "@alice: @tomflash hi  <- replying to this");
+    }
+}
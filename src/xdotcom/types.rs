@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -10,11 +13,43 @@ pub struct Tweet {
     pub conversation_id: Option<String>,
     pub in_reply_to_user_id: Option<String>,
     pub referenced_tweets: Option<Vec<ReferencedTweet>>,
+    /// Author expanded from `includes.users`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<User>,
+    /// Replied-to, quoted and retweeted tweets expanded from `includes.tweets`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub referenced: Vec<Tweet>,
+}
+
+impl Tweet {
+    /// `@username` when the author was expanded, otherwise the author id
+    pub fn author_handle(&self) -> String {
+        match (&self.author, &self.author_id) {
+            (Some(author), _) => format!("@{}", author.username),
+            (None, Some(author_id)) => format!("@{}", author_id),
+            (None, None) => "@unknown".to_string(),
+        }
+    }
+
+    /// Id of the tweet this one replies to, if any
+    pub fn replied_to_id(&self) -> Option<&str> {
+        self.referenced_tweets
+            .iter()
+            .flatten()
+            .find(|reference| reference.type_ == "replied_to")
+            .map(|reference| reference.id.as_str())
+    }
+}
+
+/// Orders numeric tweet ids without parsing them
+pub fn compare_ids(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ReferencedTweet {
     pub id: String,
+    #[serde(rename = "type")]
     pub type_: String,
 }
 
@@ -24,20 +59,79 @@ pub struct TweetResponse {
     pub includes: Option<Includes>,
 }
 
+impl TweetResponse {
+    /// The tweet with its author and referenced tweets filled in from `includes`
+    pub fn expanded(&self) -> Tweet {
+        let timeline = TimelineResponse {
+            data: vec![self.data.clone()],
+            includes: self.includes.clone(),
+            meta: None,
+        };
+        timeline.expanded().remove(0)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct TimelineResponse {
+    /// Absent when nothing matched
+    #[serde(default)]
     pub data: Vec<Tweet>,
     pub includes: Option<Includes>,
     pub meta: Option<Meta>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl TimelineResponse {
+    /// Tweets with their authors and referenced tweets filled in from `includes`
+    pub fn expanded(&self) -> Vec<Tweet> {
+        let includes = self.includes.clone().unwrap_or_default();
+
+        let users: HashMap<&str, &User> = includes
+            .users
+            .iter()
+            .flatten()
+            .map(|user| (user.id.as_str(), user))
+            .collect();
+
+        let with_author = |tweet: &Tweet| {
+            let mut tweet = tweet.clone();
+            tweet.author = tweet
+                .author_id
+                .as_deref()
+                .and_then(|id| users.get(id))
+                .map(|user| (*user).clone());
+            tweet
+        };
+
+        let tweets: HashMap<&str, Tweet> = includes
+            .tweets
+            .iter()
+            .flatten()
+            .map(|tweet| (tweet.id.as_str(), with_author(tweet)))
+            .collect();
+
+        self.data
+            .iter()
+            .map(|tweet| {
+                let mut tweet = with_author(tweet);
+                tweet.referenced = tweet
+                    .referenced_tweets
+                    .iter()
+                    .flatten()
+                    .filter_map(|reference| tweets.get(reference.id.as_str()).cloned())
+                    .collect();
+                tweet
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Includes {
     pub users: Option<Vec<User>>,
     pub tweets: Option<Vec<Tweet>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct Meta {
     pub result_count: i32,
     pub newest_id: Option<String>,
@@ -58,19 +152,60 @@ pub struct UserResponse {
     pub data: User,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct AuthTokens {
-    pub user_id: String,
-    pub ct0: String,
-    pub auth_token: String,
+#[derive(Debug, Clone, Deserialize)]
+pub struct FollowResponse {
+    pub data: FollowData,
 }
 
-impl AuthTokens {
-    pub fn new(user_id: String, ct0: String, auth_token: String) -> Self {
-        Self {
-            user_id,
-            ct0,
-            auth_token,
-        }
+#[derive(Debug, Clone, Deserialize)]
+pub struct FollowData {
+    pub following: bool,
+    pub pending_follow: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expand_includes() {
+        let response: TimelineResponse = serde_json::from_value(json!({
+            "data": [{
+                "id": "20",
+                "text": "@tomflash what do you think?",
+                "author_id": "42",
+                "referenced_tweets": [{"type": "replied_to", "id": "10"}]
+            }],
+            "includes": {
+                "users": [
+                    {"id": "42", "name": "Alice", "username": "alice"},
+                    {"id": "1", "name": "Tom", "username": "tomflash"}
+                ],
+                "tweets": [{"id": "10", "text": "gm", "author_id": "1"}]
+            },
+            "meta": {"result_count": 1, "newest_id": "20", "oldest_id": "20"}
+        }))
+        .unwrap();
+
+        let tweets = response.expanded();
+        assert_eq!(tweets[0].author_handle(), "@alice");
+        assert_eq!(tweets[0].referenced_tweets.as_ref().unwrap()[0].type_, "replied_to");
+        assert_eq!(tweets[0].referenced[0].text, "gm");
+        assert_eq!(tweets[0].referenced[0].author_handle(), "@tomflash");
+    }
+
+    #[test]
+    fn test_compare_ids() {
+        assert!(compare_ids("9", "10").is_lt());
+        assert!(compare_ids("1850000000000000001", "1850000000000000000").is_gt());
+        assert!(compare_ids("42", "42").is_eq());
+    }
+
+    #[test]
+    fn test_empty_timeline() {
+        let response: TimelineResponse =
+            serde_json::from_value(json!({"meta": {"result_count": 0}})).unwrap();
+        assert!(response.expanded().is_empty());
     }
-}
\ No newline at end of file
+}